@@ -0,0 +1,44 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+use vox_loader::VoxLoader;
+
+/// Future returned by `VoxLoader::from_async_reader`. Reads the stream to
+/// its end without blocking, then parses the collected bytes.
+pub struct ReadVox<R> {
+    reader: R,
+    bytes: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> Future for ReadVox<R> {
+    type Output = io::Result<VoxLoader>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<VoxLoader>> {
+        let this = self.get_mut();
+        let mut chunk = [0u8; 8192];
+        loop {
+            let mut buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.reader).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) if buf.filled().is_empty() => break,
+                Poll::Ready(Ok(())) => this.bytes.extend_from_slice(buf.filled()),
+                Poll::Ready(Err(why)) => return Poll::Ready(Err(why)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        if !this.bytes.starts_with(b"VOX ") {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, "missing VOX file magic")));
+        }
+        Poll::Ready(Ok(VoxLoader::from_bytes(&this.bytes)))
+    }
+}
+
+impl VoxLoader {
+    /// Streams a `.vox` from any tokio reader, such as a socket or an
+    /// object storage download, yielding to the executor while waiting on
+    /// data.
+    pub fn from_async_reader<R: AsyncRead + Unpin>(reader: R) -> ReadVox<R> {
+        ReadVox { reader, bytes: Vec::new() }
+    }
+}
@@ -0,0 +1,220 @@
+use std::f32::consts::FRAC_PI_2;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, RenderAssetUsages};
+use bevy::mesh::{Indices, PrimitiveTopology};
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::AsyncRead;
+use bevy::tasks::ConditionalSendFuture;
+use mesher::{self, IndexBuffer, VoxMesh};
+use model::Model;
+use scene::{SceneGraph, SceneNode};
+use vox_loader::VoxLoader;
+
+/// Registers `VoxScene` and the `.vox` asset loader, so that
+/// `asset_server.load("model.vox")` yields a `Handle<VoxScene>`.
+pub struct VoxPlugin;
+
+impl Plugin for VoxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<VoxScene>().register_asset_loader(VoxAssetLoader);
+    }
+}
+
+/// A loaded `.vox` file. Every model is greedy meshed into a labeled
+/// `Mesh` sub-asset (`"model.vox#Model0"`, ...) sharing one vertex colored
+/// `StandardMaterial` (`"model.vox#Material"`). The scene graph is kept as
+/// a node hierarchy, skipping hidden layers; files without one get a node
+/// per model.
+#[derive(Asset, TypePath, Debug)]
+pub struct VoxScene {
+    pub meshes: Vec<Handle<Mesh>>,
+    pub material: Handle<StandardMaterial>,
+    pub nodes: Vec<VoxSceneNode>,
+    /// Index of the root node, which rotates MagicaVoxel's Z-up space into
+    /// Bevy's Y-up space.
+    pub root: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct VoxSceneNode {
+    pub transform: Transform,
+    pub mesh: Option<Handle<Mesh>>,
+    pub children: Vec<usize>,
+}
+
+impl VoxScene {
+    /// Spawns the node hierarchy with meshes and materials attached and
+    /// returns the root entity.
+    pub fn spawn(&self, commands: &mut Commands) -> Entity {
+        self.spawn_node(commands, self.root)
+    }
+
+    fn spawn_node(&self, commands: &mut Commands, index: usize) -> Entity {
+        let node = &self.nodes[index];
+        let mut entity = commands.spawn((node.transform, Visibility::default()));
+        if let Some(ref mesh) = node.mesh {
+            entity.insert((Mesh3d(mesh.clone()), MeshMaterial3d(self.material.clone())));
+        }
+        let id = entity.id();
+        for &child in &node.children {
+            let child = self.spawn_node(commands, child);
+            commands.entity(id).add_child(child);
+        }
+        id
+    }
+}
+
+/// Converts a mesher output into a Bevy triangle list with position,
+/// normal, UV and color attributes.
+pub fn to_bevy_mesh(mesh: VoxMesh) -> Mesh {
+    let indices = match mesh.index_buffer() {
+        IndexBuffer::U16(indices) => Indices::U16(indices),
+        IndexBuffer::U32(indices) => Indices::U32(indices),
+    };
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, mesh.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, mesh.normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, mesh.uvs)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, mesh.colors)
+        .with_inserted_indices(indices)
+}
+
+#[derive(Default, TypePath)]
+pub struct VoxAssetLoader;
+
+impl AssetLoader for VoxAssetLoader {
+    type Asset = VoxScene;
+    type Settings = ();
+    type Error = io::Error;
+
+    fn load(&self, reader: &mut dyn Reader, _settings: &(), load_context: &mut LoadContext) -> impl ConditionalSendFuture<Output = io::Result<VoxScene>> {
+        LoadVox { reader, bytes: Vec::new(), load_context }
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vox"]
+    }
+}
+
+/// Reads the whole asset, then builds the scene once the bytes are in.
+struct LoadVox<'a, 'b, 'c> {
+    reader: &'a mut dyn Reader,
+    bytes: Vec<u8>,
+    load_context: &'b mut LoadContext<'c>,
+}
+
+impl<'a, 'b, 'c> Future for LoadVox<'a, 'b, 'c> {
+    type Output = io::Result<VoxScene>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<VoxScene>> {
+        let this = self.get_mut();
+        let mut chunk = [0u8; 4096];
+        loop {
+            match Pin::new(&mut *this.reader).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(length)) => this.bytes.extend_from_slice(&chunk[..length]),
+                Poll::Ready(Err(why)) => return Poll::Ready(Err(why)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        if !this.bytes.starts_with(b"VOX ") {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, "missing VOX file magic")));
+        }
+        let vl = VoxLoader::from_bytes(&this.bytes);
+        Poll::Ready(Ok(build_scene(&vl, this.load_context)))
+    }
+}
+
+fn build_scene(vl: &VoxLoader, load_context: &mut LoadContext) -> VoxScene {
+    let material = load_context.add_labeled_asset(
+        "Material",
+        StandardMaterial {
+            base_color: Color::WHITE,
+            perceptual_roughness: 1.0,
+            ..default()
+        },
+    );
+    let meshes = vl
+        .models()
+        .iter()
+        .enumerate()
+        .map(|(i, model)| load_context.add_labeled_asset(format!("Model{}", i), to_bevy_mesh(mesher::greedy(model, &vl.palette))))
+        .collect();
+    let mut scene = VoxScene { meshes, material, nodes: Vec::new(), root: 0 };
+    let mut roots = Vec::new();
+    if vl.scene().is_empty() {
+        for (i, model) in vl.models().iter().enumerate() {
+            roots.push(scene.model_node(model, i, false));
+        }
+    } else if let Some(root) = scene.scene_node(vl.scene(), vl.models(), 0, 0) {
+        roots.push(root);
+    }
+    scene.root = scene.push_node(Transform::from_rotation(Quat::from_rotation_x(-FRAC_PI_2)), None, roots);
+    scene
+}
+
+impl VoxScene {
+    fn scene_node(&mut self, scene: &SceneGraph, models: &[Model], id: u32, depth: usize) -> Option<usize> {
+        if depth > scene.nodes.len() {
+            return None;
+        }
+        match scene.node(id)? {
+            SceneNode::Transform(node) => {
+                if scene.is_hidden(node) {
+                    return None;
+                }
+                let children = self.scene_node(scene, models, node.child, depth + 1).into_iter().collect();
+                let r = node.rotation.to_matrix();
+                let t = node.translation;
+                // Rotations may be reflections, which `from_matrix` keeps as
+                // a negative scale.
+                let matrix = Mat4::from_cols_array(&[
+                    r[0][0] as f32, r[1][0] as f32, r[2][0] as f32, 0.0,
+                    r[0][1] as f32, r[1][1] as f32, r[2][1] as f32, 0.0,
+                    r[0][2] as f32, r[1][2] as f32, r[2][2] as f32, 0.0,
+                    t[0] as f32, t[1] as f32, t[2] as f32, 1.0,
+                ]);
+                Some(self.push_node(Transform::from_matrix(matrix), None, children))
+            }
+            SceneNode::Group(node) => {
+                let children = node
+                    .children
+                    .iter()
+                    .filter_map(|&child| self.scene_node(scene, models, child, depth + 1))
+                    .collect();
+                Some(self.push_node(Transform::IDENTITY, None, children))
+            }
+            SceneNode::Shape(node) => {
+                let children = node
+                    .models
+                    .iter()
+                    .filter_map(|&model| models.get(model as usize).map(|m| (model as usize, m)))
+                    .map(|(index, model)| self.model_node(model, index, true))
+                    .collect();
+                Some(self.push_node(Transform::IDENTITY, None, children))
+            }
+        }
+    }
+
+    /// Adds a node holding the mesh of model `index`. Models placed by the
+    /// scene graph are centered on their pivot the way MagicaVoxel
+    /// positions them.
+    fn model_node(&mut self, model: &Model, index: usize, centered: bool) -> usize {
+        let transform = if centered {
+            Transform::from_xyz(-((model.size.x / 2) as f32), -((model.size.y / 2) as f32), -((model.size.z / 2) as f32))
+        } else {
+            Transform::IDENTITY
+        };
+        let mesh = self.meshes.get(index).cloned();
+        self.push_node(transform, mesh, Vec::new())
+    }
+
+    fn push_node(&mut self, transform: Transform, mesh: Option<Handle<Mesh>>, children: Vec<usize>) -> usize {
+        self.nodes.push(VoxSceneNode { transform, mesh, children });
+        self.nodes.len() - 1
+    }
+}
@@ -0,0 +1,196 @@
+//! Converts between `.vox` and the other formats the library reads and
+//! writes. The formats follow the file extensions: `.vox`, `.qb`, `.obj`,
+//! `.glb`, `.usda`, or a directory of PNG slices.
+//!
+//! Usage: `vox-convert [options] <input> <output>`
+
+extern crate vox_loader;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use vox_loader::gltf::{self, GltfOptions};
+use vox_loader::slices::{self, PaletteStrategy};
+use vox_loader::{mesh, qubicle, usda};
+use vox_loader::{Axis, ColorPick, CoordinateSystem, Dither, Model, Palette, VoxLoader, VoxWriter};
+
+const USAGE: &str = "usage: vox-convert [options] <input> <output>
+
+Formats follow the extensions .vox, .qb, .obj, .glb and .usda; a path
+without an extension is a directory of PNG slices, read bottom to top
+along Z.
+
+options:
+  --scale <n>          repeat every voxel as an n*n*n block
+  --downscale <n>      merge n*n*n blocks into one voxel of their majority color
+  --up <y|z>           up axis of .obj and .glb output (default y)
+  --axis <x|y|z>       axis PNG slices are cut along (default z)
+  --palette <p>        move the colors onto `default` or the palette of a .vox file
+  --dither             dither colors that fall between entries of --palette
+  --model <n>          convert only model n";
+
+struct Options {
+    scale: u32,
+    downscale: u32,
+    y_up: bool,
+    axis: Axis,
+    palette: Option<String>,
+    dither: bool,
+    model: Option<usize>,
+    input: String,
+    output: String,
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = convert(&options) {
+        eprintln!("vox-convert: {}", message);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        scale: 1,
+        downscale: 1,
+        y_up: true,
+        axis: Axis::Z,
+        palette: None,
+        dither: false,
+        model: None,
+        input: String::new(),
+        output: String::new(),
+    };
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--scale" => options.scale = number(&value()?)?,
+            "--downscale" => options.downscale = number(&value()?)?,
+            "--up" => {
+                options.y_up = match value()?.as_str() {
+                    "y" => true,
+                    "z" => false,
+                    other => return Err(format!("unknown up axis {:?}", other)),
+                }
+            }
+            "--axis" => {
+                options.axis = match value()?.as_str() {
+                    "x" => Axis::X,
+                    "y" => Axis::Y,
+                    "z" => Axis::Z,
+                    other => return Err(format!("unknown axis {:?}", other)),
+                }
+            }
+            "--palette" => options.palette = Some(value()?),
+            "--dither" => options.dither = true,
+            "--model" => options.model = Some(number(&value()?)? as usize),
+            "-h" | "--help" => return Err(String::new()),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        return Err("expected an input and an output path".into());
+    }
+    options.output = paths.pop().unwrap();
+    options.input = paths.pop().unwrap();
+    Ok(options)
+}
+
+fn number(text: &str) -> Result<u32, String> {
+    text.parse().map_err(|_| format!("{:?} is not a number", text))
+}
+
+fn extension(path: &str) -> Option<String> {
+    Path::new(path).extension().map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let (mut models, mut palette) = read(&options.input)?;
+    if let Some(index) = options.model {
+        let model = models.get(index).cloned().ok_or(format!("there is no model {}", index))?;
+        models = vec![model];
+    }
+    for model in &mut models {
+        if options.downscale > 1 {
+            *model = model.downsampled(options.downscale, ColorPick::Majority, &palette);
+        }
+        if options.scale > 1 {
+            *model = model.upscaled(options.scale);
+        }
+    }
+    if let Some(target) = &options.palette {
+        let target = match target.as_str() {
+            "default" => Palette::default(),
+            path => VoxLoader::from_bytes(&fs::read(path).map_err(|error| format!("{}: {}", path, error))?).palette,
+        };
+        let dither = if options.dither { Dither::Ordered } else { Dither::None };
+        for model in &mut models {
+            model.requantize(&palette, &target, dither);
+        }
+        palette = target;
+    }
+    write(options, models, palette).map_err(|error| format!("{}: {}", options.output, error))
+}
+
+fn read(path: &str) -> Result<(Vec<Model>, Palette), String> {
+    let failed = |error: std::io::Error| format!("{}: {}", path, error);
+    match extension(path).as_deref() {
+        Some("vox") => {
+            let vl = VoxLoader::from_bytes(&fs::read(path).map_err(failed)?);
+            Ok((vl.models().to_vec(), vl.palette.clone()))
+        }
+        Some("qb") => {
+            let qb = qubicle::load_qb(path).map_err(failed)?;
+            Ok((qb.models().into_iter().cloned().collect(), qb.palette))
+        }
+        None => {
+            let mut paths: Vec<PathBuf> = fs::read_dir(path)
+                .map_err(failed)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+                .collect();
+            paths.sort();
+            let (model, palette) = Model::from_image_stack(&paths, PaletteStrategy::MedianCut).map_err(failed)?;
+            Ok((vec![model], palette))
+        }
+        Some(other) => Err(format!("{}: cannot read .{} files", path, other)),
+    }
+}
+
+fn write(options: &Options, models: Vec<Model>, palette: Palette) -> std::io::Result<()> {
+    let path = &options.output;
+    let single = || {
+        if models.len() > 1 {
+            eprintln!("vox-convert: writing model 0 of {}; pick another with --model", models.len());
+        }
+        models.first().cloned().unwrap_or_else(|| Model::new(vox_loader::Size { x: 0, y: 0, z: 0 }))
+    };
+    match extension(path).as_deref() {
+        Some("vox") => VoxWriter::write(&VoxLoader::from_models(models, palette), path),
+        Some("qb") => qubicle::write_qb(&models, &palette, path),
+        Some("obj") => {
+            let mut model = single();
+            if options.y_up {
+                model = model.converted(CoordinateSystem::ZUpRightHanded, CoordinateSystem::YUpRightHanded);
+            }
+            mesh::export_obj(&model, &palette, path)
+        }
+        Some("glb") => {
+            let gltf_options = GltfOptions { y_up: options.y_up, ..GltfOptions::default() };
+            gltf::export_glb(&VoxLoader::from_models(models, palette), path, &gltf_options)
+        }
+        Some("usda") => usda::export_usda(&VoxLoader::from_models(models, palette), path),
+        None => slices::export_png_slices(&single(), &palette, path, options.axis).map(|_| ()),
+        Some(other) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("cannot write .{} files", other))),
+    }
+}
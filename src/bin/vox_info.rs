@@ -0,0 +1,91 @@
+//! Prints what a `.vox` file holds: version, chunk tree, models, materials
+//! and layers, plus any warnings the parser raised.
+//!
+//! Usage: `vox-info <file.vox>...`
+
+extern crate vox_loader;
+
+use std::env;
+use std::fs;
+use std::process;
+use vox_loader::chunk::ChunkReader;
+use vox_loader::{MaterialType, VoxLoader};
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: vox-info <file.vox>...");
+        process::exit(2);
+    }
+    let mut failed = false;
+    for path in &paths {
+        if let Err(message) = info(path) {
+            eprintln!("{}: {}", path, message);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn info(path: &str) -> Result<(), String> {
+    let data = fs::read(path).map_err(|error| error.to_string())?;
+    let (version, chunks) = ChunkReader::from_file(&data).map_err(|error| error.to_string())?;
+    println!("{}", path);
+    println!("  version {}", version);
+    println!("  chunks:");
+    print_chunks(chunks, 2);
+
+    let vl = VoxLoader::from_bytes(&data);
+    println!("  models: {}", vl.models().len());
+    for (index, model) in vl.models().iter().enumerate() {
+        let stats = model.stats();
+        println!(
+            "    #{}: {}x{}x{}, {} voxels, {} colors",
+            index,
+            model.size.x,
+            model.size.y,
+            model.size.z,
+            stats.voxel_count,
+            stats.color_counts.iter().filter(|&&count| count > 0).count()
+        );
+    }
+    let materials: Vec<_> = vl.materials().values().filter(|m| m.material_type != MaterialType::Diffuse).collect();
+    println!("  materials: {} ({} non-diffuse)", vl.materials().len(), materials.len());
+    for material in materials {
+        println!("    #{}: {:?}, weight {}", material.id, material.material_type, material.weight);
+    }
+    let layers = &vl.scene().layers;
+    println!("  layers: {}", layers.len());
+    for layer in layers {
+        println!("    #{}: {:?}{}", layer.id, layer.name, if layer.hidden { " (hidden)" } else { "" });
+    }
+    if let Some(offset) = vl.truncated_at() {
+        println!("  truncated at offset {}", offset);
+    }
+    for warning in vl.warnings() {
+        println!("  warning: {}", warning);
+    }
+    Ok(())
+}
+
+fn print_chunks(chunks: ChunkReader, depth: usize) {
+    for chunk in chunks {
+        match chunk {
+            Ok(chunk) => {
+                println!(
+                    "{:indent$}{:?} at {}: {} bytes, {} bytes of children",
+                    "",
+                    chunk.id_str(),
+                    chunk.offset,
+                    chunk.content.len(),
+                    chunk.child_data.len(),
+                    indent = depth * 2
+                );
+                print_chunks(chunk.children(), depth + 1);
+            }
+            Err(error) => println!("{:indent$}{}", "", error, indent = depth * 2),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::{FromStr, SplitWhitespace};
+use model::{Model, Size, Voxel};
+
+/// A `.binvox` occupancy grid. Binvox carries no colors, so every voxel
+/// shares one palette index.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BinvoxFile {
+    pub model: Model,
+    /// Offset and uniform scale that map the unit cube of the grid back
+    /// into the voxelized mesh's space.
+    pub translate: [f32; 3],
+    pub scale: f32,
+}
+
+impl BinvoxFile {
+    /// Encodes the grid as a `.binvox`, the inverse of `read_binvox`.
+    /// Voxels of every color are written as occupied.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = self.model.size;
+        let (width, height, depth) = (size.x, size.z, size.y);
+        let mut data = format!(
+            "#binvox 1\ndim {} {} {}\ntranslate {} {} {}\nscale {}\ndata\n",
+            width, height, depth, self.translate[0], self.translate[1], self.translate[2], self.scale
+        )
+        .into_bytes();
+        let grid = self.model.to_dense_grid();
+        let mut run: Option<(u8, u8)> = None;
+        for x in 0..width {
+            for z in 0..depth {
+                for y in 0..height {
+                    let value = grid.is_solid(x as i32, (depth - 1 - z) as i32, y as i32) as u8;
+                    run = match run {
+                        Some((current, count)) if current == value && count < 255 => Some((current, count + 1)),
+                        Some((current, count)) => {
+                            data.extend_from_slice(&[current, count]);
+                            Some((value, 1))
+                        }
+                        None => Some((value, 1)),
+                    };
+                }
+            }
+        }
+        if let Some((value, count)) = run {
+            data.extend_from_slice(&[value, count]);
+        }
+        data
+    }
+}
+
+/// Wraps `model` in a `.binvox` with no translation and unit scale.
+pub fn export_binvox(model: &Model) -> Vec<u8> {
+    BinvoxFile { model: model.clone(), translate: [0.0; 3], scale: 1.0 }.to_bytes()
+}
+
+pub fn write_binvox<P: AsRef<Path>>(model: &Model, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(&export_binvox(model))
+}
+
+pub fn load_binvox<P: AsRef<Path>>(path: P, c: u8) -> io::Result<BinvoxFile> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    read_binvox(&bytes, c)
+}
+
+/// Parses a `.binvox`, filling occupied cells with palette index `c`. The
+/// run-length data walks Y fastest, then Z, then X; binvox is Y-up, so Y
+/// and Z are swapped and the new Y mirrored to stay right-handed.
+pub fn read_binvox(bytes: &[u8], c: u8) -> io::Result<BinvoxFile> {
+    let mut size = None;
+    let mut translate = [0.0; 3];
+    let mut scale = 1.0;
+    let mut offset = 0;
+    loop {
+        let end = bytes[offset..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| invalid_data("header ends before the data line".to_string()))?;
+        let line = String::from_utf8_lossy(&bytes[offset..offset + end]).trim().to_string();
+        offset += end + 1;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("#binvox") | None => {}
+            Some("dim") => {
+                let dims = parse_numbers::<u32>(words, &line)?;
+                if dims.len() != 3 || dims.iter().any(|&side| side > 256) {
+                    return Err(invalid_data(format!("bad dimensions {:?}", line)));
+                }
+                size = Some(dims);
+            }
+            Some("translate") => {
+                let values = parse_numbers::<f32>(words, &line)?;
+                if values.len() != 3 {
+                    return Err(invalid_data(format!("bad translation {:?}", line)));
+                }
+                translate = [values[0], values[1], values[2]];
+            }
+            Some("scale") => {
+                scale = *parse_numbers::<f32>(words, &line)?.first().ok_or_else(|| invalid_data(format!("bad scale {:?}", line)))?;
+            }
+            Some("data") => break,
+            Some(_) => return Err(invalid_data(format!("unexpected header line {:?}", line))),
+        }
+    }
+    let dims = size.ok_or_else(|| invalid_data("missing dim line".to_string()))?;
+    let (width, height, depth) = (dims[0], dims[1], dims[2]);
+
+    let mut model = Model::new(Size { x: width, y: depth, z: height });
+    let total = (width * height * depth) as usize;
+    let mut index = 0;
+    for pair in bytes[offset..].chunks(2) {
+        if pair.len() < 2 || index >= total {
+            break;
+        }
+        for _ in 0..pair[1] {
+            if index >= total {
+                break;
+            }
+            if pair[0] != 0 {
+                let i = index as u32;
+                let (x, z, y) = (i / (depth * height), i / height % depth, i % height);
+                model.voxels.push(Voxel { x: x as u8, y: (depth - 1 - z) as u8, z: y as u8, c });
+            }
+            index += 1;
+        }
+    }
+    if index < total {
+        return Err(invalid_data(format!("data covers {} of {} voxels", index, total)));
+    }
+    Ok(BinvoxFile { model, translate, scale })
+}
+
+fn parse_numbers<T: FromStr>(words: SplitWhitespace, line: &str) -> io::Result<Vec<T>> {
+    words
+        .map(|word| word.parse().map_err(|_| invalid_data(format!("bad number in {:?}", line))))
+        .collect()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::vec::Vec;
+use model::{Model, Size};
+
+/// A packed occupancy grid with one bit per cell, for collision and other
+/// uses that ignore colors. Bits are ordered like `VoxGrid` cells,
+/// x-fastest, then y, then z.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BitGrid {
+    pub size: Size,
+    words: Vec<u64>,
+}
+impl fmt::Debug for BitGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BitGrid {{ size: {:?}, solid: {} }}", self.size, self.count())
+    }
+}
+
+impl BitGrid {
+    pub fn new(size: Size) -> BitGrid {
+        let cells = size.x as usize * size.y as usize * size.z as usize;
+        BitGrid {
+            size,
+            words: vec![0; cells.div_ceil(64)],
+        }
+    }
+
+    fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        if x < 0 || y < 0 || z < 0 {
+            return None;
+        }
+        let (x, y, z) = (x as u32, y as u32, z as u32);
+        if x >= self.size.x || y >= self.size.y || z >= self.size.z {
+            return None;
+        }
+        Some((x + y * self.size.x + z * self.size.x * self.size.y) as usize)
+    }
+
+    /// Whether a cell is occupied; cells outside the grid are empty.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> bool {
+        self.index(x, y, z).is_some_and(|index| self.words[index / 64] >> (index % 64) & 1 != 0)
+    }
+
+    /// Sets a cell, returning false if it lies outside the grid.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, solid: bool) -> bool {
+        match self.index(x, y, z) {
+            Some(index) => {
+                let bit = 1 << (index % 64);
+                if solid {
+                    self.words[index / 64] |= bit;
+                } else {
+                    self.words[index / 64] &= !bit;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Occupancy of the six face neighbors as a mask, bits 0 to 5 standing
+    /// for +X, -X, +Y, -Y, +Z and -Z.
+    pub fn neighbors(&self, x: i32, y: i32, z: i32) -> u8 {
+        [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)]
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(dx, dy, dz))| self.get(x + dx, y + dy, z + dz))
+            .fold(0, |mask, (bit, _)| mask | 1 << bit)
+    }
+
+    /// Number of occupied cells.
+    pub fn count(&self) -> u32 {
+        self.words.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Number of occupied cells in the z slice `z`.
+    pub fn count_slice(&self, z: u32) -> u32 {
+        if z >= self.size.z {
+            return 0;
+        }
+        let slice = (self.size.x * self.size.y) as usize;
+        self.count_range(z as usize * slice, (z as usize + 1) * slice)
+    }
+
+    /// Share of cells that are occupied, from 0 to 1.
+    pub fn fill_ratio(&self) -> f32 {
+        let cells = self.size.x as u64 * self.size.y as u64 * self.size.z as u64;
+        if cells == 0 {
+            return 0.0;
+        }
+        self.count() as f32 / cells as f32
+    }
+
+    /// Popcount of the bits in `start..end`.
+    fn count_range(&self, start: usize, end: usize) -> u32 {
+        let mut count = 0;
+        let mut index = start;
+        while index < end {
+            let offset = index % 64;
+            let take = (64 - offset).min(end - index);
+            let mask = if take == 64 { u64::MAX } else { ((1u64 << take) - 1) << offset };
+            count += (self.words[index / 64] & mask).count_ones();
+            index += take;
+        }
+        count
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl Model {
+    pub fn to_bitgrid(&self) -> BitGrid {
+        let mut grid = BitGrid::new(self.size);
+        for voxel in &self.voxels {
+            grid.set(voxel.x as i32, voxel.y as i32, voxel.z as i32, true);
+        }
+        grid
+    }
+}
@@ -0,0 +1,172 @@
+use std::str;
+use std::string::String;
+use std::vec::Vec;
+use vox_loader::ParseError;
+
+/// One chunk, borrowing its content and children from the data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Chunk<'a> {
+    pub id: [u8; 4],
+    /// Offset of the chunk header in the data the reader was made from.
+    pub offset: usize,
+    pub content: &'a [u8],
+    /// The raw children; iterate them with `children()`.
+    pub child_data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// The id as text, or `""` if it is not valid UTF-8.
+    pub fn id_str(&self) -> &str {
+        str::from_utf8(&self.id).unwrap_or("")
+    }
+
+    pub fn children(&self) -> ChunkReader<'a> {
+        ChunkReader { data: self.child_data, offset: 0, base: self.offset + 12 + self.content.len(), done: false }
+    }
+}
+
+/// An owned chunk kept as raw bytes, as `VoxLoader` stores the chunks it
+/// does not understand. The id holds one char per byte of the file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RawChunk {
+    pub id: String,
+    pub content: Vec<u8>,
+    /// The encoded child chunks, exactly as they were read.
+    pub children: Vec<u8>,
+    /// The known chunk it came after, as its id and how many chunks of
+    /// that id preceded that one, or `None` if it opened MAIN. `VoxWriter`
+    /// puts it back after the same chunk.
+    pub after: Option<(String, usize)>,
+}
+
+/// Iterates the chunks laid out one after another in a byte slice, for
+/// tooling that works on the container format below `VoxLoader`. Each chunk
+/// is a four-byte id, the content length, the length of its children, the
+/// content and then the child chunks. A chunk that runs past the end of the
+/// data is returned as an error, after which iteration stops.
+#[derive(Clone, Debug)]
+pub struct ChunkReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    base: usize,
+    done: bool,
+}
+
+impl<'a> ChunkReader<'a> {
+    /// Reads chunks from the start of `data`.
+    pub fn new(data: &'a [u8]) -> ChunkReader<'a> {
+        ChunkReader { data, offset: 0, base: 0, done: false }
+    }
+
+    /// Checks the `VOX ` header of a whole file and returns its version
+    /// with a reader over the top-level chunks, usually just `MAIN`.
+    pub fn from_file(data: &'a [u8]) -> Result<(u32, ChunkReader<'a>), ParseError> {
+        if data.len() < 8 || &data[..4] != b"VOX " {
+            return Err(ParseError { message: "missing VOX file header".into() });
+        }
+        let version = read_u32(&data[4..8]);
+        Ok((version, ChunkReader { data: &data[8..], offset: 0, base: 8, done: false }))
+    }
+}
+
+impl<'a> Iterator for ChunkReader<'a> {
+    type Item = Result<Chunk<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Result<Chunk<'a>, ParseError>> {
+        if self.done || self.offset == self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.offset..];
+        let header = rest.get(..12);
+        let lengths = header.map(|header| (read_u32(&header[4..8]) as usize, read_u32(&header[8..12]) as usize));
+        let chunk = match lengths {
+            Some((length, child_length)) if length.saturating_add(child_length) <= rest.len() - 12 => {
+                let mut id = [0; 4];
+                id.copy_from_slice(&rest[..4]);
+                Chunk {
+                    id,
+                    offset: self.base + self.offset,
+                    content: &rest[12..12 + length],
+                    child_data: &rest[12 + length..12 + length + child_length],
+                }
+            }
+            _ => {
+                self.done = true;
+                let message = format!("chunk at offset {} runs past the end of the data", self.base + self.offset);
+                return Some(Err(ParseError { message }));
+            }
+        };
+        self.offset += 12 + chunk.content.len() + chunk.child_data.len();
+        Some(Ok(chunk))
+    }
+}
+
+/// Builds chunk data, filling in content and child lengths. Chunks opened
+/// with `begin` take every chunk written before the matching `end` as
+/// children.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkWriter {
+    data: Vec<u8>,
+    open: Vec<usize>,
+}
+
+impl ChunkWriter {
+    pub fn new() -> ChunkWriter {
+        ChunkWriter::default()
+    }
+
+    /// A writer that starts with the `VOX ` header and `version`.
+    pub fn with_header(version: u32) -> ChunkWriter {
+        let mut writer = ChunkWriter::new();
+        writer.data.extend_from_slice(b"VOX ");
+        writer.data.extend_from_slice(&version.to_le_bytes());
+        writer
+    }
+
+    /// Writes a chunk without children.
+    pub fn chunk(&mut self, id: &[u8; 4], content: &[u8]) {
+        self.begin(id, content);
+        self.end();
+    }
+
+    /// Opens a chunk whose children follow.
+    pub fn begin(&mut self, id: &[u8; 4], content: &[u8]) {
+        self.open.push(self.data.len());
+        self.data.extend_from_slice(id);
+        self.data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]);
+        self.data.extend_from_slice(content);
+    }
+
+    /// Writes a kept chunk with its children unchanged.
+    pub fn raw(&mut self, chunk: &RawChunk) {
+        let mut id = [b' '; 4];
+        for (byte, c) in id.iter_mut().zip(chunk.id.chars()) {
+            *byte = c as u8;
+        }
+        self.begin(&id, &chunk.content);
+        self.data.extend_from_slice(&chunk.children);
+        self.end();
+    }
+
+    /// Closes the innermost open chunk. Does nothing if none is open.
+    pub fn end(&mut self) {
+        if let Some(start) = self.open.pop() {
+            let length = read_u32(&self.data[start + 4..start + 8]) as usize;
+            let child_length = (self.data.len() - start - 12 - length) as u32;
+            self.data[start + 8..start + 12].copy_from_slice(&child_length.to_le_bytes());
+        }
+    }
+
+    /// Closes any open chunks and returns the data.
+    pub fn finish(mut self) -> Vec<u8> {
+        while !self.open.is_empty() {
+            self.end();
+        }
+        self.data
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
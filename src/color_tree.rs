@@ -0,0 +1,63 @@
+use std::vec::Vec;
+use palette::Color;
+
+/// A k-d tree over the RGB values of palette entries 1 to 255, for repeated
+/// nearest-color queries. The points are stored in tree order: every range
+/// has its node at the middle, splitting on red, green and blue in turn.
+#[derive(Clone, Debug)]
+pub(crate) struct ColorTree {
+    points: Vec<([i32; 3], u8)>,
+}
+
+impl ColorTree {
+    pub fn new(colors: &[Color]) -> ColorTree {
+        let mut points: Vec<([i32; 3], u8)> = colors
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(index, color)| ([color.r as i32, color.g as i32, color.b as i32], index as u8))
+            .collect();
+        build(&mut points, 0);
+        ColorTree { points }
+    }
+
+    /// The index whose color is closest to `rgb` by squared RGB distance,
+    /// ties going to the lower index.
+    pub fn closest(&self, rgb: [u8; 3]) -> u8 {
+        let target = [rgb[0] as i32, rgb[1] as i32, rgb[2] as i32];
+        let mut best = (i32::MAX, u8::MAX);
+        self.search(target, 0, self.points.len(), 0, &mut best);
+        best.1
+    }
+
+    fn search(&self, target: [i32; 3], lo: usize, hi: usize, depth: usize, best: &mut (i32, u8)) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let (point, index) = self.points[mid];
+        let distance = (0..3).map(|axis| (target[axis] - point[axis]).pow(2)).sum::<i32>();
+        if (distance, index) < *best {
+            *best = (distance, index);
+        }
+        let axis = depth % 3;
+        let offset = target[axis] - point[axis];
+        let (near, far) = if offset < 0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.search(target, near.0, near.1, depth + 1, best);
+        if offset * offset <= best.0 {
+            self.search(target, far.0, far.1, depth + 1, best);
+        }
+    }
+}
+
+fn build(points: &mut [([i32; 3], u8)], depth: usize) {
+    if points.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    points.sort_unstable_by_key(|&(point, index)| (point[axis], index));
+    let mid = points.len() / 2;
+    let (left, right) = points.split_at_mut(mid);
+    build(left, depth + 1);
+    build(&mut right[1..], depth + 1);
+}
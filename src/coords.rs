@@ -0,0 +1,92 @@
+use model::{Model, Size, Voxel};
+use transform::mirror;
+use scene::{multiply, Rotation, SceneGraph, SceneNode};
+
+/// The axis convention models and scene transforms are expressed in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CoordinateSystem {
+    /// MagicaVoxel's own space: X right, Y forward, Z up.
+    #[default]
+    ZUpRightHanded,
+    /// X right, Y up, Z toward the viewer, as in glTF, three.js and Godot.
+    /// MagicaVoxel's Y becomes -Z.
+    YUpRightHanded,
+    /// X right, Y up, Z forward, as in Unity. MagicaVoxel's Y becomes Z.
+    YUpLeftHanded,
+}
+
+impl CoordinateSystem {
+    /// Maps MagicaVoxel coordinates into this system, row by row.
+    fn basis(self) -> [[i32; 3]; 3] {
+        match self {
+            CoordinateSystem::ZUpRightHanded => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            CoordinateSystem::YUpRightHanded => [[1, 0, 0], [0, 0, 1], [0, -1, 0]],
+            CoordinateSystem::YUpLeftHanded => [[1, 0, 0], [0, 0, 1], [0, 1, 0]],
+        }
+    }
+
+    /// The signed permutation that takes vectors in this system to `to`.
+    pub fn matrix_to(self, to: CoordinateSystem) -> [[i32; 3]; 3] {
+        multiply(to.basis(), transpose(self.basis()))
+    }
+}
+
+impl Model {
+    /// The model with its voxel coordinates and size moved from one
+    /// coordinate system to another. Mirrored axes count from the far side,
+    /// so voxels stay within `0..size`; voxels lying outside the size are
+    /// dropped.
+    pub fn converted(&self, from: CoordinateSystem, to: CoordinateSystem) -> Model {
+        let m = from.matrix_to(to);
+        let sides = [self.size.x, self.size.y, self.size.z];
+        let mut size = [0; 3];
+        for (row, side) in m.iter().zip(size.iter_mut()) {
+            *side = sides[column(row)];
+        }
+        let voxels = self
+            .voxels
+            .iter()
+            .filter_map(|v| {
+                let old = [v.x, v.y, v.z];
+                let mut p = [0u8; 3];
+                for (row, coordinate) in m.iter().zip(p.iter_mut()) {
+                    let axis = column(row);
+                    *coordinate = if row[axis] < 0 { mirror(sides[axis], old[axis])? } else { old[axis] };
+                }
+                Some(Voxel { x: p[0], y: p[1], z: p[2], c: v.c })
+            })
+            .collect();
+        Model::from_voxels(Size { x: size[0], y: size[1], z: size[2] }, voxels)
+    }
+}
+
+impl SceneGraph {
+    /// Moves every transform's translation and rotation, keyframes
+    /// included, from one coordinate system to another.
+    pub fn convert(&mut self, from: CoordinateSystem, to: CoordinateSystem) {
+        let m = from.matrix_to(to);
+        let convert = |rotation: &mut Rotation, translation: &mut [i32; 3]| {
+            let t = *translation;
+            *translation = [0, 1, 2].map(|row| (0..3).map(|j| m[row][j] * t[j]).sum());
+            let converted = multiply(multiply(m, rotation.to_matrix()), transpose(m));
+            *rotation = Rotation::from_matrix(converted).unwrap_or(Rotation::IDENTITY);
+        };
+        for node in self.nodes.values_mut() {
+            if let SceneNode::Transform(transform) = node {
+                convert(&mut transform.rotation, &mut transform.translation);
+            }
+        }
+        for key in self.animation.transforms.values_mut().flatten() {
+            convert(&mut key.rotation, &mut key.translation);
+        }
+    }
+}
+
+/// The column of the non-zero entry of a permutation matrix row.
+fn column(row: &[i32; 3]) -> usize {
+    row.iter().position(|&entry| entry != 0).unwrap_or(0)
+}
+
+fn transpose(m: [[i32; 3]; 3]) -> [[i32; 3]; 3] {
+    [0, 1, 2].map(|row| [0, 1, 2].map(|col| m[col][row]))
+}
@@ -0,0 +1,131 @@
+use std::string::{String, ToString};
+use std::vec::Vec;
+use scene::Rotation;
+
+/// The DICT key-value encoding shared by the extended chunks: an int32 pair
+/// count followed by length-prefixed key and value strings. Entries keep
+/// their file order so a dictionary is written back the way it was read.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Dict {
+    entries: Vec<(String, String)>,
+}
+
+impl Dict {
+    pub fn new() -> Dict {
+        Dict { entries: Vec::new() }
+    }
+
+    /// Reads a dictionary from the start of `bytes`, returning it together
+    /// with the number of bytes consumed, or `None` if the data is truncated.
+    pub fn read(bytes: &[u8]) -> Option<(Dict, usize)> {
+        let mut offset = 0;
+        let num_pairs = read_int(bytes, &mut offset)?;
+        let mut dict = Dict::new();
+        for _ in 0..num_pairs {
+            let key = read_string(bytes, &mut offset)?;
+            let value = read_string(bytes, &mut offset)?;
+            dict.insert(key, value);
+        }
+        Some((dict, offset))
+    }
+
+    pub fn write(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (key, value) in &self.entries {
+            data.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            data.extend_from_slice(key.as_bytes());
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(value.as_bytes());
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.write(&mut data);
+        data
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Sets `key`, replacing an existing value in place.
+    pub fn insert<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        let key = key.into();
+        let value = value.into();
+        match self.entries.iter_mut().find(|entry| entry.0 == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|entry| entry.0 == key).map(|entry| entry.1.as_str())
+    }
+
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        self.get(key).map(|value| value.to_string())
+    }
+
+    pub fn get_i32(&self, key: &str) -> Option<i32> {
+        self.get(key).and_then(|value| value.trim().parse().ok())
+    }
+
+    pub fn get_u32(&self, key: &str) -> Option<u32> {
+        self.get(key).and_then(|value| value.trim().parse().ok())
+    }
+
+    pub fn get_f32(&self, key: &str) -> Option<f32> {
+        self.get(key).and_then(|value| value.trim().parse().ok())
+    }
+
+    /// Boolean flags such as `_hidden` are stored as "0" or "1".
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|value| value.trim() == "1")
+    }
+
+    /// Parses a space separated integer triple such as `_t`.
+    pub fn get_vec3(&self, key: &str) -> Option<[i32; 3]> {
+        parse_triple(self.get(key)?)
+    }
+
+    /// Parses a space separated float triple such as `_focus`.
+    pub fn get_vec3_f32(&self, key: &str) -> Option<[f32; 3]> {
+        parse_triple(self.get(key)?)
+    }
+
+    pub fn get_rotation(&self, key: &str) -> Option<Rotation> {
+        self.get(key).and_then(|value| value.trim().parse().ok()).map(Rotation)
+    }
+}
+
+fn parse_triple<T: ::std::str::FromStr + Copy + Default>(value: &str) -> Option<[T; 3]> {
+    let mut parts = value.split_whitespace();
+    let mut triple = [T::default(); 3];
+    for axis in triple.iter_mut() {
+        *axis = parts.next()?.parse().ok()?;
+    }
+    Some(triple)
+}
+
+fn read_int(bytes: &[u8], offset: &mut usize) -> Option<u32> {
+    let slice = bytes.get(*offset..*offset + 4)?;
+    *offset += 4;
+    Some(u32::from(slice[0]) | u32::from(slice[1]) << 8 | u32::from(slice[2]) << 16 | u32::from(slice[3]) << 24)
+}
+
+fn read_string(bytes: &[u8], offset: &mut usize) -> Option<String> {
+    let length = read_int(bytes, offset)? as usize;
+    let slice = bytes.get(*offset..offset.checked_add(length)?)?;
+    *offset += length;
+    Some(String::from_utf8_lossy(slice).into_owned())
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette};
+use chunk::{ChunkReader, ChunkWriter};
+use vox_loader::ParseError;
+
+/// Version written to the `VDIF` chunk that starts a serialized patch.
+const PATCH_VERSION: u32 = 1;
+
+/// What changed between two versions of a model, from `diff`. Voxels are
+/// listed in `(x, y, z)` order. Doubles as a patch: `Model::apply` replays
+/// it, and `to_bytes` and `from_bytes` carry it between processes.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VoxDiff {
+    /// The old and new size, if the model was resized.
+    pub size: Option<(Size, Size)>,
+    /// Voxels only the new model has.
+    pub added: Vec<Voxel>,
+    /// Voxels only the old model has, with their old color.
+    pub removed: Vec<Voxel>,
+    /// Voxels both models have, with their old color, and the new color.
+    pub recolored: Vec<(Voxel, u8)>,
+    /// Palette entries whose color changed: index, old and new color. Only
+    /// filled in by `with_palettes`.
+    pub palette: Vec<(u8, Color, Color)>,
+}
+
+/// Compares two versions of a model cell by cell. Where a model has
+/// several voxels at one coordinate, the last one counts, as in
+/// `Model::voxel_at`.
+pub fn diff(a: &Model, b: &Model) -> VoxDiff {
+    let old = cells(a);
+    let new = cells(b);
+    let mut result = VoxDiff::default();
+    if a.size != b.size {
+        result.size = Some((a.size, b.size));
+    }
+    for (&(x, y, z), &c) in &old {
+        match new.get(&(x, y, z)) {
+            None => result.removed.push(Voxel { x, y, z, c }),
+            Some(&to) if to != c => result.recolored.push((Voxel { x, y, z, c }, to)),
+            Some(_) => {}
+        }
+    }
+    for (&(x, y, z), &c) in &new {
+        if !old.contains_key(&(x, y, z)) {
+            result.added.push(Voxel { x, y, z, c });
+        }
+    }
+    result
+}
+
+impl VoxDiff {
+    /// Adds the palette entries that differ between `a` and `b`.
+    pub fn with_palettes(mut self, a: &Palette, b: &Palette) -> VoxDiff {
+        self.palette = (0..=255u8)
+            .filter(|&index| a.get(index) != b.get(index))
+            .map(|index| (index, a.get(index), b.get(index)))
+            .collect();
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.is_none() && self.added.is_empty() && self.removed.is_empty() && self.recolored.is_empty() && self.palette.is_empty()
+    }
+
+    /// Serializes the patch as a run of chunks in the `.vox` container
+    /// layout: a `VDIF` chunk holding the format version, then `SIZE`,
+    /// `ADD `, `DEL `, `RCOL` and `PALD` for whichever parts are present.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = ChunkWriter::new();
+        writer.chunk(b"VDIF", &PATCH_VERSION.to_le_bytes());
+        if let Some((from, to)) = self.size {
+            let sides = [from.x, from.y, from.z, to.x, to.y, to.z];
+            writer.chunk(b"SIZE", &sides.iter().flat_map(|side| side.to_le_bytes()).collect::<Vec<u8>>());
+        }
+        let voxels = |voxels: &[Voxel]| voxels.iter().flat_map(|v| [v.x, v.y, v.z, v.c]).collect::<Vec<u8>>();
+        if !self.added.is_empty() {
+            writer.chunk(b"ADD ", &voxels(&self.added));
+        }
+        if !self.removed.is_empty() {
+            writer.chunk(b"DEL ", &voxels(&self.removed));
+        }
+        if !self.recolored.is_empty() {
+            let content: Vec<u8> = self.recolored.iter().flat_map(|&(v, to)| [v.x, v.y, v.z, v.c, to]).collect();
+            writer.chunk(b"RCOL", &content);
+        }
+        if !self.palette.is_empty() {
+            let content: Vec<u8> = self
+                .palette
+                .iter()
+                .flat_map(|&(index, a, b)| [index, a.r, a.g, a.b, a.a, b.r, b.g, b.b, b.a])
+                .collect();
+            writer.chunk(b"PALD", &content);
+        }
+        writer.finish()
+    }
+
+    /// Reads a patch written by `to_bytes`. Chunks it does not know are
+    /// skipped, so later versions can add parts.
+    pub fn from_bytes(data: &[u8]) -> Result<VoxDiff, ParseError> {
+        let error = |message: &str| ParseError { message: message.into() };
+        let mut chunks = ChunkReader::new(data);
+        match chunks.next() {
+            Some(Ok(chunk)) if &chunk.id == b"VDIF" && chunk.content.len() == 4 => {}
+            _ => return Err(error("missing VDIF patch header")),
+        }
+        let mut diff = VoxDiff::default();
+        for chunk in chunks {
+            let chunk = chunk?;
+            let content = chunk.content;
+            let records = |width: usize| {
+                if content.len() % width == 0 {
+                    Ok(content.chunks(width))
+                } else {
+                    Err(ParseError { message: format!("{} chunk has a partial record", chunk.id_str()) })
+                }
+            };
+            let voxel = |r: &[u8]| Voxel { x: r[0], y: r[1], z: r[2], c: r[3] };
+            match &chunk.id {
+                b"SIZE" => {
+                    if content.len() != 24 {
+                        return Err(error("SIZE chunk must hold six sides"));
+                    }
+                    let side = |i: usize| u32::from_le_bytes([content[i * 4], content[i * 4 + 1], content[i * 4 + 2], content[i * 4 + 3]]);
+                    diff.size = Some((Size { x: side(0), y: side(1), z: side(2) }, Size { x: side(3), y: side(4), z: side(5) }));
+                }
+                b"ADD " => diff.added = records(4)?.map(voxel).collect(),
+                b"DEL " => diff.removed = records(4)?.map(voxel).collect(),
+                b"RCOL" => diff.recolored = records(5)?.map(|r| (voxel(r), r[4])).collect(),
+                b"PALD" => {
+                    diff.palette = records(9)?
+                        .map(|r| (r[0], Color::new(r[1], r[2], r[3], r[4]), Color::new(r[5], r[6], r[7], r[8])))
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+        Ok(diff)
+    }
+
+    /// A one-line description such as `3 added, 1 removed, 2 recolored`,
+    /// the same as the `Display` output.
+    pub fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for VoxDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let mut parts = Vec::new();
+        if let Some((from, to)) = self.size {
+            parts.push(format!("resized {}x{}x{} -> {}x{}x{}", from.x, from.y, from.z, to.x, to.y, to.z));
+        }
+        for (count, what) in [(self.added.len(), "added"), (self.removed.len(), "removed"), (self.recolored.len(), "recolored")] {
+            if count > 0 {
+                parts.push(format!("{} {}", count, what));
+            }
+        }
+        if !self.palette.is_empty() {
+            parts.push(format!("{} palette entries changed", self.palette.len()));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl Model {
+    /// Brings the model to the new side of `diff`: resizes it, dropping
+    /// voxels that fall outside, then removes, recolors and adds voxels by
+    /// coordinate. The old colors recorded in the diff are not checked.
+    /// Returns how many voxels changed.
+    pub fn apply(&mut self, diff: &VoxDiff) -> usize {
+        let mut changed = 0;
+        if let Some((_, to)) = diff.size {
+            let before = self.voxels.len();
+            self.size = to;
+            self.voxels.retain(|v| (v.x as u32) < to.x && (v.y as u32) < to.y && (v.z as u32) < to.z);
+            changed += before - self.voxels.len();
+        }
+        let mut edits: BTreeMap<(u8, u8, u8), u8> = BTreeMap::new();
+        for v in &diff.removed {
+            edits.insert((v.x, v.y, v.z), 0);
+        }
+        for &(v, to) in &diff.recolored {
+            edits.insert((v.x, v.y, v.z), to);
+        }
+        for v in &diff.added {
+            edits.insert((v.x, v.y, v.z), v.c);
+        }
+        let size = self.size;
+        let mut placed = BTreeSet::new();
+        for voxel in &mut self.voxels {
+            if let Some(&c) = edits.get(&(voxel.x, voxel.y, voxel.z)) {
+                if voxel.c != c {
+                    voxel.c = c;
+                    changed += 1;
+                }
+                placed.insert((voxel.x, voxel.y, voxel.z));
+            }
+        }
+        self.voxels.retain(|v| v.c != 0);
+        for (&(x, y, z), &c) in &edits {
+            let inside = (x as u32) < size.x && (y as u32) < size.y && (z as u32) < size.z;
+            if c != 0 && inside && !placed.contains(&(x, y, z)) {
+                self.voxels.push(Voxel { x, y, z, c });
+                changed += 1;
+            }
+        }
+        self.invalidate_index();
+        changed
+    }
+}
+
+impl Palette {
+    /// Sets the new color of every entry `diff` lists as changed.
+    pub fn apply(&mut self, diff: &VoxDiff) {
+        for &(index, _, color) in &diff.palette {
+            self.set(index, color);
+        }
+    }
+}
+
+fn cells(model: &Model) -> BTreeMap<(u8, u8, u8), u8> {
+    model.voxels.iter().map(|v| ((v.x, v.y, v.z), v.c)).collect()
+}
@@ -0,0 +1,450 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use byteorder::{LittleEndian, WriteBytesExt};
+use coords::CoordinateSystem;
+use material::{Material, MaterialType};
+use mesh::FACES;
+use mesher::Surfaces;
+use model::Model;
+use palette::Palette;
+use png;
+use scene::{SceneGraph, SceneNode};
+use vox_loader::VoxLoader;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorMode {
+    /// Colors are written per vertex as `COLOR_0`.
+    VertexColors,
+    /// A 256x1 palette texture is embedded and vertices get `TEXCOORD_0`
+    /// pointing at their palette entry.
+    PaletteTexture,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GltfOptions {
+    pub color_mode: ColorMode,
+    /// Rotate the models into glTF's Y-up space. Files loaded with other
+    /// `ParseOptions::target_coords` are converted from their own space.
+    pub y_up: bool,
+}
+impl Default for GltfOptions {
+    fn default() -> GltfOptions {
+        GltfOptions {
+            color_mode: ColorMode::VertexColors,
+            y_up: true,
+        }
+    }
+}
+
+/// Writes the loaded file as a binary glTF. The scene graph becomes the node
+/// hierarchy, skipping hidden layers; files without one get a node per model.
+pub fn export_glb<P: AsRef<Path>>(vl: &VoxLoader, path: P, options: &GltfOptions) -> io::Result<()> {
+    File::create(path)?.write_all(&to_glb(vl, options))
+}
+
+pub fn to_glb(vl: &VoxLoader, options: &GltfOptions) -> Vec<u8> {
+    let mut builder = Builder::new(&vl.palette, vl.materials(), options);
+    builder.coords = vl.coordinate_system();
+    let mut roots = Vec::new();
+    if vl.scene().is_empty() {
+        for model in vl.models() {
+            roots.push(builder.model_node(model));
+        }
+    } else if let Some(root) = builder.scene_node(vl.scene(), vl.models(), 0, 0) {
+        roots.push(root);
+    }
+    builder.finish(roots)
+}
+
+/// Writes a single model, without any scene transform, as a binary glTF.
+pub fn model_to_glb(model: &Model, palette: &Palette, materials: &BTreeMap<u32, Material>, options: &GltfOptions) -> Vec<u8> {
+    let mut builder = Builder::new(palette, materials, options);
+    let root = builder.model_node(model);
+    builder.finish(vec![root])
+}
+
+#[derive(Default)]
+struct Primitive {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+struct Builder<'a> {
+    palette: &'a Palette,
+    materials: &'a BTreeMap<u32, Material>,
+    options: &'a GltfOptions,
+    /// The space the models are in.
+    coords: CoordinateSystem,
+    bin: Vec<u8>,
+    buffer_views: Vec<String>,
+    accessors: Vec<String>,
+    meshes: Vec<String>,
+    nodes: Vec<String>,
+    gltf_materials: Vec<String>,
+    material_ids: BTreeMap<Option<u8>, usize>,
+    /// Meshes of models placed by the scene graph, by model id.
+    shared_meshes: BTreeMap<u32, Option<usize>>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(palette: &'a Palette, materials: &'a BTreeMap<u32, Material>, options: &'a GltfOptions) -> Builder<'a> {
+        Builder {
+            palette,
+            materials,
+            options,
+            coords: CoordinateSystem::ZUpRightHanded,
+            bin: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+            meshes: Vec::new(),
+            nodes: Vec::new(),
+            gltf_materials: Vec::new(),
+            material_ids: BTreeMap::new(),
+            shared_meshes: BTreeMap::new(),
+        }
+    }
+
+    fn scene_node(&mut self, scene: &SceneGraph, models: &[Model], id: u32, depth: usize) -> Option<usize> {
+        if depth > scene.nodes.len() {
+            return None;
+        }
+        match scene.node(id)? {
+            SceneNode::Transform(node) => {
+                if scene.is_hidden(node) {
+                    return None;
+                }
+                let child = self.scene_node(scene, models, node.child, depth + 1);
+                let r = node.rotation.to_matrix();
+                let t = node.translation;
+                let matrix = [
+                    r[0][0], r[1][0], r[2][0], 0,
+                    r[0][1], r[1][1], r[2][1], 0,
+                    r[0][2], r[1][2], r[2][2], 0,
+                    t[0], t[1], t[2], 1,
+                ];
+                let matrix: Vec<String> = matrix.iter().map(|v| v.to_string()).collect();
+                let children = child.map_or(String::new(), |child| format!(",\"children\":[{}]", child));
+                Some(self.push_node(format!("{{\"name\":\"node_{}\",\"matrix\":[{}]{}}}", node.id, matrix.join(","), children)))
+            }
+            SceneNode::Group(node) => {
+                let children: Vec<usize> = node
+                    .children
+                    .iter()
+                    .filter_map(|&child| self.scene_node(scene, models, child, depth + 1))
+                    .collect();
+                Some(self.group_node(node.id, &children))
+            }
+            SceneNode::Shape(node) => {
+                let children: Vec<usize> = node
+                    .models
+                    .iter()
+                    .filter_map(|&id| models.get(id as usize).map(|model| (id, model)))
+                    .map(|(id, model)| self.instance_node(id, model))
+                    .collect();
+                Some(self.group_node(node.id, &children))
+            }
+        }
+    }
+
+    fn group_node(&mut self, id: u32, children: &[usize]) -> usize {
+        let children: Vec<String> = children.iter().map(|child| child.to_string()).collect();
+        self.push_node(format!("{{\"name\":\"node_{}\",\"children\":[{}]}}", id, children.join(",")))
+    }
+
+    /// Adds a node holding the mesh of `model` at its own coordinates.
+    fn model_node(&mut self, model: &Model) -> usize {
+        let mesh = self.add_mesh(model, [0.0; 3]);
+        self.mesh_node(mesh)
+    }
+
+    /// Adds a node for model `id` placed by the scene graph, centered on its
+    /// pivot the way MagicaVoxel positions it. Every instance of a model
+    /// shares the mesh built for the first one.
+    fn instance_node(&mut self, id: u32, model: &Model) -> usize {
+        let mesh = match self.shared_meshes.get(&id) {
+            Some(&mesh) => mesh,
+            None => {
+                let pivot = [(model.size.x / 2) as f32, (model.size.y / 2) as f32, (model.size.z / 2) as f32];
+                let mesh = self.add_mesh(model, pivot);
+                self.shared_meshes.insert(id, mesh);
+                mesh
+            }
+        };
+        self.mesh_node(mesh)
+    }
+
+    fn mesh_node(&mut self, mesh: Option<usize>) -> usize {
+        match mesh {
+            Some(mesh) => self.push_node(format!("{{\"mesh\":{}}}", mesh)),
+            None => self.push_node("{}".to_string()),
+        }
+    }
+
+    fn push_node(&mut self, node: String) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn material_key(&self, c: u8) -> Option<u8> {
+        match self.materials.get(&(c as u32)) {
+            Some(material) if material.material_type != MaterialType::Diffuse => Some(c),
+            _ => None,
+        }
+    }
+
+    fn add_mesh(&mut self, model: &Model, pivot: [f32; 3]) -> Option<usize> {
+        let grid = model.to_dense_grid();
+        // Vertex colors keep the palette alpha; the material supplies the
+        // opacity of see-through primitives.
+        let surfaces = Surfaces::with_materials(self.palette, self.materials);
+        let mut primitives: BTreeMap<Option<u8>, Primitive> = BTreeMap::new();
+        for voxel in &model.voxels {
+            let p = [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+            let color = self.palette.get(voxel.c);
+            let key = self.material_key(voxel.c);
+            let visible = surfaces.visible_faces(&grid, p, voxel.c);
+            for (bit, face) in FACES.iter().enumerate() {
+                if visible & 1 << bit == 0 {
+                    continue;
+                }
+                let primitive = primitives.entry(key).or_default();
+                let base = primitive.positions.len() as u32;
+                for corner in &face.corners {
+                    primitive.positions.push([
+                        (p[0] + corner[0]) as f32 - pivot[0],
+                        (p[1] + corner[1]) as f32 - pivot[1],
+                        (p[2] + corner[2]) as f32 - pivot[2],
+                    ]);
+                    primitive.normals.push([face.normal[0] as f32, face.normal[1] as f32, face.normal[2] as f32]);
+                    primitive.colors.push([
+                        color.r as f32 / 255.0,
+                        color.g as f32 / 255.0,
+                        color.b as f32 / 255.0,
+                        color.a as f32 / 255.0,
+                    ]);
+                    primitive.uvs.push([(voxel.c as f32 + 0.5) / 256.0, 0.5]);
+                }
+                primitive.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+            }
+        }
+        if primitives.is_empty() {
+            return None;
+        }
+
+        let mut gltf_primitives = Vec::new();
+        for (key, primitive) in &primitives {
+            let material = self.material(*key);
+            let position = self.push_vec3(&primitive.positions, true);
+            let normal = self.push_vec3(&primitive.normals, false);
+            let color = match self.options.color_mode {
+                ColorMode::VertexColors => {
+                    let floats: Vec<f32> = primitive.colors.iter().flat_map(|c| c.iter().cloned()).collect();
+                    format!("\"COLOR_0\":{}", self.push_floats(&floats, "VEC4", None))
+                }
+                ColorMode::PaletteTexture => {
+                    let floats: Vec<f32> = primitive.uvs.iter().flat_map(|uv| uv.iter().cloned()).collect();
+                    format!("\"TEXCOORD_0\":{}", self.push_floats(&floats, "VEC2", None))
+                }
+            };
+            let indices = self.push_indices(&primitive.indices);
+            gltf_primitives.push(format!(
+                "{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{},{}}},\"indices\":{},\"material\":{}}}",
+                position, normal, color, indices, material
+            ));
+        }
+        self.meshes.push(format!("{{\"primitives\":[{}]}}", gltf_primitives.join(",")));
+        Some(self.meshes.len() - 1)
+    }
+
+    /// The glTF material for a palette index with a non-diffuse MATL
+    /// material, or the shared plain material for `None`.
+    fn material(&mut self, key: Option<u8>) -> usize {
+        if let Some(&id) = self.material_ids.get(&key) {
+            return id;
+        }
+        let texture = match self.options.color_mode {
+            ColorMode::PaletteTexture => ",\"baseColorTexture\":{\"index\":0}",
+            ColorMode::VertexColors => "",
+        };
+        let json = match key.and_then(|c| self.materials.get(&(c as u32)).map(|material| (c, material))) {
+            None => format!(
+                "{{\"name\":\"voxel\",\"pbrMetallicRoughness\":{{\"baseColorFactor\":[1,1,1,1],\"metallicFactor\":0,\"roughnessFactor\":1{}}}}}",
+                texture
+            ),
+            Some((c, material)) => {
+                let color = self.palette.get(c);
+                let pbr = material.to_pbr();
+                let emission = pbr.emissive;
+                let mut json = format!(
+                    "{{\"name\":\"material_{}\",\"pbrMetallicRoughness\":{{\"baseColorFactor\":[1,1,1,{}],\"metallicFactor\":{},\"roughnessFactor\":{}{}}}",
+                    c, pbr.alpha, pbr.metallic, pbr.roughness, texture
+                );
+                if emission > 0.0 {
+                    json.push_str(&format!(
+                        ",\"emissiveFactor\":[{},{},{}]",
+                        color.r as f32 / 255.0 * emission,
+                        color.g as f32 / 255.0 * emission,
+                        color.b as f32 / 255.0 * emission
+                    ));
+                }
+                if pbr.is_transparent() {
+                    json.push_str(",\"alphaMode\":\"BLEND\"");
+                }
+                json.push('}');
+                json
+            }
+        };
+        self.gltf_materials.push(json);
+        let id = self.gltf_materials.len() - 1;
+        self.material_ids.insert(key, id);
+        id
+    }
+
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+        let target = target.map_or(String::new(), |target| format!(",\"target\":{}", target));
+        self.buffer_views.push(format!(
+            "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{}{}}}",
+            self.bin.len(),
+            bytes.len(),
+            target
+        ));
+        self.bin.extend_from_slice(bytes);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_floats(&mut self, floats: &[f32], kind: &str, bounds: Option<(String, String)>) -> usize {
+        let mut bytes = Vec::with_capacity(floats.len() * 4);
+        for &value in floats {
+            bytes.write_f32::<LittleEndian>(value).unwrap();
+        }
+        let view = self.push_view(&bytes, Some(ARRAY_BUFFER));
+        let components = match kind {
+            "VEC2" => 2,
+            "VEC3" => 3,
+            _ => 4,
+        };
+        let bounds = bounds.map_or(String::new(), |(min, max)| format!(",\"min\":[{}],\"max\":[{}]", min, max));
+        self.accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"{}\"{}}}",
+            view,
+            FLOAT,
+            floats.len() / components,
+            kind,
+            bounds
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn push_vec3(&mut self, values: &[[f32; 3]], with_bounds: bool) -> usize {
+        let floats: Vec<f32> = values.iter().flat_map(|v| v.iter().cloned()).collect();
+        let bounds = if with_bounds {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for v in values {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(v[axis]);
+                    max[axis] = max[axis].max(v[axis]);
+                }
+            }
+            Some((format!("{},{},{}", min[0], min[1], min[2]), format!("{},{},{}", max[0], max[1], max[2])))
+        } else {
+            None
+        };
+        self.push_floats(&floats, "VEC3", bounds)
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let mut bytes = Vec::with_capacity(indices.len() * 4);
+        for &index in indices {
+            bytes.write_u32::<LittleEndian>(index).unwrap();
+        }
+        let view = self.push_view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"SCALAR\"}}",
+            view,
+            UNSIGNED_INT,
+            indices.len()
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn finish(mut self, children: Vec<usize>) -> Vec<u8> {
+        let children: Vec<String> = children.iter().map(|child| child.to_string()).collect();
+        let target = if self.options.y_up { CoordinateSystem::YUpRightHanded } else { CoordinateSystem::ZUpRightHanded };
+        let m = self.coords.matrix_to(target);
+        let transform = if self.coords == target {
+            String::new()
+        } else if self.coords == CoordinateSystem::ZUpRightHanded {
+            ",\"rotation\":[-0.70710677,0,0,0.70710677]".to_string()
+        } else {
+            let columns: Vec<String> = (0..3).map(|col| format!("{},{},{},0", m[0][col], m[1][col], m[2][col])).collect();
+            format!(",\"matrix\":[{},0,0,0,1]", columns.join(","))
+        };
+        let root = self.push_node(format!("{{\"name\":\"root\"{},\"children\":[{}]}}", transform, children.join(",")));
+
+        let mut textures = String::new();
+        if self.options.color_mode == ColorMode::PaletteTexture {
+            let atlas = self.palette.to_texture_atlas(256);
+            let image = self.push_view(&png::encode(atlas.width, atlas.height, &atlas.pixels).expect("palette atlas is not empty"), None);
+            textures = format!(
+                ",\"images\":[{{\"bufferView\":{},\"mimeType\":\"image/png\"}}],\"samplers\":[{{\"magFilter\":9728,\"minFilter\":9728}}],\"textures\":[{{\"source\":0,\"sampler\":0}}]",
+                image
+            );
+        }
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+
+        let mut json = format!(
+            "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"vox_loader\"}},\"scene\":0,\"scenes\":[{{\"nodes\":[{}]}}],\"nodes\":[{}]",
+            root,
+            self.nodes.join(",")
+        );
+        if !self.meshes.is_empty() {
+            json.push_str(&format!(
+                ",\"meshes\":[{}],\"materials\":[{}],\"accessors\":[{}],\"bufferViews\":[{}],\"buffers\":[{{\"byteLength\":{}}}]",
+                self.meshes.join(","),
+                self.gltf_materials.join(","),
+                self.accessors.join(","),
+                self.buffer_views.join(","),
+                self.bin.len()
+            ));
+            json.push_str(&textures);
+        }
+        json.push('}');
+        let mut json = json.into_bytes();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+
+        let has_bin = !self.meshes.is_empty();
+        let total = 12 + 8 + json.len() + if has_bin { 8 + self.bin.len() } else { 0 };
+        let mut glb = Vec::with_capacity(total);
+        glb.extend_from_slice(b"glTF");
+        glb.write_u32::<LittleEndian>(2).unwrap();
+        glb.write_u32::<LittleEndian>(total as u32).unwrap();
+        glb.write_u32::<LittleEndian>(json.len() as u32).unwrap();
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        if has_bin {
+            glb.write_u32::<LittleEndian>(self.bin.len() as u32).unwrap();
+            glb.extend_from_slice(b"BIN\0");
+            glb.extend_from_slice(&self.bin);
+        }
+        glb
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt};
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette, PaletteBuilder};
+use png;
+
+/// One Goxel layer. Goxel volumes are unbounded, so each layer becomes a
+/// model sized to its voxels, with `position` holding the minimum corner.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GoxLayer {
+    pub name: String,
+    pub visible: bool,
+    pub position: [i32; 3],
+    pub model: Model,
+}
+
+/// A Goxel `.gox` file normalized into paletted models. All layers share
+/// one palette; past 255 distinct colors the rest are matched to the
+/// closest entry.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GoxFile {
+    pub layers: Vec<GoxLayer>,
+    pub palette: Palette,
+}
+
+impl GoxFile {
+    pub fn models(&self) -> Vec<&Model> {
+        self.layers.iter().map(|layer| &layer.model).collect()
+    }
+}
+
+pub fn load_gox<P: AsRef<Path>>(path: P) -> io::Result<GoxFile> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    read_gox(&bytes)
+}
+
+/// Parses a `.gox` file. Its BL16 chunks hold 16x16x16 voxel blocks encoded
+/// as 64x64 PNG images, which the LAYR chunks place in the layer.
+pub fn read_gox(bytes: &[u8]) -> io::Result<GoxFile> {
+    if !bytes.starts_with(b"GOX ") {
+        return Err(invalid_data("missing GOX file magic".to_string()));
+    }
+    let mut cursor = Cursor::new(&bytes[4..]);
+    let _version = cursor.read_i32::<LittleEndian>()?;
+
+    let mut blocks = Vec::new();
+    let mut palette = PaletteBuilder::new();
+    let mut layers = Vec::new();
+    while (cursor.position() as usize) < cursor.get_ref().len() {
+        let mut id = [0; 4];
+        cursor.read_exact(&mut id)?;
+        let length = read_length(&mut cursor)?;
+        let mut data = vec![0; length];
+        cursor.read_exact(&mut data)?;
+        let _crc = cursor.read_i32::<LittleEndian>()?;
+        match &id {
+            b"BL16" => {
+                let (width, height, pixels) = png::decode(&data)?;
+                if width * height != 16 * 16 * 16 {
+                    return Err(invalid_data(format!("BL16 block image is {}x{}", width, height)));
+                }
+                blocks.push(pixels);
+            }
+            b"LAYR" => layers.push(read_layer(&data, &blocks, &mut palette)?),
+            _ => {}
+        }
+    }
+    Ok(GoxFile { layers, palette: palette.finish() })
+}
+
+fn read_layer(data: &[u8], blocks: &[Vec<u8>], palette: &mut PaletteBuilder) -> io::Result<GoxLayer> {
+    let mut cursor = Cursor::new(data);
+    let block_count = cursor.read_i32::<LittleEndian>()?;
+    let mut voxels = Vec::new();
+    for _ in 0..block_count {
+        let index = cursor.read_i32::<LittleEndian>()?;
+        let origin = [
+            cursor.read_i32::<LittleEndian>()?,
+            cursor.read_i32::<LittleEndian>()?,
+            cursor.read_i32::<LittleEndian>()?,
+        ];
+        let _reserved = cursor.read_i32::<LittleEndian>()?;
+        let pixels = blocks
+            .get(index as usize)
+            .ok_or_else(|| invalid_data(format!("layer references missing block {}", index)))?;
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            if pixel[3] == 0 {
+                continue;
+            }
+            let i = i as i32;
+            let offset = [i % 16, i / 16 % 16, i / 256];
+            let mut position = [0; 3];
+            for axis in 0..3 {
+                position[axis] = origin[axis]
+                    .checked_add(offset[axis])
+                    .ok_or_else(|| invalid_data(format!("block origin {:?} overflows", origin)))?;
+            }
+            voxels.push((position, Color::new(pixel[0], pixel[1], pixel[2], 255)));
+        }
+    }
+
+    let mut name = String::new();
+    let mut visible = true;
+    while (cursor.position() as usize) < data.len() {
+        let key = read_sized(&mut cursor)?;
+        let value = read_sized(&mut cursor)?;
+        match &key[..] {
+            b"name" => name = String::from_utf8_lossy(&value).trim_end_matches('\0').to_string(),
+            b"visible" => visible = value.iter().any(|&byte| byte != 0),
+            _ => {}
+        }
+    }
+
+    let mut min = [i32::MAX; 3];
+    let mut max = [i32::MIN; 3];
+    for &(position, _) in &voxels {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+    if voxels.is_empty() {
+        min = [0; 3];
+        max = [-1; 3];
+    }
+    let mut size = [0; 3];
+    for axis in 0..3 {
+        size[axis] = match max[axis].checked_sub(min[axis]).and_then(|extent| extent.checked_add(1)) {
+            Some(side) if side <= 256 => side,
+            _ => return Err(invalid_data(format!("layer {:?} from {:?} to {:?} exceeds 256 voxels per axis", name, min, max))),
+        };
+    }
+    let mut model = Model::new(Size { x: size[0] as u32, y: size[1] as u32, z: size[2] as u32 });
+    for (position, color) in voxels {
+        model.voxels.push(Voxel {
+            x: (position[0] - min[0]) as u8,
+            y: (position[1] - min[1]) as u8,
+            z: (position[2] - min[2]) as u8,
+            c: palette.index_of(color),
+        });
+    }
+    Ok(GoxLayer { name, visible, position: min, model })
+}
+
+fn read_sized(cursor: &mut Cursor<&[u8]>) -> io::Result<Vec<u8>> {
+    let length = read_length(cursor)?;
+    let mut value = vec![0; length];
+    cursor.read_exact(&mut value)?;
+    Ok(value)
+}
+
+/// Reads a length prefix, refusing one longer than the bytes left so a
+/// corrupt file cannot trigger a huge allocation.
+fn read_length(cursor: &mut Cursor<&[u8]>) -> io::Result<usize> {
+    let length = cursor.read_i32::<LittleEndian>()?.max(0) as usize;
+    let remaining = cursor.get_ref().len() - (cursor.position() as usize).min(cursor.get_ref().len());
+    if length > remaining {
+        return Err(invalid_data(format!("length {} runs past the {} bytes left", length, remaining)));
+    }
+    Ok(length)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,120 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use mesher::{IndexBuffer, VoxMesh};
+
+/// Vertex layouts `vertex_bytes` can pack a mesh into. Attributes are
+/// tightly interleaved in the order listed, using shader locations from 0.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VertexLayout {
+    /// `Float32x3` position, `Float32x3` normal, `Float32x2` uv and
+    /// `Float32x4` color; the same order as `VoxMesh::interleaved`.
+    PositionNormalUvColor,
+    /// `Float32x3` position, `Float32x3` normal and `Unorm8x4` color.
+    PositionNormalColor,
+    /// `Float32x3` position and `Unorm8x4` color, for unlit rendering.
+    PositionColor,
+}
+
+#[cfg(feature = "wgpu")]
+const POSITION_NORMAL_UV_COLOR: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Float32x4];
+#[cfg(feature = "wgpu")]
+const POSITION_NORMAL_COLOR: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Unorm8x4];
+#[cfg(feature = "wgpu")]
+const POSITION_COLOR: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Unorm8x4];
+
+impl VertexLayout {
+    /// Size of one vertex in bytes.
+    pub fn stride(self) -> usize {
+        match self {
+            VertexLayout::PositionNormalUvColor => 48,
+            VertexLayout::PositionNormalColor => 28,
+            VertexLayout::PositionColor => 16,
+        }
+    }
+
+    #[cfg(feature = "wgpu")]
+    pub fn attributes(self) -> &'static [wgpu::VertexAttribute] {
+        match self {
+            VertexLayout::PositionNormalUvColor => &POSITION_NORMAL_UV_COLOR,
+            VertexLayout::PositionNormalColor => &POSITION_NORMAL_COLOR,
+            VertexLayout::PositionColor => &POSITION_COLOR,
+        }
+    }
+
+    /// The per-vertex buffer descriptor matching `vertex_bytes`.
+    #[cfg(feature = "wgpu")]
+    pub fn buffer_layout(self) -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.stride() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: self.attributes(),
+        }
+    }
+}
+
+/// Packs the vertices of `mesh` into a little-endian byte buffer in the
+/// given layout, ready to upload as a vertex buffer.
+pub fn vertex_bytes(mesh: &VoxMesh, layout: VertexLayout) -> Vec<u8> {
+    let mut data = Vec::with_capacity(mesh.vertex_count() * layout.stride());
+    for i in 0..mesh.vertex_count() {
+        write_floats(&mut data, &mesh.positions[i]);
+        match layout {
+            VertexLayout::PositionNormalUvColor => {
+                write_floats(&mut data, &mesh.normals[i]);
+                write_floats(&mut data, &mesh.uvs[i]);
+                write_floats(&mut data, &mesh.colors[i]);
+            }
+            VertexLayout::PositionNormalColor => {
+                write_floats(&mut data, &mesh.normals[i]);
+                write_unorm8(&mut data, &mesh.colors[i]);
+            }
+            VertexLayout::PositionColor => write_unorm8(&mut data, &mesh.colors[i]),
+        }
+    }
+    data
+}
+
+/// The index buffer as bytes, using the narrowest index type that fits.
+/// Pair it with `index_format` when binding, and draw
+/// `mesh.indices.len()` indices since 16-bit buffers may end in padding.
+pub fn index_bytes(mesh: &VoxMesh) -> Vec<u8> {
+    let mut data = Vec::new();
+    match mesh.index_buffer() {
+        IndexBuffer::U16(indices) => {
+            for index in indices {
+                data.write_u16::<LittleEndian>(index).unwrap();
+            }
+            // Buffer sizes must stay a multiple of 4 bytes.
+            if data.len() % 4 != 0 {
+                data.write_u16::<LittleEndian>(0).unwrap();
+            }
+        }
+        IndexBuffer::U32(indices) => {
+            for index in indices {
+                data.write_u32::<LittleEndian>(index).unwrap();
+            }
+        }
+    }
+    data
+}
+
+/// The index format of the buffer returned by `index_bytes`.
+#[cfg(feature = "wgpu")]
+pub fn index_format(mesh: &VoxMesh) -> wgpu::IndexFormat {
+    if mesh.vertex_count() > u16::MAX as usize + 1 {
+        wgpu::IndexFormat::Uint32
+    } else {
+        wgpu::IndexFormat::Uint16
+    }
+}
+
+fn write_floats(data: &mut Vec<u8>, values: &[f32]) {
+    for &value in values {
+        data.write_f32::<LittleEndian>(value).unwrap();
+    }
+}
+
+fn write_unorm8(data: &mut Vec<u8>, values: &[f32; 4]) {
+    for &value in values {
+        data.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+use std::vec::Vec;
+use model::{Model, Size, Voxel};
+
+/// A dense voxel volume with one color index per cell, 0 meaning empty.
+/// Cells are stored x-fastest, then y, then z.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VoxGrid {
+    pub size: Size,
+    data: Vec<u8>,
+}
+impl fmt::Debug for VoxGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VoxGrid {{ size: {:?}, data: {:?} }}", self.size, self.data)
+    }
+}
+
+impl VoxGrid {
+    /// An empty grid. Panics if the cell count overflows a `usize`.
+    pub fn new(size: Size) -> VoxGrid {
+        let count = size.cell_count().expect("grid size overflows usize");
+        VoxGrid { size, data: vec![0; count] }
+    }
+
+    pub fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        VoxGrid::index_of(self.size, x, y, z)
+    }
+
+    /// The offset of `(x, y, z)` in the data of a grid of `size`, without
+    /// needing the grid itself.
+    pub(crate) fn index_of(size: Size, x: i32, y: i32, z: i32) -> Option<usize> {
+        if x < 0 || y < 0 || z < 0 {
+            return None;
+        }
+        let (x, y, z) = (x as u32, y as u32, z as u32);
+        if x >= size.x || y >= size.y || z >= size.z {
+            return None;
+        }
+        let (sx, sy) = (size.x as usize, size.y as usize);
+        Some(x as usize + y as usize * sx + z as usize * sx * sy)
+    }
+
+    /// The color index at a cell, or 0 outside the grid so neighbor lookups
+    /// need no bounds checks.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.index(x, y, z).map_or(0, |index| self.data[index])
+    }
+
+    /// Sets a cell, returning false if it lies outside the grid.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, c: u8) -> bool {
+        match self.index(x, y, z) {
+            Some(index) => {
+                self.data[index] = c;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32, z: i32) -> bool {
+        self.get(x, y, z) != 0
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn to_model(&self) -> Model {
+        let mut model = Model::new(self.size);
+        for (index, &c) in self.data.iter().enumerate() {
+            if c == 0 {
+                continue;
+            }
+            let (sx, sy) = (self.size.x as usize, self.size.y as usize);
+            model.voxels.push(Voxel {
+                x: (index % sx) as u8,
+                y: (index / sx % sy) as u8,
+                z: (index / (sx * sy)) as u8,
+                c,
+            });
+        }
+        model
+    }
+}
+
+impl Model {
+    /// The voxels as a dense grid. Sides are capped at 256, the most voxel
+    /// coordinates can reach, so oversized models still fit in memory.
+    pub fn to_dense_grid(&self) -> VoxGrid {
+        let size = Size { x: self.size.x.min(256), y: self.size.y.min(256), z: self.size.z.min(256) };
+        let mut grid = VoxGrid::new(size);
+        for voxel in &self.voxels {
+            grid.set(voxel.x as i32, voxel.y as i32, voxel.z as i32, voxel.c);
+        }
+        grid
+    }
+}
@@ -1,11 +1,2100 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
 #[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate byteorder;
+#[cfg(feature = "bevy")]
+extern crate bevy;
+#[cfg(feature = "glam")]
+extern crate glam;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "libc")]
+extern crate libc;
+#[cfg(feature = "tangents")]
+extern crate mikktspace;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wgpu")]
+extern crate wgpu;
+
+/// Without `std`, stands in for the parts of it the parsing path needs so
+/// modules import the same `std::` paths either way.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use alloc::{boxed, collections, string, vec};
+    pub use core::{cell, fmt, ops, slice, str};
+}
+
+#[cfg(feature = "tokio")]
+mod async_reader;
+#[cfg(feature = "bevy")]
+pub mod bevy_loader;
+#[cfg(feature = "std")]
+pub mod binvox;
+mod bitgrid;
+pub mod chunk;
+mod color_tree;
+mod coords;
+mod dict;
+mod diff;
+#[cfg(feature = "image")]
+pub mod goxel;
+#[cfg(feature = "std")]
+pub mod gpu;
+mod grid;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+mod material;
+#[cfg(feature = "glam")]
+mod math;
+#[cfg(feature = "std")]
+pub mod mesh;
+#[cfg(feature = "std")]
+pub mod mesher;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod model;
+mod octree;
+mod ops;
+mod palette;
+#[cfg(feature = "image")]
+mod png;
+#[cfg(feature = "std")]
+pub mod quantize;
+#[cfg(feature = "std")]
+pub mod qubicle;
+#[cfg(feature = "std")]
+mod raycast;
+#[cfg(feature = "std")]
+pub mod render;
+mod render_settings;
+mod scene;
+#[cfg(feature = "std")]
+mod simplify;
+mod stats;
+#[cfg(feature = "tangents")]
+mod tangent;
+#[cfg(feature = "std")]
+pub mod slices;
+#[cfg(feature = "std")]
+pub mod sproxel;
+mod transform;
+#[cfg(feature = "usd")]
+pub mod usda;
+#[cfg(feature = "std")]
+mod vox_index;
 mod vox_loader;
-pub use vox_loader::VoxLoader;
-pub use vox_loader::Voxel;
+#[cfg(feature = "std")]
+mod vox_writer;
+#[cfg(feature = "std")]
+pub mod voxedit;
+#[cfg(feature = "std")]
+pub mod voxelize;
+#[cfg(feature = "std")]
+pub mod voxlap;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod world;
+#[cfg(feature = "std")]
+mod world_loader;
+#[cfg(feature = "std")]
+pub mod xraw;
+#[cfg(feature = "tokio")]
+pub use async_reader::ReadVox;
+pub use bitgrid::BitGrid;
+pub use chunk::{ChunkReader, ChunkWriter, RawChunk};
+pub use coords::CoordinateSystem;
+pub use dict::Dict;
+pub use diff::{diff, VoxDiff};
+#[cfg(feature = "image")]
+pub use goxel::{GoxFile, GoxLayer};
+pub use grid::VoxGrid;
+pub use material::{Material, MaterialType, PbrMapping, PbrMaterial};
+#[cfg(feature = "std")]
+pub use mesher::{AmbientOcclusion, IndexBuffer, MeshOptions, SplitMesh, VoxMesh};
+pub use model::{ColoredVoxels, Model, Size, Voxel, Voxels};
+pub use octree::{Octree, OctreeCell, OctreeNode};
+pub use ops::{Conflict, PaletteMerge};
+pub use palette::{Color, PackOrder, Palette, TextureAtlas, DEFAULT_PALETTE};
+#[cfg(feature = "std")]
+pub use quantize::Dither;
+#[cfg(feature = "std")]
+pub use qubicle::{QbFile, QbMatrix};
+#[cfg(feature = "std")]
+pub use raycast::Hit;
+#[cfg(feature = "std")]
+pub use render::RgbaImage;
+pub use render_settings::{Camera, CameraMode, RenderObject};
+#[cfg(feature = "std")]
+pub use simplify::SimplifyOptions;
+pub use scene::{Animation, GroupNode, Instance, Layer, LoopMode, ModelKey, Pose, Rotation, SceneGraph, SceneNode, ShapeNode, TransformKey, TransformNode};
+#[cfg(feature = "image")]
+pub use slices::PaletteStrategy;
+pub use stats::ModelStats;
+pub use transform::{Aabb, Anchor, Axis, ColorPick, Overflow};
+#[cfg(feature = "std")]
+pub use vox_index::{ModelEntry, VoxIndex};
+pub use vox_loader::{FormatVersion, ParseError, ParseOptions, Progress, VoxLoader};
+#[cfg(feature = "std")]
+pub use vox_writer::VoxWriter;
+pub use world::{FlattenBounds, World, WorldVoxels};
+#[cfg(feature = "std")]
+pub use world_loader::{NamingScheme, StreamEvents, WorldLoader};
+#[cfg(feature = "std")]
+pub use voxedit::{VxmFile, VxmLayer};
+#[cfg(feature = "std")]
+pub use voxelize::{FillMode, VoxelizeOptions};
+#[cfg(feature = "std")]
+pub use voxlap::VoxlapFile;
+#[cfg(feature = "std")]
+pub use xraw::XrawVolume;
+
+#[cfg(test)]
+const TINY_VOX: &[u8] = &[
+    b'V', b'O', b'X', b' ', 150, 0, 0, 0,
+    b'M', b'A', b'I', b'N', 0, 0, 0, 0, 44, 0, 0, 0,
+    b'S', b'I', b'Z', b'E', 12, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0,
+    b'X', b'Y', b'Z', b'I', 8, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 2, 0, 79,
+];
 
 #[test]
 fn it_works() {
-    // use vox_loader::VoxLoader;
-    // VoxLoader::new("../vox_files/3x3x3.vox");
+    let vl = VoxLoader::from_bytes(TINY_VOX);
+    assert_eq!(vl.version(), 150);
+    assert!(vl.warnings().is_empty());
+    assert_eq!(vl.models()[0].size.x, 3);
+    assert_eq!(vl.models()[0].voxels.len(), 1);
+    assert_eq!(vl.models()[0].voxels[0].c, 79);
+    assert_eq!(vl.models()[0].voxel_at(1, 2, 0), Some(79));
+    assert_eq!(vl.models()[0].voxel_at(0, 0, 0), None);
+    assert_eq!(vl.models()[0].iter().len(), 1);
+    assert_eq!(vl.models()[0].iter_colored(&vl.palette).next().unwrap().3, vl.palette.get(79));
+    #[cfg(feature = "std")]
+    assert_eq!(VoxLoader::from_reader(TINY_VOX).models()[0].voxels[0].y, 2);
+}
+
+/// Parsing a byte slice needs nothing from `std`, so this also runs
+/// under `--no-default-features`.
+#[test]
+fn byte_slice_parsing() {
+    let vl = VoxLoader::from_bytes(TINY_VOX);
+    let model = &vl.models()[0];
+    assert_eq!(model.size, Size { x: 3, y: 3, z: 3 });
+    assert_eq!(model.voxels[0], Voxel { x: 1, y: 2, z: 0, c: 79 });
+    assert_eq!(model.to_dense_grid().get(1, 2, 0), 79);
+    assert_eq!(vl.palette.get(79), Palette::default().get(79));
+    assert!(!VoxLoader::from_bytes(&TINY_VOX[..30]).warnings().is_empty());
+}
+
+#[test]
+fn oversized_model_grid() {
+    let model = Model::from_voxels(Size { x: u32::MAX, y: u32::MAX, z: 2 }, vec![Voxel { x: 255, y: 3, z: 1, c: 6 }]);
+    assert_eq!(model.to_dense_grid().size, Size { x: 256, y: 256, z: 2 });
+    assert_eq!(model.voxel_at(255, 3, 1), Some(6));
+    assert_eq!(model.voxel_at(256, 3, 1), None);
+    assert_eq!(model.surface_voxels().len(), 1);
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(VoxGrid::index_of(Size { x: 70000, y: 70000, z: 1 }, 69999, 69999, 0), Some(70000 * 70000 - 1));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn writer_round_trip() {
+    let mut vl = VoxLoader::from_bytes(TINY_VOX);
+    vl.models_mut()[0].voxels.push(Voxel { x: 1, y: 0, z: 0, c: 5 });
+    let mut second = Model::new(Size { x: 1, y: 2, z: 1 });
+    second.voxels.push(Voxel { x: 0, y: 1, z: 0, c: 9 });
+    vl.models_mut().push(second);
+    let reloaded = VoxLoader::from_bytes(&VoxWriter::to_bytes(&vl));
+    assert_eq!(reloaded.models().len(), 2);
+    assert_eq!(reloaded.models()[0].voxels[1].x, 1);
+    assert_eq!(reloaded.models()[1].size.y, 2);
+    assert_eq!(reloaded.palette, vl.palette);
+}
+
+#[test]
+fn rotation_decoding() {
+    assert_eq!(Rotation::IDENTITY.to_matrix(), [[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+    assert_eq!(Rotation(0b0010001).to_matrix(), [[0, -1, 0], [1, 0, 0], [0, 0, 1]]);
+    assert_eq!(Rotation(0b0010001).apply([1, 2, 3]), [-2, 1, 3]);
+}
+
+#[test]
+fn dict_round_trip() {
+    let mut dict = Dict::new();
+    dict.insert("_t", "-3 4 10");
+    dict.insert("_r", "17");
+    let (read, length) = Dict::read(&dict.to_bytes()).unwrap();
+    assert_eq!(length, dict.to_bytes().len());
+    assert_eq!(read.get_vec3("_t"), Some([-3, 4, 10]));
+    assert_eq!(read.get_rotation("_r"), Some(Rotation(17)));
+    assert!(Dict::read(&dict.to_bytes()[..10]).is_none());
+}
+
+#[test]
+fn palette_index_offset() {
+    let mut entries = vec![Color::default(); 256];
+    entries[0] = Color::new(10, 20, 30, 255);
+    let palette = Palette::from_rgba_chunk(&entries);
+    assert_eq!(palette.get(0), Color::default());
+    assert_eq!(palette.get(1).as_u32(PackOrder::Rgba), 0x0a141eff);
+    assert_eq!(palette.get(1).as_u32(PackOrder::Abgr), 0xff1e140a);
+    assert_eq!(palette.to_rgba_chunk(), entries);
+}
+
+#[test]
+fn octree_collapses_uniform_space() {
+    let mut model = Model::new(Size { x: 4, y: 4, z: 4 });
+    for &(x, y, z) in &[(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0), (0, 0, 1), (1, 0, 1), (0, 1, 1), (1, 1, 1)] {
+        model.voxels.push(Voxel { x, y, z, c: 3 });
+    }
+    model.voxels.push(Voxel { x: 3, y: 3, z: 3, c: 7 });
+    let octree = model.to_octree();
+    assert_eq!(octree.query(1, 1, 1), Some(OctreeCell { origin: [0, 0, 0], side: 2, color: 3 }));
+    assert_eq!(octree.query(0, 3, 0), Some(OctreeCell { origin: [0, 2, 0], side: 2, color: 0 }));
+    assert_eq!(octree.get(3, 3, 3), 7);
+    let mut leaves = 0;
+    octree.traverse(|_| leaves += 1);
+    assert_eq!(leaves, 2);
+
+    model.voxels.push(Voxel { x: 9, y: 0, z: 0, c: 5 });
+    assert_eq!(model.to_octree(), octree);
+    let wide = Model::from_voxels(Size { x: u32::MAX, y: 1, z: 1 }, vec![Voxel { x: 255, y: 0, z: 0, c: 2 }]).to_octree();
+    assert_eq!(wide.side(), 256);
+    assert_eq!(wide.get(255, 0, 0), 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn obj_export_skips_hidden_faces() {
+    let model = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }, Voxel { x: 1, y: 0, z: 0, c: 1 }]);
+    let (obj, mtl) = mesh::to_obj(&model, &VoxLoader::from_bytes(TINY_VOX).palette, "model.mtl");
+    assert_eq!(obj.lines().filter(|line| line.starts_with("f ")).count(), 10);
+    assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 12);
+    assert!(mtl.contains("newmtl color_1"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn stl_export_is_closed() {
+    let model = Model::from_voxels(Size { x: 2, y: 2, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }, Voxel { x: 1, y: 1, z: 0, c: 1 }]);
+    let stl = mesh::export_stl(&model, 2.0);
+    let count = u32::from_le_bytes([stl[80], stl[81], stl[82], stl[83]]) as usize;
+    assert_eq!(count, 24);
+    assert_eq!(stl.len(), 84 + count * 50);
+    let mut edges = std::collections::HashMap::new();
+    for triangle in stl[84..].chunks(50) {
+        let vertex = |i: usize| -> Vec<i32> {
+            (0..3).map(|axis| {
+                let at = 12 + i * 12 + axis * 4;
+                f32::from_le_bytes([triangle[at], triangle[at + 1], triangle[at + 2], triangle[at + 3]]) as i32
+            }).collect()
+        };
+        for i in 0..3 {
+            *edges.entry((vertex(i), vertex((i + 1) % 3))).or_insert(0i32) += 1;
+        }
+    }
+    for ((a, b), &count) in &edges {
+        assert_eq!(edges.get(&(b.clone(), a.clone())), Some(&count));
+    }
+}
+
+#[cfg(test)]
+fn solid_cube(side: u8, c: u8) -> Model {
+    let mut model = Model::new(Size { x: side as u32, y: side as u32, z: side as u32 });
+    for z in 0..side {
+        for y in 0..side {
+            for x in 0..side {
+                model.voxels.push(Voxel { x, y, z, c });
+            }
+        }
+    }
+    model
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn greedy_merges_cube_faces() {
+    let palette = Palette::magica_voxel_default();
+    let mesh = mesher::greedy(&solid_cube(3, 4), &palette);
+    assert_eq!(mesh.triangle_count(), 12);
+    let mut model = solid_cube(3, 4);
+    model.voxels[0].c = 5;
+    assert!(mesher::greedy(&model, &palette).triangle_count() > 12);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn culled_mesher_drops_shared_faces() {
+    let palette = Palette::magica_voxel_default();
+    assert_eq!(mesher::culled(&solid_cube(1, 1), &palette).triangle_count(), 12);
+    assert_eq!(mesher::culled(&solid_cube(3, 1), &palette).triangle_count(), 108);
+    let pair = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }, Voxel { x: 1, y: 0, z: 0, c: 2 }]);
+    let mesh = mesher::culled(&pair, &palette);
+    assert_eq!(mesh.triangle_count(), 20);
+    assert_eq!(mesh.vertex_count(), 40);
+    assert_eq!(mesh.interleaved().len(), 40 * mesher::INTERLEAVED_STRIDE);
+    assert_eq!(mesh.index_buffer(), IndexBuffer::U16(mesh.indices.iter().map(|&i| i as u16).collect()));
+    assert!(mesh.normals.iter().all(|n| n[0] * n[0] + n[1] * n[1] + n[2] * n[2] == 1.0));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn marching_cubes_surface_is_closed() {
+    let mesh = mesher::marching_cubes(&solid_cube(3, 2), &Palette::magica_voxel_default(), 0.3);
+    assert!(mesh.triangle_count() > 0);
+    let key = |p: [f32; 3]| [(p[0] * 1000.0).round() as i64, (p[1] * 1000.0).round() as i64, (p[2] * 1000.0).round() as i64];
+    let mut edges = std::collections::HashMap::new();
+    for triangle in mesh.indices.chunks(3) {
+        for i in 0..3 {
+            let a = key(mesh.positions[triangle[i] as usize]);
+            let b = key(mesh.positions[triangle[(i + 1) % 3] as usize]);
+            *edges.entry((a, b)).or_insert(0i32) += 1;
+        }
+    }
+    for ((a, b), &count) in &edges {
+        assert_eq!(edges.get(&(*b, *a)), Some(&count));
+    }
+    for triangle in mesh.indices.chunks(3) {
+        let p: Vec<[f32; 3]> = triangle.iter().map(|&i| mesh.positions[i as usize]).collect();
+        let u = [p[1][0] - p[0][0], p[1][1] - p[0][1], p[1][2] - p[0][2]];
+        let v = [p[2][0] - p[0][0], p[2][1] - p[0][1], p[2][2] - p[0][2]];
+        let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+        let c = [(p[0][0] + p[1][0] + p[2][0]) / 3.0 - 1.5, (p[0][1] + p[1][1] + p[2][1]) / 3.0 - 1.5, (p[0][2] + p[1][2] + p[2][2]) / 3.0 - 1.5];
+        assert!(n[0] * c[0] + n[1] * c[1] + n[2] * c[2] >= 0.0);
+    }
+}
+
+#[test]
+fn palette_texture_atlas() {
+    let palette = Palette::magica_voxel_default();
+    let atlas = palette.to_texture_atlas(16);
+    assert_eq!((atlas.width, atlas.height, atlas.pixels.len()), (16, 16, 1024));
+    assert_eq!(atlas.uv(17), [1.5 / 16.0, 1.5 / 16.0]);
+    assert_eq!(&atlas.pixels[68..72], &[palette.get(17).r, palette.get(17).g, palette.get(17).b, palette.get(17).a]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn ambient_occlusion_darkens_inner_corners() {
+    let palette = Palette::magica_voxel_default();
+    let options = MeshOptions { ambient_occlusion: AmbientOcclusion::Attribute, ..MeshOptions::default() };
+    let cube = mesher::greedy_with(&solid_cube(3, 4), &palette, &options);
+    assert_eq!(cube.triangle_count(), 12);
+    assert!(cube.ao.iter().all(|&ao| ao == 1.0));
+    let mut step = solid_cube(2, 4);
+    step.voxels.retain(|v| v.z == 0 || v.x == 0);
+    let mesh = mesher::culled_with(&step, &palette, &options);
+    assert_eq!(mesh.ao.len(), mesh.vertex_count());
+    assert!(mesh.ao.iter().any(|&ao| ao < 1.0));
+    let shaded = mesher::culled_with(&step, &palette, &MeshOptions { ambient_occlusion: AmbientOcclusion::VertexColors, ..options });
+    assert!(shaded.ao.is_empty());
+    assert!(shaded.colors.iter().any(|c| c[0] < palette.get(4).r as f32 / 255.0));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn gpu_vertex_packing() {
+    let mesh = mesher::culled(&solid_cube(1, 1), &Palette::magica_voxel_default());
+    let full = gpu::vertex_bytes(&mesh, gpu::VertexLayout::PositionNormalUvColor);
+    assert_eq!(full.len(), mesh.vertex_count() * 48);
+    assert_eq!(&full[..4], &mesh.interleaved()[0].to_le_bytes());
+    let compact = gpu::vertex_bytes(&mesh, gpu::VertexLayout::PositionColor);
+    assert_eq!(compact.len(), mesh.vertex_count() * gpu::VertexLayout::PositionColor.stride());
+    assert_eq!(compact[15], Palette::magica_voxel_default().get(1).a);
+    let indices = gpu::index_bytes(&mesh);
+    assert_eq!(indices.len(), 36 * 2);
+    assert_eq!(&indices[2..4], &(mesh.indices[1] as u16).to_le_bytes());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn qubicle_import() {
+    let mut qb = Vec::new();
+    for word in &[0x0101u32, 0, 1, 1, 0, 1] {
+        qb.extend_from_slice(&word.to_le_bytes());
+    }
+    qb.push(1);
+    qb.push(b'a');
+    for word in &[2u32, 1, 2, 5, 6, 7] {
+        qb.extend_from_slice(&word.to_le_bytes());
+    }
+    for word in &[0xff0000ffu32, 0, 6, 2, 2, 0xff00ff00, 6] {
+        qb.extend_from_slice(&word.to_le_bytes());
+    }
+    let file = qubicle::read_qb(&qb).unwrap();
+    let matrix = &file.matrices[0];
+    assert_eq!(matrix.name, "a");
+    assert_eq!(matrix.position, [5, -9, 6]);
+    assert_eq!(matrix.model.size, Size { x: 2, y: 2, z: 1 });
+    assert_eq!(matrix.model.voxels, vec![
+        Voxel { x: 0, y: 1, z: 0, c: 1 },
+        Voxel { x: 0, y: 0, z: 0, c: 2 },
+        Voxel { x: 1, y: 0, z: 0, c: 2 },
+    ]);
+    assert_eq!(file.palette.get(1), Color::new(255, 0, 0, 255));
+    assert_eq!(file.palette.get(2), Color::new(0, 255, 0, 255));
+    assert!(qubicle::read_qb(&qb[..40]).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn qubicle_round_trip() {
+    let palette = Palette::magica_voxel_default();
+    let model = Model::from_voxels(Size { x: 3, y: 2, z: 4 }, vec![Voxel { x: 2, y: 1, z: 3, c: 7 }, Voxel { x: 0, y: 0, z: 1, c: 200 }]);
+    let file = qubicle::read_qb(&qubicle::export_qb(std::slice::from_ref(&model), &palette)).unwrap();
+    let reloaded = &file.matrices[0].model;
+    assert_eq!(reloaded.size, model.size);
+    let mut expected: Vec<_> = model.iter_colored(&palette).collect();
+    let mut actual: Vec<_> = reloaded.iter_colored(&file.palette).collect();
+    expected.sort_by_key(|v| (v.0, v.1, v.2));
+    actual.sort_by_key(|v| (v.0, v.1, v.2));
+    assert_eq!(actual, expected);
+}
+
+#[cfg(all(test, feature = "image"))]
+const BLOCK_PNG: &[u8] = &[
+    137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 64, 0, 0, 0, 64, 8, 6, 0, 0, 0, 170, 105, 113,
+    222, 0, 0, 0, 49, 73, 68, 65, 84, 120, 218, 237, 204, 177, 13, 0, 48, 12, 195, 48, 255, 255, 180, 155, 27, 146, 142, 36, 52, 43,
+    77, 166, 155, 31, 15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 96, 167, 233, 3, 192, 191, 5, 251, 92, 48, 118, 18, 0,
+    0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+];
+
+#[test]
+#[cfg(feature = "image")]
+fn goxel_import() {
+    let mut layer = Vec::new();
+    for word in &[1i32, 0, -16, 0, 32, 0, 4] {
+        layer.extend_from_slice(&word.to_le_bytes());
+    }
+    layer.extend_from_slice(b"name");
+    layer.extend_from_slice(&4i32.to_le_bytes());
+    layer.extend_from_slice(b"body");
+    layer.extend_from_slice(&7i32.to_le_bytes());
+    layer.extend_from_slice(b"visible");
+    layer.extend_from_slice(&1i32.to_le_bytes());
+    layer.push(0);
+    let gox = |layer: &[u8]| {
+        let mut gox = b"GOX ".to_vec();
+        gox.extend_from_slice(&2i32.to_le_bytes());
+        for &(id, data) in &[(b"BL16", BLOCK_PNG), (b"LAYR", layer)] {
+            gox.extend_from_slice(id);
+            gox.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            gox.extend_from_slice(data);
+            gox.extend_from_slice(&0i32.to_le_bytes());
+        }
+        gox
+    };
+    let file = goxel::read_gox(&gox(&layer)).unwrap();
+    let layer = &file.layers[0];
+    assert_eq!((layer.name.as_str(), layer.visible, layer.position), ("body", false, [-16, 0, 32]));
+    assert_eq!(layer.model.size, Size { x: 16, y: 16, z: 16 });
+    assert_eq!(layer.model.voxel_at(1, 1, 0), Some(1));
+    assert_eq!(layer.model.voxel_at(15, 15, 15), Some(2));
+    assert_eq!(layer.model.voxels.len(), 3);
+    assert_eq!(file.palette.get(2), Color::new(0, 255, 0, 255));
+
+    // Origins at the edge of the i32 range, and blocks too far apart, are
+    // rejected rather than overflowing.
+    let words = |words: &[i32]| words.iter().flat_map(|word| word.to_le_bytes()).collect::<Vec<u8>>();
+    let edge = goxel::read_gox(&gox(&words(&[1, 0, i32::MAX - 4, 0, 0, 0]))).unwrap_err();
+    assert_eq!(edge.kind(), std::io::ErrorKind::InvalidData);
+    let apart = goxel::read_gox(&gox(&words(&[2, 0, i32::MIN, 0, 0, 0, 0, i32::MAX - 15, 0, 0, 0]))).unwrap_err();
+    assert_eq!(apart.kind(), std::io::ErrorKind::InvalidData);
+    let mut long = gox(&[]);
+    long[12..16].copy_from_slice(&i32::MAX.to_le_bytes());
+    assert_eq!(goxel::read_gox(&long).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    let long_name = [words(&[0]), words(&[i32::MAX])].concat();
+    assert_eq!(goxel::read_gox(&gox(&long_name)).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sproxel_csv_round_trip() {
+    let (model, palette) = sproxel::read_csv("2,2,1\n#FF0000FF,#00000000\n\n#00000000,#00FF0080\n").unwrap();
+    assert_eq!(model.size, Size { x: 2, y: 1, z: 2 });
+    assert_eq!(model.voxel_at(0, 0, 1), Some(1));
+    assert_eq!(model.voxel_at(1, 0, 0), Some(2));
+    assert_eq!(palette.get(2), Color::new(0, 255, 0, 128));
+    let csv = sproxel::to_csv(&model, &palette);
+    assert_eq!(csv, "2,2,1\n#FF0000FF,#00000000\n\n#00000000,#00FF0080\n\n");
+    assert!(sproxel::read_csv("2,2,1\n#FF0000FF\n").is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn voxlap_import() {
+    let mut kvx = Vec::new();
+    for word in &[43u32, 1, 2, 3, 0, 256, 512, 14, 19] {
+        kvx.extend_from_slice(&word.to_le_bytes());
+    }
+    for half in &[0u16, 5, 5] {
+        kvx.extend_from_slice(&half.to_le_bytes());
+    }
+    kvx.extend_from_slice(&[0, 2, 0, 5, 6]);
+    let mut colors = vec![0u8; 768];
+    colors[15] = 63;
+    colors[19] = 63;
+    kvx.extend_from_slice(&colors);
+    let file = voxlap::read_kvx(&kvx).unwrap();
+    assert_eq!(file.model.size, Size { x: 1, y: 2, z: 3 });
+    assert_eq!(file.model.voxels, vec![Voxel { x: 0, y: 0, z: 2, c: 1 }, Voxel { x: 0, y: 0, z: 1, c: 2 }]);
+    assert_eq!(file.palette.get(1), Color::new(255, 0, 0, 255));
+    assert_eq!(file.pivot, [0.0, 1.0, 1.0]);
+
+    let mut kv6 = b"Kvxl".to_vec();
+    for word in &[2u32, 1, 4] {
+        kv6.extend_from_slice(&word.to_le_bytes());
+    }
+    for float in &[1.0f32, 0.5, 1.0] {
+        kv6.extend_from_slice(&float.to_le_bytes());
+    }
+    kv6.extend_from_slice(&2u32.to_le_bytes());
+    for &(color, z) in &[(0x00ff0000u32, 3u16), (0x0000ff00, 0)] {
+        kv6.extend_from_slice(&color.to_le_bytes());
+        kv6.extend_from_slice(&z.to_le_bytes());
+        kv6.extend_from_slice(&[0, 0]);
+    }
+    for word in &[2u32, 0] {
+        kv6.extend_from_slice(&word.to_le_bytes());
+    }
+    for half in &[2u16, 0] {
+        kv6.extend_from_slice(&half.to_le_bytes());
+    }
+    let file = voxlap::read_kv6(&kv6).unwrap();
+    assert_eq!(file.model.voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }, Voxel { x: 0, y: 0, z: 3, c: 2 }]);
+    assert_eq!(file.palette.get(2), Color::new(0, 255, 0, 255));
+    assert_eq!(file.pivot, [1.0, 0.5, 3.0]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn binvox_round_trip() {
+    let file = binvox::read_binvox(b"#binvox 1\ndim 1 2 1\ntranslate 0.5 0 -1\nscale 2\ndata\n\x00\x01\x01\x01", 9).unwrap();
+    assert_eq!(file.model.size, Size { x: 1, y: 1, z: 2 });
+    assert_eq!(file.model.voxels, vec![Voxel { x: 0, y: 0, z: 1, c: 9 }]);
+    assert_eq!((file.translate, file.scale), ([0.5, 0.0, -1.0], 2.0));
+    let model = Model::from_voxels(Size { x: 2, y: 3, z: 2 }, vec![Voxel { x: 1, y: 2, z: 0, c: 4 }, Voxel { x: 0, y: 0, z: 1, c: 4 }]);
+    let reloaded = binvox::read_binvox(&binvox::export_binvox(&model), 4).unwrap().model;
+    assert_eq!(reloaded.size, model.size);
+    assert_eq!(reloaded.voxel_at(1, 2, 0), Some(4));
+    assert_eq!(reloaded.voxel_at(0, 0, 1), Some(4));
+    assert_eq!(reloaded.voxels.len(), 2);
+    assert!(binvox::read_binvox(b"#binvox 1\ndim 2 2 2\ndata\n\x01\x03", 1).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn xraw_round_trip_and_quantize() {
+    let palette = Palette::magica_voxel_default();
+    let model = Model::from_voxels(Size { x: 2, y: 1, z: 3 }, vec![Voxel { x: 1, y: 0, z: 2, c: 30 }, Voxel { x: 0, y: 0, z: 0, c: 250 }]);
+    let volume = xraw::read_xraw(&xraw::export_xraw(&model, &palette)).unwrap();
+    assert_eq!(volume.get(1, 0, 2), Some(palette.get(30)));
+    assert_eq!(volume.get(1, 0, 0), None);
+    let (quantized, quantized_palette) = volume.quantize().unwrap();
+    assert_eq!(quantized.iter_colored(&quantized_palette).map(|v| v.3).collect::<Vec<_>>(), vec![palette.get(250), palette.get(30)]);
+
+    let mut volume = XrawVolume::new(Size { x: 16, y: 16, z: 2 });
+    for z in 0..2 {
+        for y in 0..16 {
+            for x in 0..16 {
+                volume.colors[x as usize + y as usize * 16 + z as usize * 256] = Color::new(x * 16, y * 16, z * 128, 255);
+            }
+        }
+    }
+    let (model, palette) = volume.quantize().unwrap();
+    assert_eq!(model.voxels.len(), 512);
+    for (x, y, z, color) in model.iter_colored(&palette) {
+        let original = volume.get(x as u32, y as u32, z as u32).unwrap();
+        assert!((color.r as i32 - original.r as i32).abs() <= 16);
+        assert!((color.b as i32 - original.b as i32).abs() <= 64);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn xraw_oversized_header() {
+    let mut data = b"XRAW".to_vec();
+    data.extend_from_slice(&[0, 4, 8, 0]);
+    for &word in &[u32::MAX, u32::MAX, u32::MAX, 0] {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    data.extend_from_slice(&[0; 8]);
+    assert!(xraw::read_xraw(&data).is_err());
+    data[8..20].copy_from_slice(&[0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0]);
+    assert!(xraw::read_xraw(&data).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn voxedit_import() {
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    let mut data = b"VXMC".to_vec();
+    for &side in &[2u32, 1, 3] {
+        data.write_u32::<LittleEndian>(side).unwrap();
+    }
+    for &pivot in &[0.5f32, 0.0, 0.25] {
+        data.write_f32::<LittleEndian>(pivot).unwrap();
+    }
+    data.push(0);
+    data.extend_from_slice(&[0; 16]);
+    data.write_u32::<LittleEndian>(0).unwrap();
+    data.push(2);
+    data.extend_from_slice(&[30, 20, 10, 255, 0, 60, 50, 40, 255, 1]);
+    data.push(0);
+    data.push(1);
+    data.extend_from_slice(b"Body\0\x01");
+    data.extend_from_slice(&[2, 0, 3, 0xff, 1, 1, 0]);
+
+    let file = voxedit::read_vxm(&data).unwrap();
+    assert_eq!(file.version, 12);
+    assert_eq!(file.palette.get(1), Color::new(10, 20, 30, 255));
+    assert_eq!(file.emissive, vec![2]);
+    assert_eq!(file.pivot, [0.5, 0.75, 0.0]);
+    let layer = &file.layers[0];
+    assert_eq!((layer.name.as_str(), layer.visible), ("Body", true));
+    assert_eq!(layer.model.size, Size { x: 2, y: 3, z: 1 });
+    assert_eq!(layer.model.voxels, vec![
+        Voxel { x: 0, y: 2, z: 0, c: 1 },
+        Voxel { x: 0, y: 1, z: 0, c: 1 },
+        Voxel { x: 1, y: 0, z: 0, c: 2 },
+    ]);
+    assert!(voxedit::read_vxm(b"VXM4").is_err());
+}
+
+#[test]
+#[cfg(feature = "image")]
+fn png_slice_export() {
+    let model = Model::from_voxels(Size { x: 2, y: 3, z: 1 }, vec![Voxel { x: 1, y: 2, z: 0, c: 1 }]);
+    let mut palette = Palette::from_rgba_chunk(&[]);
+    palette.set(1, Color::new(200, 100, 50, 255));
+
+    let (width, height, pixels) = png::decode(&slices::slice_png(&model, &palette, Axis::Z, 0).unwrap()).unwrap();
+    assert_eq!((width, height), (2, 3));
+    assert_eq!(&pixels[4..8], &[200, 100, 50, 255]);
+    assert!(pixels[8..].iter().all(|&channel| channel == 0));
+
+    let (width, height, pixels) = png::decode(&slices::slice_png(&model, &palette, Axis::X, 1).unwrap()).unwrap();
+    assert_eq!((width, height), (3, 1));
+    assert_eq!(&pixels[8..12], &[200, 100, 50, 255]);
+
+    let dir = std::env::temp_dir().join("vox_loader_png_slices");
+    let paths = slices::export_png_slices(&model, &palette, &dir, Axis::Y).unwrap();
+    assert_eq!(paths.len(), 3);
+    assert!(paths[2].ends_with("slice_002.png"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "image")]
+fn image_stack_import() {
+    let model = Model::from_voxels(Size { x: 2, y: 2, z: 2 }, vec![
+        Voxel { x: 0, y: 1, z: 0, c: 3 },
+        Voxel { x: 1, y: 0, z: 1, c: 4 },
+    ]);
+    let mut palette = Palette::from_rgba_chunk(&[]);
+    palette.set(3, Color::new(255, 0, 0, 255));
+    palette.set(4, Color::new(0, 0, 255, 255));
+    let dir = std::env::temp_dir().join("vox_loader_image_stack");
+    let paths = slices::export_png_slices(&model, &palette, &dir, Axis::Z).unwrap();
+
+    let (imported, imported_palette) = Model::from_image_stack(&paths, PaletteStrategy::FirstSeen).unwrap();
+    assert_eq!(imported.size, model.size);
+    let colors: Vec<_> = imported.iter_colored(&imported_palette).collect();
+    assert_eq!(colors, model.iter_colored(&palette).collect::<Vec<_>>());
+
+    let mut fixed = Palette::from_rgba_chunk(&[]);
+    fixed.set(7, Color::new(250, 10, 10, 255));
+    fixed.set(9, Color::new(10, 10, 250, 255));
+    let (imported, _) = Model::from_image_stack(&paths, PaletteStrategy::Fixed(fixed)).unwrap();
+    assert_eq!(imported.voxel_at(0, 1, 0), Some(7));
+    assert_eq!(imported.voxel_at(1, 0, 1), Some(9));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A 3x2 indexed PNG whose palette is black, red and blue, with black made
+/// transparent through tRNS. Rows are red, black, blue and black, black, red.
+#[cfg(all(test, feature = "image"))]
+const INDEXED_PNG: &[u8] = &[
+    137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 3, 0, 0, 0, 2, 8, 3, 0, 0, 0, 170, 170, 150,
+    40, 0, 0, 0, 9, 80, 76, 84, 69, 0, 0, 0, 255, 0, 0, 0, 0, 255, 74, 165, 173, 129, 0, 0, 0, 1, 116, 82, 78, 83, 0, 64,
+    230, 216, 102, 0, 0, 0, 16, 73, 68, 65, 84, 120, 218, 99, 96, 100, 96, 98, 96, 96, 96, 4, 0, 0, 26, 0, 5, 172, 252,
+    247, 192, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+];
+
+#[test]
+#[cfg(feature = "image")]
+fn indexed_image_stack_import() {
+    let dir = std::env::temp_dir().join("vox_loader_indexed_stack");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("layer.png");
+    std::fs::write(&path, INDEXED_PNG).unwrap();
+
+    let (model, palette) = Model::from_image_stack(&[&path], PaletteStrategy::FirstSeen).unwrap();
+    assert_eq!(model.size, Size { x: 3, y: 2, z: 1 });
+    assert_eq!(model.voxels.len(), 3);
+    assert_eq!(model.voxel_at(0, 1, 0).map(|c| palette.get(c)), Some(Color::new(255, 0, 0, 255)));
+    assert_eq!(model.voxel_at(2, 1, 0).map(|c| palette.get(c)), Some(Color::new(0, 0, 255, 255)));
+    assert_eq!(model.voxel_at(2, 0, 0).map(|c| palette.get(c)), Some(Color::new(255, 0, 0, 255)));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn voxelize_cube_mesh() {
+    let mut palette = Palette::from_rgba_chunk(&[]);
+    palette.set(4, Color::new(255, 0, 0, 255));
+    let mesh = mesher::greedy(&solid_cube(4, 4), &palette);
+
+    let mut options = VoxelizeOptions { resolution: 4, ..VoxelizeOptions::default() };
+    let (surface, surface_palette) = voxelize::voxelize_mesh(&mesh, &options);
+    assert_eq!(surface.size, Size { x: 4, y: 4, z: 4 });
+    assert_eq!(surface.voxels.len(), 56);
+    assert_eq!(surface.voxel_at(1, 1, 1), None);
+    assert!(surface.iter_colored(&surface_palette).all(|(_, _, _, color)| color == Color::new(255, 0, 0, 255)));
+
+    options.fill = FillMode::Solid;
+    let (solid, _) = voxelize::voxelize_mesh(&mesh, &options);
+    assert_eq!(solid.voxels.len(), 64);
+
+    options.resolution = 8;
+    let (scaled, _) = voxelize::voxelize(&mesh.positions, &mesh.indices, None, &options);
+    assert_eq!(scaled.size, Size { x: 8, y: 8, z: 8 });
+    assert_eq!(scaled.voxels.len(), 512);
+}
+
+#[test]
+fn in_place_editing_keeps_index() {
+    let mut model = solid_cube(2, 3);
+    assert_eq!(model.voxel_at(1, 1, 1), Some(3));
+    assert!(model.set_voxel(1, 1, 1, 5));
+    assert!(!model.set_voxel(2, 0, 0, 5));
+    assert_eq!(model.voxel_at(1, 1, 1), Some(5));
+    assert_eq!(model.clear_voxel(0, 0, 0), Some(3));
+    assert_eq!(model.clear_voxel(0, 0, 0), None);
+    assert_eq!(model.voxel_at(0, 0, 0), None);
+    assert_eq!(model.voxels.len(), 7);
+
+    assert_eq!(model.replace_color(3, 9), 6);
+    assert_eq!(model.voxel_at(0, 1, 0), Some(9));
+    assert_eq!(model.replace_color(9, 0), 6);
+    assert_eq!(model.voxels, vec![Voxel { x: 1, y: 1, z: 1, c: 5 }]);
+    assert_eq!(model.voxel_at(0, 1, 0), None);
+    assert!(model.set_voxel(0, 1, 0, 2));
+    assert_eq!(model.voxel_at(0, 1, 0), Some(2));
+    assert_eq!(model.voxels.len(), 2);
+
+    let mut wide = Model::new(Size { x: 300, y: 1, z: 1 });
+    assert!(!wide.set_voxel(299, 0, 0, 4));
+    assert!(wide.set_voxel(255, 0, 0, 4));
+    assert_eq!(wide.voxels, vec![Voxel { x: 255, y: 0, z: 0, c: 4 }]);
+}
+
+#[test]
+fn quarter_turn_rotations() {
+    let model = Model::from_voxels(Size { x: 3, y: 2, z: 1 }, vec![Voxel { x: 2, y: 0, z: 0, c: 1 }]);
+    let turned = model.rotated_90(Axis::Z, 1);
+    assert_eq!(turned.size, Size { x: 2, y: 3, z: 1 });
+    assert_eq!(turned.voxels, vec![Voxel { x: 1, y: 2, z: 0, c: 1 }]);
+    assert_eq!(model.rotated_90(Axis::Z, -1), model.rotated_90(Axis::Z, 3));
+    for &axis in &[Axis::X, Axis::Y, Axis::Z] {
+        assert_eq!(model.rotated_90(axis, 4), model);
+        assert_eq!(model.rotated_90(axis, 1).rotated_90(axis, -1), model);
+    }
+    let tipped = model.rotated_90(Axis::X, 1);
+    assert_eq!(tipped.size, Size { x: 3, y: 1, z: 2 });
+    assert_eq!(tipped.voxels, vec![Voxel { x: 2, y: 0, z: 0, c: 1 }]);
+    assert_eq!(model.rotated_90(Axis::Y, 1).voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }]);
+    let stray = Model::from_voxels(Size { x: 0, y: 0, z: 0 }, vec![Voxel { x: 1, y: 1, z: 1, c: 1 }]);
+    assert!(stray.rotated_90(Axis::Z, 1).voxels.is_empty());
+}
+
+#[test]
+fn mirror_flips() {
+    let model = Model::from_voxels(Size { x: 4, y: 2, z: 3 }, vec![Voxel { x: 0, y: 1, z: 2, c: 6 }]);
+    assert_eq!(model.flipped(Axis::X).voxels, vec![Voxel { x: 3, y: 1, z: 2, c: 6 }]);
+    assert_eq!(model.flipped(Axis::Y).voxels, vec![Voxel { x: 0, y: 0, z: 2, c: 6 }]);
+    assert_eq!(model.flipped(Axis::Z).voxels, vec![Voxel { x: 0, y: 1, z: 0, c: 6 }]);
+    assert_eq!(model.flipped(Axis::Z).flipped(Axis::Z), model);
+    assert_eq!(model.flipped(Axis::X).size, model.size);
+    let stray = Model::from_voxels(Size { x: 0, y: 0, z: 0 }, vec![Voxel { x: 0, y: 1, z: 2, c: 6 }]);
+    assert!(stray.flipped(Axis::X).voxels.is_empty());
+}
+
+#[test]
+fn crop_and_translate() {
+    let model = Model::from_voxels(Size { x: 4, y: 4, z: 4 }, vec![
+        Voxel { x: 1, y: 1, z: 1, c: 1 },
+        Voxel { x: 3, y: 2, z: 0, c: 2 },
+    ]);
+    assert_eq!(model.bounds(), Some(Aabb::new([1, 1, 0], [4, 3, 2])));
+    let cropped = model.crop(Aabb::new([1, 1, 1], [3, 9, 3]));
+    assert_eq!(cropped.size, Size { x: 2, y: 3, z: 2 });
+    assert_eq!(cropped.voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }]);
+
+    let clipped = model.translated(1, 0, -1, Overflow::Clip);
+    assert_eq!(clipped.size, model.size);
+    assert_eq!(clipped.voxels, vec![Voxel { x: 2, y: 1, z: 0, c: 1 }]);
+    let grown = model.translated(1, 0, -1, Overflow::Grow);
+    assert_eq!(grown.size, Size { x: 5, y: 4, z: 5 });
+    assert_eq!(grown.voxels, vec![Voxel { x: 2, y: 1, z: 1, c: 1 }, Voxel { x: 4, y: 2, z: 0, c: 2 }]);
+}
+
+#[test]
+fn resize_around_anchor() {
+    let model = Model::from_voxels(Size { x: 2, y: 2, z: 2 }, vec![Voxel { x: 1, y: 0, z: 1, c: 4 }]);
+    let size = Size { x: 6, y: 6, z: 6 };
+    assert_eq!(model.resized(size, Anchor::Min).voxels, vec![Voxel { x: 1, y: 0, z: 1, c: 4 }]);
+    assert_eq!(model.resized(size, Anchor::Center).voxels, vec![Voxel { x: 3, y: 2, z: 3, c: 4 }]);
+    assert_eq!(model.resized(size, Anchor::Max).voxels, vec![Voxel { x: 5, y: 4, z: 5, c: 4 }]);
+    assert_eq!(model.resized(size, Anchor::BottomCenter).voxels, vec![Voxel { x: 3, y: 2, z: 1, c: 4 }]);
+    let trimmed = model.resized(Size { x: 1, y: 1, z: 1 }, Anchor::Max);
+    assert_eq!(trimmed.size, Size { x: 1, y: 1, z: 1 });
+    assert!(trimmed.voxels.is_empty());
+    assert_eq!(model.resized(Size { x: 1, y: 2, z: 1 }, Anchor::Max).voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 4 }]);
+}
+
+#[test]
+fn integer_upscaling() {
+    let model = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 1, y: 0, z: 0, c: 8 }]);
+    let doubled = model.upscaled(2);
+    assert_eq!(doubled.size, Size { x: 4, y: 2, z: 2 });
+    assert_eq!(doubled.voxels.len(), 8);
+    assert_eq!(doubled.voxel_at(3, 1, 1), Some(8));
+    assert_eq!(doubled.voxel_at(1, 0, 0), None);
+    assert_eq!(model.upscaled(1), model);
+
+    let capped = Model::from_voxels(Size { x: 200, y: 1, z: 1 }, vec![Voxel { x: 199, y: 0, z: 0, c: 1 }]).upscaled(2);
+    assert_eq!(capped.size.x, 256);
+    assert!(capped.voxels.is_empty());
+
+    let huge = model.upscaled(u32::MAX);
+    assert_eq!(huge.size, Size { x: 256, y: 256, z: 256 });
+    assert!(huge.voxels.is_empty());
+}
+
+#[test]
+fn downsampled_levels_of_detail() {
+    let mut palette = Palette::from_rgba_chunk(&[]);
+    palette.set(1, Color::new(0, 0, 0, 255));
+    palette.set(2, Color::new(200, 200, 200, 255));
+    palette.set(3, Color::new(100, 100, 100, 255));
+    let mut model = solid_cube(2, 2);
+    model.voxels[0].c = 1;
+    model.voxels.push(Voxel { x: 2, y: 0, z: 0, c: 1 });
+    model.size.x = 3;
+
+    let majority = model.downsampled(2, ColorPick::Majority, &palette);
+    assert_eq!(majority.size, Size { x: 2, y: 1, z: 1 });
+    assert_eq!(majority.voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 2 }, Voxel { x: 1, y: 0, z: 0, c: 1 }]);
+
+    model.voxels.truncate(2);
+    model.voxels[1].c = 2;
+    let average = model.downsampled(2, ColorPick::Average, &palette);
+    assert_eq!(average.voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 3 }]);
+    assert!(model.downsampled_with(2, ColorPick::Majority, &palette, 3).voxels.is_empty());
+}
+
+#[test]
+fn boolean_operations() {
+    let base = solid_cube(2, 1);
+    let brush = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 2 }, Voxel { x: 1, y: 0, z: 0, c: 2 }]);
+
+    let union = base.union(&brush, [1, 1, 1], Conflict::KeepOurs);
+    assert_eq!(union.voxels.len(), 8);
+    assert_eq!(union.voxel_at(1, 1, 1), Some(1));
+    assert_eq!(base.union(&brush, [1, 1, 1], Conflict::KeepTheirs).voxel_at(1, 1, 1), Some(2));
+
+    let subtracted = base.subtract(&brush, [-1, 0, 0]);
+    assert_eq!(subtracted.voxels.len(), 7);
+    assert_eq!(subtracted.voxel_at(0, 0, 0), None);
+
+    let intersected = base.intersect(&brush, [0, 1, 0], Conflict::KeepTheirs);
+    assert_eq!(intersected.voxels, vec![Voxel { x: 0, y: 1, z: 0, c: 2 }, Voxel { x: 1, y: 1, z: 0, c: 2 }]);
+    assert_eq!(base.intersect(&brush, [0, 1, 0], Conflict::KeepOurs).voxel_at(1, 1, 0), Some(1));
+}
+
+#[test]
+fn flood_fill_regions() {
+    let mut model = solid_cube(3, 1);
+    model.voxels.retain(|v| (v.x, v.y, v.z) != (1, 1, 1));
+    model.set_voxel(0, 0, 0, 2);
+
+    assert_eq!(model.flood_select([1, 1, 1]), vec![[1, 1, 1]]);
+    assert_eq!(model.flood_select([2, 2, 2]).len(), 25);
+    assert!(model.flood_select([3, 0, 0]).is_empty());
+
+    assert_eq!(model.flood_fill([2, 2, 2], 5), 25);
+    assert_eq!(model.voxel_at(0, 0, 0), Some(2));
+    assert_eq!(model.voxel_at(0, 1, 0), Some(5));
+    assert_eq!(model.flood_fill([1, 1, 1], 3), 1);
+    assert_eq!(model.voxels.len(), 27);
+    assert_eq!(model.flood_fill([0, 0, 0], 2), 0);
+}
+
+#[test]
+fn hollowed_shells() {
+    let model = solid_cube(6, 2);
+    assert_eq!(model.hollowed(1).voxels.len(), 216 - 64);
+    assert_eq!(model.hollowed(2).voxels.len(), 216 - 8);
+    assert_eq!(model.hollowed(3), model);
+    assert!(model.hollowed(1).voxel_at(2, 2, 2).is_none());
+    assert_eq!(model.hollowed(1).voxel_at(0, 3, 3), Some(2));
+
+    let mut stray = model.clone();
+    stray.voxels.push(Voxel { x: 9, y: 0, z: 0, c: 2 });
+    assert_eq!(stray.hollowed(3), model);
+}
+
+#[test]
+fn connected_component_split() {
+    let model = Model::from_voxels(Size { x: 4, y: 4, z: 1 }, vec![
+        Voxel { x: 0, y: 0, z: 0, c: 1 },
+        Voxel { x: 3, y: 3, z: 0, c: 1 },
+        Voxel { x: 1, y: 0, z: 0, c: 2 },
+        Voxel { x: 1, y: 1, z: 0, c: 2 },
+        Voxel { x: 2, y: 2, z: 0, c: 3 },
+    ]);
+    assert_eq!(model.connected_components(), vec![0, 1, 0, 0, 2]);
+    let pieces = model.split_components();
+    assert_eq!(pieces.len(), 3);
+    assert_eq!(pieces[0].voxels.len(), 3);
+    assert_eq!(pieces[1].voxels, vec![Voxel { x: 3, y: 3, z: 0, c: 1 }]);
+    assert_eq!(pieces[2].size, model.size);
+
+    let mut stray = model.clone();
+    stray.voxels.insert(1, Voxel { x: 7, y: 0, z: 0, c: 4 });
+    assert_eq!(stray.connected_components(), vec![0, 1, 2, 0, 0, 3]);
+    assert_eq!(stray.split_components()[1].voxels, vec![Voxel { x: 7, y: 0, z: 0, c: 4 }]);
+}
+
+#[test]
+fn merge_with_palette_remapping() {
+    let mut ours = Palette::from_rgba_chunk(&[]);
+    ours.set(1, Color::new(255, 0, 0, 255));
+    ours.set(2, Color::new(0, 0, 200, 255));
+    let mut theirs = Palette::from_rgba_chunk(&[]);
+    theirs.set(1, Color::new(0, 0, 255, 255));
+    theirs.set(4, Color::new(255, 0, 0, 255));
+    let model = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }]);
+    let other = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }, Voxel { x: 1, y: 0, z: 0, c: 4 }]);
+
+    let (merged, palette) = model.merge(&ours, &other, &theirs, [-1, 0, 2], PaletteMerge::Append);
+    assert_eq!(merged.size, Size { x: 3, y: 1, z: 3 });
+    assert_eq!(merged.voxel_at(1, 0, 0), Some(1));
+    assert_eq!(merged.voxel_at(1, 0, 2), Some(1));
+    let blue = merged.voxel_at(0, 0, 2).unwrap();
+    assert_eq!(blue, 2);
+    assert_eq!(palette.get(blue), Color::new(0, 0, 255, 255));
+
+    let (merged, palette) = model.merge(&ours, &other, &theirs, [0, 0, 0], PaletteMerge::Nearest);
+    assert_eq!(palette, ours);
+    assert_eq!(merged.voxels.len(), 2);
+    assert_eq!(merged.voxel_at(0, 0, 0), Some(2));
+    assert_eq!(merged.voxel_at(1, 0, 0), Some(1));
+    assert_eq!(model.merge(&ours, &other, &theirs, [0, 0, 0], PaletteMerge::Shared).0.voxel_at(1, 0, 0), Some(4));
+
+    // 254 grays fill every free slot, including the white one at 200, so
+    // their white falls back to the closest color actually left.
+    let gray = |value: u8| Color::new(value, value, value, 255);
+    ours.set(200, gray(255));
+    let mut theirs = Palette::from_rgba_chunk(&[]);
+    let mut many = Model::new(Size { x: 16, y: 16, z: 1 });
+    for index in 1..=255u8 {
+        theirs.set(index, gray(index));
+        many.set_voxel(index as u32 % 16, index as u32 / 16, 0, index);
+    }
+    let (merged, palette) = model.merge(&ours, &many, &theirs, [0, 0, 1], PaletteMerge::Append);
+    assert_eq!(palette.get(merged.voxel_at(1, 0, 1).unwrap()), gray(1));
+    assert_eq!(palette.get(merged.voxel_at(15, 15, 1).unwrap()), gray(254));
+}
+
+#[test]
+fn trim_to_bounds() {
+    let model = Model::from_voxels(Size { x: 32, y: 32, z: 32 }, vec![
+        Voxel { x: 4, y: 10, z: 2, c: 1 },
+        Voxel { x: 6, y: 9, z: 2, c: 2 },
+    ]);
+    let trimmed = model.trimmed();
+    assert_eq!(trimmed.size, Size { x: 3, y: 2, z: 1 });
+    assert_eq!(trimmed.voxels, vec![Voxel { x: 0, y: 1, z: 0, c: 1 }, Voxel { x: 2, y: 0, z: 0, c: 2 }]);
+    assert_eq!(trimmed.trimmed(), trimmed);
+    assert_eq!(Model::new(model.size).bounds(), None);
+    assert_eq!(Model::new(model.size).trimmed().size, Size { x: 0, y: 0, z: 0 });
+}
+
+#[test]
+fn surface_voxel_extraction() {
+    let model = solid_cube(3, 1);
+    let surface = model.surface_voxels();
+    assert_eq!(surface.len(), 26);
+    assert!(!surface.contains(&Voxel { x: 1, y: 1, z: 1, c: 1 }));
+    let faces = model.surface_faces();
+    assert_eq!(faces[0], (Voxel { x: 0, y: 0, z: 0, c: 1 }, 0b101010));
+    assert!(faces.contains(&(Voxel { x: 1, y: 1, z: 2, c: 1 }, 0b010000)));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn face_visibility_masks() {
+    let model = solid_cube(3, 1);
+    let masks = model.face_masks();
+    assert_eq!(masks.len(), model.voxels.len());
+    let center = model.voxels.iter().position(|v| (v.x, v.y, v.z) == (1, 1, 1)).unwrap();
+    assert_eq!(masks[center], 0);
+    assert_eq!(masks.iter().filter(|&&mask| mask != 0).count(), model.surface_voxels().len());
+    let visible_faces: u32 = masks.iter().map(|mask| mask.count_ones()).sum();
+    assert_eq!(visible_faces as usize * 2, mesher::culled(&model, &Palette::default()).triangle_count());
+
+    let single = Model::from_voxels(Size { x: 1, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }]);
+    assert_eq!(single.face_masks(), vec![0b111111]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn dda_raycast() {
+    let model = Model::from_voxels(Size { x: 4, y: 4, z: 4 }, vec![Voxel { x: 2, y: 1, z: 1, c: 7 }]);
+    let hit = model.raycast([-3.0, 1.5, 1.5], [2.0, 0.0, 0.0], 100.0).unwrap();
+    assert_eq!(hit.voxel, Voxel { x: 2, y: 1, z: 1, c: 7 });
+    assert_eq!(hit.face, [-1, 0, 0]);
+    assert!((hit.t - 5.0).abs() < 1e-5);
+    assert!(model.raycast([-3.0, 1.5, 1.5], [1.0, 0.0, 0.0], 4.0).is_none());
+
+    let hit = model.raycast([2.5, 1.5, 3.9], [0.0, 0.0, -1.0], 10.0).unwrap();
+    assert_eq!(hit.face, [0, 0, 1]);
+    assert!((hit.t - 1.9).abs() < 1e-5);
+    let diagonal = model.raycast([0.5, -0.5, 1.5], [1.0, 1.0, 0.0], 10.0).unwrap();
+    assert_eq!(diagonal.voxel.x, 2);
+    assert!(model.raycast([0.5, 0.5, 0.5], [0.0, 1.0, 0.0], 10.0).is_none());
+    assert_eq!(model.raycast([2.2, 1.2, 1.2], [0.0, 1.0, 0.0], 1.0).unwrap().face, [0, 0, 0]);
+}
+
+#[test]
+fn bitgrid_occupancy() {
+    let mut model = solid_cube(3, 1);
+    model.voxels.retain(|v| v.z != 2 || (v.x, v.y) == (1, 1));
+    let grid = model.to_bitgrid();
+    assert_eq!(grid.count(), 19);
+    assert_eq!(grid.count_slice(0), 9);
+    assert_eq!(grid.count_slice(2), 1);
+    assert_eq!(grid.words().len(), 1);
+    assert!(grid.get(1, 1, 2) && !grid.get(0, 0, 2) && !grid.get(-1, 0, 0));
+    assert_eq!(grid.neighbors(1, 1, 1), 0b111111);
+    assert_eq!(grid.neighbors(0, 0, 0), 0b010101);
+    assert!((grid.fill_ratio() - 19.0 / 27.0).abs() < 1e-6);
+
+    let mut big = BitGrid::new(Size { x: 10, y: 10, z: 2 });
+    assert!(big.set(9, 9, 0, true) && big.set(0, 0, 1, true));
+    assert_eq!((big.count_slice(0), big.count_slice(1)), (1, 1));
+    big.set(9, 9, 0, false);
+    assert_eq!(big.count(), 1);
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[test]
+fn memory_mapped_loading() {
+    let path = std::env::temp_dir().join("vox_loader_mmap.vox");
+    std::fs::write(&path, TINY_VOX).unwrap();
+    // Nothing else touches the file while it is mapped.
+    let vl = unsafe { VoxLoader::from_mmap(&path) }.unwrap();
+    assert_eq!(vl.models()[0].voxels[0].c, 79);
+    std::fs::remove_file(&path).unwrap();
+    assert!(unsafe { VoxLoader::from_mmap(&path) }.is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn lazy_model_index() {
+    let mut vl = VoxLoader::from_bytes(TINY_VOX);
+    vl.models_mut().push(Model::from_voxels(Size { x: 2, y: 2, z: 2 }, vec![Voxel { x: 1, y: 1, z: 1, c: 9 }]));
+    vl.models_mut().push(Model::new(Size { x: 4, y: 1, z: 1 }));
+    let bytes = VoxWriter::to_bytes(&vl);
+
+    let mut index = VoxIndex::from_reader(std::io::Cursor::new(bytes)).unwrap();
+    assert_eq!(index.version(), 150);
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.models()[1].voxel_count, 1);
+    assert_eq!(index.palette, vl.palette);
+    assert_eq!(index.load_model(1).unwrap(), vl.models()[1]);
+    assert_eq!(index.load_model(0).unwrap(), vl.models()[0]);
+    assert_eq!(index.load_model(2).unwrap().size, Size { x: 4, y: 1, z: 1 });
+    assert!(index.load_model(3).is_err());
+
+    // A voxel count far beyond the XYZI chunk is refused before reading.
+    let mut lying = TINY_VOX.to_vec();
+    lying[56..60].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut index = VoxIndex::from_reader(std::io::Cursor::new(lying)).unwrap();
+    assert_eq!(index.load_model(0).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    assert!(VoxIndex::from_reader(std::io::Cursor::new(b"RIFF0000".to_vec())).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn many_model_parsing() {
+    let mut vl = VoxLoader::from_bytes(TINY_VOX);
+    for i in 0..20u8 {
+        vl.models_mut().push(Model::from_voxels(Size { x: 1, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: i + 1 }]));
+    }
+    let reloaded = VoxLoader::from_bytes(&VoxWriter::to_bytes(&vl));
+    assert_eq!(reloaded.models(), vl.models());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_reader_loading() {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    let mut context = Context::from_waker(Waker::noop());
+    let mut future = VoxLoader::from_async_reader(TINY_VOX);
+    match std::pin::Pin::new(&mut future).poll(&mut context) {
+        Poll::Ready(vl) => assert_eq!(vl.unwrap().models()[0].voxels[0].c, 79),
+        Poll::Pending => panic!("a byte slice never blocks"),
+    }
+    let mut future = VoxLoader::from_async_reader(&b"RIFF"[..]);
+    assert!(matches!(std::pin::Pin::new(&mut future).poll(&mut context), Poll::Ready(Err(_))));
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn wasm_parse_facade() {
+    let file = match wasm::parse(TINY_VOX) {
+        Ok(file) => file,
+        Err(_) => panic!("TINY_VOX should parse"),
+    };
+    assert_eq!(file.version(), 150);
+    let models = file.models();
+    assert_eq!((models[0].size_x(), models[0].voxel_count()), (3, 1));
+    assert_eq!(models[0].voxels(), vec![1, 2, 0, 79]);
+    assert_eq!(file.palette().len(), 1024);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn progress_callbacks() {
+    struct Recorder {
+        chunks: Vec<String>,
+        last: (usize, usize),
+    }
+    impl Progress for Recorder {
+        fn on_progress(&mut self, bytes_read: usize, total: usize) {
+            assert!(bytes_read >= self.last.0);
+            self.last = (bytes_read, total);
+        }
+
+        fn on_chunk(&mut self, id: &str) {
+            self.chunks.push(id.to_string());
+        }
+    }
+
+    let mut recorder = Recorder { chunks: Vec::new(), last: (0, 0) };
+    VoxLoader::from_bytes_with_progress(TINY_VOX, &mut recorder);
+    assert_eq!(&recorder.chunks[..3], ["MAIN", "SIZE", "XYZI"]);
+    assert_eq!(recorder.last, (TINY_VOX.len(), TINY_VOX.len()));
+}
+
+#[test]
+fn strict_and_lenient_parsing() {
+    let mut bytes = TINY_VOX.to_vec();
+    bytes[16] += 12;
+    bytes.extend_from_slice(b"ABCD");
+    bytes.extend_from_slice(&[0; 8]);
+    bytes[60] = 7;
+
+    let lenient = VoxLoader::from_bytes_with_options(&bytes, &ParseOptions::default()).unwrap();
+    assert!(lenient.models()[0].voxels.is_empty());
+    assert_eq!(lenient.warnings().len(), 2);
+    assert!(lenient.warnings()[0].contains("ABCD"));
+
+    let strict = VoxLoader::from_bytes_with_options(&bytes, &ParseOptions { strict: true, ..ParseOptions::default() });
+    assert!(strict.unwrap_err().message.contains("ABCD"));
+    assert!(VoxLoader::from_bytes_with_options(TINY_VOX, &ParseOptions { strict: true, ..ParseOptions::default() }).is_ok());
+}
+
+#[test]
+fn lenient_oversized_model() {
+    let mut bytes = TINY_VOX.to_vec();
+    bytes[32..40].copy_from_slice(&[0x70, 0x11, 1, 0, 0x70, 0x11, 1, 0]);
+    let vl = VoxLoader::from_bytes(&bytes);
+    assert_eq!(vl.warnings().len(), 1);
+    assert_eq!(vl.models()[0].size, Size { x: 256, y: 256, z: 3 });
+    assert_eq!(vl.models()[0].voxel_at(1, 2, 0), Some(79));
+    assert_eq!(vl.models()[0].stats().voxel_count, 1);
+    assert!(VoxLoader::from_bytes_with_options(&bytes, &ParseOptions { strict: true, ..ParseOptions::default() }).is_err());
+}
+
+#[test]
+fn truncated_file_recovery() {
+    let mut bytes = TINY_VOX.to_vec();
+    bytes[16] += 44;
+    bytes.extend_from_slice(&TINY_VOX[20..]);
+    for cut in 0..bytes.len() {
+        let vl = VoxLoader::from_bytes(&bytes[..cut]);
+        assert!(vl.truncated_at().is_some());
+        assert!(vl.warnings().iter().any(|warning| warning.starts_with("truncated")));
+        assert_eq!(vl.models().len(), if cut >= 64 { 1 } else { 0 });
+        assert!(VoxLoader::from_bytes_with_options(&bytes[..cut], &ParseOptions { strict: true, ..ParseOptions::default() }).is_err());
+    }
+    let vl = VoxLoader::from_bytes(&bytes);
+    assert_eq!((vl.models().len(), vl.truncated_at()), (2, None));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn unknown_chunk_round_trip() {
+    let mut bytes = TINY_VOX.to_vec();
+    bytes[16] += 15;
+    bytes.extend_from_slice(b"zEXT");
+    bytes.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0, 7, 8, 9]);
+    let vl = VoxLoader::from_bytes(&bytes);
+    assert_eq!(vl.unknown_chunks(), [RawChunk {
+        id: "zEXT".to_string(),
+        content: vec![7, 8, 9],
+        children: Vec::new(),
+        after: Some(("XYZI".to_string(), 0)),
+    }]);
+
+    let reloaded = VoxLoader::from_bytes(&VoxWriter::to_bytes(&vl));
+    assert_eq!(reloaded.unknown_chunks(), vl.unknown_chunks());
+    assert_eq!(reloaded.models()[0].voxels, vl.models()[0].voxels);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn lossless_scene_round_trip() {
+    let dict = |pairs: &[(&str, &str)]| {
+        let mut dict = Dict::new();
+        for &(key, value) in pairs {
+            dict.insert(key, value);
+        }
+        dict.to_bytes()
+    };
+    let ints = |values: &[u32]| values.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>();
+    let rgba: Vec<u8> = Palette::magica_voxel_default().to_rgba_chunk().iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+    let camera = [("_mode", "pers"), ("_focus", "0 0 0"), ("_angle", "30 45 0"), ("_radius", "100"), ("_frustum", "0.4"), ("_fov", "45")];
+
+    let mut writer = ChunkWriter::with_header(200);
+    writer.begin(b"MAIN", &[]);
+    writer.chunk(b"SIZE", &ints(&[3, 3, 3]));
+    writer.chunk(b"XYZI", &[1, 0, 0, 0, 1, 2, 0, 79]);
+    writer.chunk(b"zMID", &[4, 5]);
+    writer.chunk(b"nTRN", &[ints(&[0]), dict(&[]), ints(&[1, u32::MAX, u32::MAX, 1]), dict(&[])].concat());
+    writer.chunk(b"nGRP", &[ints(&[1]), dict(&[]), ints(&[1, 2])].concat());
+    writer.chunk(b"nTRN", &[ints(&[2]), dict(&[("_name", "arm"), ("_hidden", "1")]), ints(&[3, u32::MAX, 0, 1]), dict(&[("_r", "17"), ("_t", "1 -2 3")])].concat());
+    writer.chunk(b"nSHP", &[ints(&[3]), dict(&[]), ints(&[1, 0]), dict(&[])].concat());
+    writer.chunk(b"LAYR", &[ints(&[0]), dict(&[("_name", "base")]), ints(&[u32::MAX])].concat());
+    writer.chunk(b"RGBA", &rgba);
+    writer.chunk(b"IMAP", &(0..=255u8).rev().collect::<Vec<u8>>());
+    writer.chunk(b"MATL", &[ints(&[1]), dict(&[("_type", "_metal"), ("_weight", "0.5"), ("_metal", "1"), ("_rough", "0.25")])].concat());
+    writer.chunk(b"rOBJ", &dict(&[("_type", "_bloom"), ("_mix", "0.5")]));
+    writer.chunk(b"rCAM", &[ints(&[0]), dict(&camera)].concat());
+    writer.chunk(b"NOTE", &[ints(&[2, 3]), b"sky".to_vec(), ints(&[0])].concat());
+    writer.begin(b"zEXT", &[7, 8, 9]);
+    writer.chunk(b"zSUB", &[1, 2]);
+    writer.end();
+    let bytes = writer.finish();
+
+    let vl = VoxLoader::from_bytes(&bytes);
+    assert_eq!(vl.unknown_chunks().len(), 2);
+    assert_eq!(vl.unknown_chunks()[0].after, Some(("XYZI".to_string(), 0)));
+    assert_eq!(vl.unknown_chunks()[1].children, [&b"zSUB"[..], &[2, 0, 0, 0, 0, 0, 0, 0, 1, 2]].concat());
+    assert_eq!(vl.scene().nodes.len(), 4);
+    assert_eq!(VoxWriter::to_bytes(&vl), bytes);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn low_level_chunks() {
+    let mut writer = ChunkWriter::with_header(150);
+    writer.begin(b"MAIN", &[]);
+    writer.chunk(b"SIZE", &TINY_VOX[32..44]);
+    writer.chunk(b"XYZI", &TINY_VOX[56..64]);
+    assert_eq!(writer.finish(), TINY_VOX);
+
+    let (version, mut top) = ChunkReader::from_file(TINY_VOX).unwrap();
+    let main = top.next().unwrap().unwrap();
+    assert_eq!((version, main.id_str(), main.offset), (150, "MAIN", 8));
+    let ids: Vec<_> = main.children().map(|chunk| chunk.unwrap().id).collect();
+    assert_eq!(ids, [*b"SIZE", *b"XYZI"]);
+    assert_eq!(main.children().nth(1).unwrap().unwrap().offset, 44);
+    assert!(top.next().is_none());
+
+    let mut cut = ChunkReader::new(&TINY_VOX[8..60]);
+    assert!(cut.next().unwrap().is_err());
+    assert!(cut.next().is_none());
+}
+
+#[test]
+fn palette_remapping() {
+    let mut model = VoxLoader::from_bytes(TINY_VOX).models()[0].clone();
+    model.set_voxel(0, 0, 0, 3);
+    let mut palette = Palette::magica_voxel_default();
+    let mut mapping = [0u8; 256];
+    for (i, entry) in mapping.iter_mut().enumerate() {
+        *entry = 255 - i as u8;
+    }
+    mapping[0] = 0;
+    let before = (palette.get(79), palette.get(3));
+    palette.remap(&mapping);
+    assert_eq!(model.remap_colors(&mapping), 2);
+    assert_eq!((model.voxel_at(1, 2, 0), model.voxel_at(0, 0, 0)), (Some(176), Some(252)));
+    assert_eq!((palette.get(176), palette.get(252)), before);
+
+    let mut studio = Palette::magica_voxel_default();
+    studio.set(9, before.0);
+    let to_studio = palette.mapping_to(&studio);
+    model.remap_colors(&to_studio);
+    assert_eq!(studio.get(model.voxel_at(1, 2, 0).unwrap()), before.0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn perceptual_quantization() {
+    let mut target = Palette::magica_voxel_default();
+    target.set(1, Color::new(250, 0, 0, 255));
+    target.set(2, Color::new(0, 0, 250, 255));
+    assert_eq!(quantize::nearest_index(Color::new(255, 10, 10, 255), &target), 1);
+    assert_eq!(quantize::nearest_index(target.get(200), &target), 200);
+
+    let mut source = Palette::magica_voxel_default();
+    source.set(5, Color::new(255, 0, 0, 255));
+    let mut model = Model::new(Size { x: 4, y: 4, z: 1 });
+    for (x, y) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+        model.set_voxel(x, y, 0, 5);
+    }
+    let mut plain = model.clone();
+    assert_eq!(plain.requantize(&source, &target, Dither::None), 16);
+    let red = quantize::nearest_index(source.get(5), &target);
+    assert!(plain.iter().all(|(_, _, _, c)| c == red));
+    assert_eq!(model.requantize(&source, &target, Dither::Ordered), 16);
+    assert_eq!(model.voxels.len(), 16);
+}
+
+#[test]
+fn official_default_palette() {
+    // MagicaVoxel's default is a 6x6x6 color cube without black, followed
+    // by red, green, blue and gray ramps.
+    let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut expected = vec![0u32];
+    for &r in &steps {
+        for &g in &steps {
+            for &b in &steps {
+                if (r, g, b) != (0, 0, 0) {
+                    expected.push(r << 24 | g << 16 | b << 8 | 0xff);
+                }
+            }
+        }
+    }
+    for shift in [24, 16, 8] {
+        expected.extend(ramp.iter().map(|&v| v << shift | 0xff));
+    }
+    expected.extend(ramp.iter().map(|&v| v << 24 | v << 16 | v << 8 | 0xff));
+    assert_eq!(DEFAULT_PALETTE[..], expected[..]);
+
+    let palette = Palette::default();
+    assert_eq!(palette.get(1), Color::new(255, 255, 255, 255));
+    assert_eq!(palette.get(255), Color::new(0x11, 0x11, 0x11, 255));
+    assert_eq!(palette.as_u32(PackOrder::Rgba), DEFAULT_PALETTE);
+    assert_eq!(VoxLoader::from_bytes(TINY_VOX).palette.colors(), palette.colors());
+}
+
+#[test]
+fn nearest_color_search() {
+    let mut palette = Palette::default();
+    let brute = |palette: &Palette, r: u8, g: u8, b: u8| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        (1..=255u8).min_by_key(|&i| {
+            let c = palette.get(i);
+            (d(c.r, r) + d(c.g, g) + d(c.b, b), i)
+        }).unwrap()
+    };
+    for step in 0..2000u32 {
+        let (r, g, b) = ((step * 37 % 256) as u8, (step * 101 % 256) as u8, (step * 211 % 256) as u8);
+        assert_eq!(palette.closest_index(r, g, b), brute(&palette, r, g, b));
+    }
+    assert_eq!(palette.closest_index(255, 255, 255), 1);
+    palette.set(200, Color::new(1, 2, 3, 255));
+    assert_eq!(palette.closest_index(1, 2, 3), 200);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn coordinate_system_conversion() {
+    let options = ParseOptions { target_coords: CoordinateSystem::YUpRightHanded, ..ParseOptions::default() };
+    let vl = VoxLoader::from_bytes_with_options(TINY_VOX, &options).unwrap();
+    assert_eq!(vl.coordinate_system(), CoordinateSystem::YUpRightHanded);
+    let model = &vl.models()[0];
+    assert_eq!(model.voxels[0], Voxel { x: 1, y: 0, z: 0, c: 79 });
+
+    let mut tall = Model::new(Size { x: 2, y: 3, z: 4 });
+    tall.set_voxel(1, 2, 3, 9);
+    let left = tall.converted(CoordinateSystem::ZUpRightHanded, CoordinateSystem::YUpLeftHanded);
+    assert_eq!((left.size, left.voxel_at(1, 3, 2)), (Size { x: 2, y: 4, z: 3 }, Some(9)));
+    let back = left.converted(CoordinateSystem::YUpLeftHanded, CoordinateSystem::ZUpRightHanded);
+    assert_eq!(back, tall);
+
+    let stray = Model::from_voxels(Size { x: 0, y: 0, z: 0 }, vec![Voxel { x: 1, y: 2, z: 3, c: 9 }]);
+    assert!(stray.converted(CoordinateSystem::ZUpRightHanded, CoordinateSystem::YUpRightHanded).voxels.is_empty());
+
+    let mut scene = SceneGraph::default();
+    let quarter = Rotation::from_matrix([[0, -1, 0], [1, 0, 0], [0, 0, 1]]).unwrap();
+    scene.insert(SceneNode::Transform(TransformNode { id: 0, child: 1, layer_id: 0, rotation: quarter, translation: [1, 2, 3], name: None, hidden: false }));
+    scene.convert(CoordinateSystem::ZUpRightHanded, CoordinateSystem::YUpRightHanded);
+    match scene.node(0) {
+        Some(SceneNode::Transform(node)) => {
+            assert_eq!(node.translation, [1, 3, -2]);
+            assert_eq!(node.rotation.to_matrix(), [[0, 0, 1], [0, 1, 0], [-1, 0, 0]]);
+        }
+        other => panic!("unexpected node {:?}", other),
+    }
+
+    let reloaded = VoxLoader::from_bytes(&VoxWriter::to_bytes(&vl));
+    assert_eq!(reloaded.models()[0].voxels, VoxLoader::from_bytes(TINY_VOX).models()[0].voxels);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn model_diffing() {
+    let mut before = Model::new(Size { x: 4, y: 4, z: 4 });
+    before.set_voxel(0, 0, 0, 1);
+    before.set_voxel(1, 0, 0, 2);
+    before.set_voxel(2, 0, 0, 3);
+    let mut after = before.clone();
+    after.clear_voxel(0, 0, 0);
+    after.set_voxel(1, 0, 0, 5);
+    after.set_voxel(3, 3, 3, 7);
+    after.set_voxel(0, 1, 0, 7);
+
+    let changes = diff(&before, &after);
+    assert_eq!(changes.added, vec![Voxel { x: 0, y: 1, z: 0, c: 7 }, Voxel { x: 3, y: 3, z: 3, c: 7 }]);
+    assert_eq!(changes.removed, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }]);
+    assert_eq!(changes.recolored, vec![(Voxel { x: 1, y: 0, z: 0, c: 2 }, 5)]);
+    assert_eq!(changes.summary(), "2 added, 1 removed, 1 recolored");
+    assert!(diff(&after, &after).is_empty());
+
+    let mut palette = Palette::default();
+    palette.set(5, Color::new(9, 9, 9, 255));
+    let changes = diff(&before, &before).with_palettes(&Palette::default(), &palette);
+    assert_eq!(changes.palette, vec![(5, Palette::default().get(5), Color::new(9, 9, 9, 255))]);
+    assert_eq!(changes.to_string(), "1 palette entries changed");
+}
+
+#[test]
+fn patch_application() {
+    let mut before = Model::new(Size { x: 4, y: 4, z: 4 });
+    before.set_voxel(0, 0, 0, 1);
+    before.set_voxel(1, 0, 0, 2);
+    before.set_voxel(3, 3, 3, 4);
+    let mut after = Model::new(Size { x: 4, y: 4, z: 3 });
+    after.set_voxel(1, 0, 0, 5);
+    after.set_voxel(2, 2, 2, 6);
+    let mut palette = Palette::default();
+    palette.set(6, Color::new(1, 2, 3, 255));
+    let patch = diff(&before, &after).with_palettes(&Palette::default(), &palette);
+
+    let decoded = VoxDiff::from_bytes(&patch.to_bytes()).unwrap();
+    assert_eq!(decoded, patch);
+    assert!(VoxDiff::from_bytes(b"VOX ").is_err());
+
+    let mut model = before.clone();
+    assert_eq!(model.apply(&decoded), 4);
+    assert!(diff(&model, &after).is_empty());
+    assert_eq!(model.voxel_at(2, 2, 2), Some(6));
+    let mut patched = Palette::default();
+    patched.apply(&decoded);
+    assert_eq!(patched, palette);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn model_content_hash() {
+    let mut model = Model::new(Size { x: 3, y: 3, z: 3 });
+    model.set_voxel(0, 1, 2, 4);
+    model.set_voxel(2, 2, 2, 9);
+    let hash = model.content_hash();
+    let mut shuffled = model.clone();
+    shuffled.voxels.reverse();
+    assert_eq!(shuffled.content_hash(), hash);
+
+    let vl = VoxLoader::from_bytes(TINY_VOX);
+    let reloaded = VoxLoader::from_bytes(&VoxWriter::to_bytes(&vl));
+    assert_eq!(reloaded.models()[0].content_hash(), vl.models()[0].content_hash());
+
+    shuffled.set_voxel(2, 2, 2, 8);
+    assert_ne!(shuffled.content_hash(), hash);
+    model.size.z = 4;
+    assert_ne!(model.content_hash(), hash);
+    assert_eq!(Model::new(Size { x: 1, y: 1, z: 1 }).content_hash(), 0xde24_483a_76fb_b974);
+}
+
+#[test]
+fn model_statistics() {
+    let mut model = Model::new(Size { x: 4, y: 4, z: 4 });
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                model.set_voxel(x, y, z, if (x, y, z) == (1, 1, 1) { 2 } else { 1 });
+            }
+        }
+    }
+    model.voxels.push(Voxel { x: 0, y: 0, z: 0, c: 1 });
+    let stats = model.stats();
+    assert_eq!(stats.voxel_count, 27);
+    assert_eq!(stats.occupancy, 27.0 / 64.0);
+    assert_eq!(stats.bounds, Some(Aabb::new([0, 0, 0], [3, 3, 3])));
+    assert_eq!(stats.surface_count, 26);
+    assert_eq!((stats.color_counts[1], stats.color_counts[2]), (26, 1));
+
+    let empty = Model::new(Size { x: 0, y: 0, z: 0 }).stats();
+    assert_eq!((empty.voxel_count, empty.occupancy, empty.bounds), (0, 0.0, None));
+}
+
+#[test]
+fn palette_usage() {
+    let mut first = Model::new(Size { x: 2, y: 2, z: 2 });
+    first.set_voxel(0, 0, 0, 3);
+    first.set_voxel(1, 0, 0, 3);
+    first.set_voxel(0, 1, 0, 7);
+    let mut second = Model::new(Size { x: 2, y: 2, z: 2 });
+    second.set_voxel(1, 1, 1, 255);
+
+    let histogram = first.color_histogram();
+    assert_eq!((histogram[3], histogram[7], histogram.iter().sum::<usize>()), (2, 1, 3));
+    let unused = Palette::default().unused_indices(&[first, second]);
+    assert_eq!(unused.len(), 252);
+    assert!(!unused.contains(&3) && !unused.contains(&7) && !unused.contains(&255));
+    assert_eq!(unused[..3], [1, 2, 4]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn loader_from_models() {
+    let mut model = Model::new(Size { x: 2, y: 2, z: 2 });
+    model.set_voxel(1, 0, 1, 12);
+    let vl = VoxLoader::from_models(vec![model.clone()], Palette::default());
+    assert_eq!(vl.version(), 150);
+    let reloaded = VoxLoader::from_bytes(&VoxWriter::to_bytes(&vl));
+    assert_eq!(reloaded.models(), &[model][..]);
+    assert_eq!(reloaded.palette, Palette::default());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn isometric_thumbnail() {
+    let mut model = Model::new(Size { x: 2, y: 2, z: 2 });
+    for (x, y, z) in (0..8).map(|i| (i & 1, i >> 1 & 1, i >> 2)) {
+        model.set_voxel(x, y, z, 1);
+    }
+    let mut palette = Palette::default();
+    palette.set(1, Color::new(200, 100, 50, 255));
+    let image = render::thumbnail(&model, &palette, 64);
+    assert_eq!((image.width, image.height, image.pixels.len()), (64, 64, 64 * 64 * 4));
+    assert_eq!(image.pixel(0, 0), [0, 0, 0, 0]);
+    let colors: Vec<[u8; 4]> = (0..64 * 64).map(|i| image.pixel(i % 64, i / 64)).collect();
+    for shade in [[200, 100, 50, 255], [160, 80, 40, 255], [120, 60, 30, 255]] {
+        assert!(colors.contains(&shade), "missing {:?}", shade);
+    }
+    // The cube's outline is a hexagon filling most of the image.
+    assert_eq!(image.pixel(32, 32)[3], 255);
+    assert!(colors.iter().filter(|c| c[3] == 255).count() > 64 * 64 / 2);
+
+    assert_eq!(render::thumbnail(&Model::new(Size { x: 0, y: 0, z: 0 }), &palette, 8), RgbaImage::new(8, 8));
+    #[cfg(feature = "image")]
+    assert!(image.to_png().unwrap().starts_with(b"\x89PNG"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn ascii_slice_rendering() {
+    let mut model = Model::new(Size { x: 3, y: 2, z: 2 });
+    model.set_voxel(0, 0, 0, 1);
+    model.set_voxel(2, 1, 0, 12);
+    model.set_voxel(1, 0, 1, 40);
+    model.set_voxel(2, 0, 1, 200);
+    assert_eq!(model.ascii_slices(Axis::Z), "z = 0\n..c\n1..\n\nz = 1\n...\n.E#\n");
+    assert_eq!(model.ascii_slices(Axis::X), "x = 0\n..\n1.\n\nx = 1\nE.\n..\n\nx = 2\n#.\n.c\n");
+    assert_eq!(Model::new(Size { x: 0, y: 0, z: 0 }).ascii_slices(Axis::Y), "");
+}
+
+#[cfg(feature = "glam")]
+#[test]
+fn glam_transforms() {
+    use glam::{Mat3, Mat4, Vec3};
+    let quarter = Rotation::from_matrix([[0, -1, 0], [1, 0, 0], [0, 0, 1]]).unwrap();
+    assert!(quarter.to_mat3().abs_diff_eq(Mat3::from_rotation_z(std::f32::consts::FRAC_PI_2), 1e-6));
+    assert_eq!(Rotation::from_mat3(Mat3::from_rotation_z(std::f32::consts::FRAC_PI_2)), Some(quarter));
+    assert_eq!(Rotation::from_mat3(Mat3::from_rotation_z(0.3)), None);
+
+    let mut node = TransformNode { id: 0, child: 1, layer_id: 0, rotation: quarter, translation: [4, -2, 7], name: None, hidden: false };
+    let m = node.to_mat4();
+    assert_eq!(m.transform_point3(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(4.0, -1.0, 7.0));
+
+    let mirrored = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)) * Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+    assert!(node.set_mat4(mirrored));
+    assert_eq!((node.rotation.to_matrix(), node.translation), ([[-1, 0, 0], [0, 1, 0], [0, 0, 1]], [1, 2, 3]));
+    assert!(!node.set_mat4(Mat4::from_scale(Vec3::splat(2.0))));
+    assert_eq!(node.translation, [1, 2, 3]);
+}
+
+/// A two-model file whose shape switches to model 1 at frame 5 under a
+/// transform keyed at frames 0 and 10.
+#[cfg(all(test, feature = "std"))]
+fn animated_vox() -> Vec<u8> {
+    let int = |value: i32| value.to_le_bytes().to_vec();
+    let dict = |pairs: &[(&str, &str)]| {
+        let mut dict = Dict::new();
+        for &(key, value) in pairs {
+            dict.insert(key, value);
+        }
+        dict.to_bytes()
+    };
+    let transform = |id: i32, child: i32, frames: &[Vec<u8>]| {
+        let mut content = [int(id), dict(&[]), int(child), int(-1), int(0), int(frames.len() as i32)].concat();
+        content.extend(frames.concat());
+        content
+    };
+    let mut writer = ChunkWriter::with_header(200);
+    writer.begin(b"MAIN", &[]);
+    for c in [1u8, 2] {
+        writer.chunk(b"SIZE", &[int(1), int(1), int(1)].concat());
+        writer.chunk(b"XYZI", &[int(1), vec![0, 0, 0, c]].concat());
+    }
+    writer.chunk(b"nTRN", &transform(0, 1, &[dict(&[("_t", "1 1 1")])]));
+    writer.chunk(b"nGRP", &[int(1), dict(&[]), int(1), int(2)].concat());
+    let keys = [dict(&[("_f", "10"), ("_t", "10 0 0"), ("_r", "17")]), dict(&[("_f", "0"), ("_t", "0 0 0")])];
+    writer.chunk(b"nTRN", &transform(2, 3, &keys));
+    let shape = [int(3), dict(&[]), int(2), int(0), dict(&[("_f", "0")]), int(1), dict(&[("_f", "5")])].concat();
+    writer.chunk(b"nSHP", &shape);
+    writer.finish()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn animation_frames() {
+    let vl = VoxLoader::from_bytes(&animated_vox());
+    let animation = &vl.scene().animation;
+    assert_eq!(animation.frame_count(), 11);
+    let mut last = animation.clone();
+    last.shapes.insert(9, vec![ModelKey { frame: u32::MAX, model: 0 }]);
+    assert_eq!(last.frame_count(), u32::MAX);
+    assert_eq!(animation.transforms[&2].iter().map(|key| key.frame).collect::<Vec<_>>(), vec![0, 10]);
+    assert_eq!(animation.shapes[&3], vec![ModelKey { frame: 0, model: 0 }, ModelKey { frame: 5, model: 1 }]);
+
+    let start = vl.scene().frame(0);
+    assert_eq!(start, vec![Instance { model: 0, shape: 3, rotation: Rotation::IDENTITY, translation: [1, 1, 1] }]);
+    let middle = vl.scene().frame(7);
+    assert_eq!((middle[0].model, middle[0].translation), (1, [1, 1, 1]));
+    let end = vl.scene().frame(12);
+    assert_eq!(end, vec![Instance { model: 1, shape: 3, rotation: Rotation(17), translation: [11, 1, 1] }]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn keyframe_sampling() {
+    let vl = VoxLoader::from_bytes(&animated_vox());
+    let animation = &vl.scene().animation;
+    let pose = animation.sample(0.5, 10.0, LoopMode::Once);
+    assert_eq!(pose.transforms[&2], (Rotation::IDENTITY, [5.0, 0.0, 0.0]));
+    assert_eq!(pose.transforms[&0], (Rotation::IDENTITY, [1.0, 1.0, 1.0]));
+    assert_eq!((pose.models[&3], animation.sample(0.45, 10.0, LoopMode::Once).models[&3]), (1, 0));
+
+    assert_eq!(animation.sample(1.2, 10.0, LoopMode::Once).transforms[&2], (Rotation(17), [10.0, 0.0, 0.0]));
+    assert_eq!(animation.sample(1.2, 10.0, LoopMode::Loop).transforms[&2].1, [1.0, 0.0, 0.0]);
+    assert_eq!(animation.sample(1.2, 10.0, LoopMode::PingPong).transforms[&2].1, [8.0, 0.0, 0.0]);
+    assert_eq!(animation.sample(-3.0, 10.0, LoopMode::Loop).transforms[&2].1, [0.0, 0.0, 0.0]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn world_voxel_iteration() {
+    let mut bar = Model::new(Size { x: 3, y: 1, z: 1 });
+    for x in 0..3 {
+        bar.set_voxel(x, 0, 0, x as u8 + 1);
+    }
+    let models = vec![bar];
+    let mut scene = SceneGraph::default();
+    let quarter = Rotation::from_matrix([[0, -1, 0], [1, 0, 0], [0, 0, 1]]).unwrap();
+    scene.insert(SceneNode::Transform(TransformNode { id: 0, child: 1, layer_id: 0, rotation: quarter, translation: [10, 0, 0], name: None, hidden: false }));
+    scene.insert(SceneNode::Shape(ShapeNode { id: 1, models: vec![0, 0] }));
+    let voxels: Vec<_> = scene.iter_world_voxels(&models).collect();
+    assert_eq!(&voxels[..3], &[([9, -1, 0], 1, 0), ([9, 0, 0], 2, 0), ([9, 1, 0], 3, 0)]);
+    assert_eq!(voxels.len(), 6);
+
+    let unplaced: Vec<_> = SceneGraph::default().iter_world_voxels(&models).collect();
+    assert_eq!(unplaced, vec![([0, 0, 0], 1, 0), ([1, 0, 0], 2, 0), ([2, 0, 0], 3, 0)]);
+
+    let vl = VoxLoader::from_bytes(&animated_vox());
+    assert_eq!(vl.scene().iter_world_voxels(vl.models()).collect::<Vec<_>>(), vec![([1, 1, 1], 1, 0)]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn scene_flattening() {
+    let dot = |c: u8| Model::from_voxels(Size { x: 1, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c }]);
+    let models = vec![dot(1), dot(2), dot(3)];
+    let mut scene = SceneGraph::default();
+    let place = |id: u32, x: i32| TransformNode { id, child: id + 1, layer_id: 0, rotation: Rotation::IDENTITY, translation: [x, 0, 0], name: None, hidden: false };
+    scene.insert(SceneNode::Transform(place(0, 0)));
+    scene.insert(SceneNode::Group(GroupNode { id: 1, children: vec![2, 4, 6] }));
+    for (id, x, model) in [(2, 0, 0), (4, 150, 1), (6, 299, 2)] {
+        scene.insert(SceneNode::Transform(place(id, x)));
+        scene.insert(SceneNode::Shape(ShapeNode { id: id + 1, models: vec![model] }));
+    }
+
+    let clipped = scene.flatten(&models, FlattenBounds::Clip);
+    assert_eq!(clipped.size, Size { x: 256, y: 1, z: 1 });
+    assert_eq!(clipped.voxels, vec![Voxel { x: 0, y: 0, z: 0, c: 1 }, Voxel { x: 150, y: 0, z: 0, c: 2 }]);
+    let centered = scene.flatten(&models, FlattenBounds::ClipCentered);
+    assert_eq!(centered.voxels, vec![Voxel { x: 128, y: 0, z: 0, c: 2 }]);
+    let shrunk = scene.flatten(&models, FlattenBounds::Downsample);
+    assert_eq!(shrunk.size, Size { x: 150, y: 1, z: 1 });
+    assert_eq!(shrunk.iter().map(|(x, _, _, c)| (x, c)).collect::<Vec<_>>(), vec![(0, 1), (75, 2), (149, 3)]);
+
+    assert_eq!(SceneGraph::default().flatten(&[], FlattenBounds::Clip).voxels, vec![]);
+}
+
+/// A forest of one tree model placed at x = 0, 10 and 20, plus a rock.
+#[cfg(all(test, feature = "std"))]
+fn forest_vox() -> Vec<u8> {
+    let int = |value: i32| value.to_le_bytes().to_vec();
+    let empty = Dict::new().to_bytes();
+    let transform = |id: i32, child: i32, x: i32| {
+        let mut attributes = Dict::new();
+        attributes.insert("_name", format!("prop_{}", id));
+        let mut frame = Dict::new();
+        frame.insert("_t", format!("{} 0 0", x));
+        [int(id), attributes.to_bytes(), int(child), int(-1), int(0), int(1), frame.to_bytes()].concat()
+    };
+    let mut writer = ChunkWriter::with_header(200);
+    writer.begin(b"MAIN", &[]);
+    for c in [1u8, 2] {
+        writer.chunk(b"SIZE", &[int(1), int(1), int(2)].concat());
+        writer.chunk(b"XYZI", &[int(2), vec![0, 0, 0, c, 0, 0, 1, c]].concat());
+    }
+    writer.chunk(b"nTRN", &transform(0, 1, 0));
+    writer.chunk(b"nGRP", &[int(1), empty.clone(), int(4), int(2), int(4), int(6), int(8)].concat());
+    for (id, x, model) in [(2, 0, 0), (4, 10, 0), (6, 20, 0), (8, 5, 1)] {
+        writer.chunk(b"nTRN", &transform(id, id + 1, x));
+        writer.chunk(b"nSHP", &[int(id + 1), empty.clone(), int(1), int(model), empty.clone()].concat());
+    }
+    writer.finish()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn scene_instances() {
+    let vl = VoxLoader::from_bytes(&forest_vox());
+    let instances = vl.scene().instances();
+    assert_eq!(instances.keys().collect::<Vec<_>>(), vec![&0, &1]);
+    assert_eq!(instances[&0].iter().map(|instance| instance.translation[0]).collect::<Vec<_>>(), vec![0, 10, 20]);
+    assert_eq!(instances[&1][0].shape, 9);
+}
+
+#[cfg(feature = "gltf")]
+#[test]
+fn gltf_shares_instanced_meshes() {
+    let glb = gltf::to_glb(&VoxLoader::from_bytes(&forest_vox()), &gltf::GltfOptions::default());
+    let json_length = u32::from_le_bytes([glb[12], glb[13], glb[14], glb[15]]) as usize;
+    let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+    assert_eq!(json.matches("\"primitives\"").count(), 2);
+    assert_eq!(json.matches("{\"mesh\":0}").count(), 3);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn node_names_and_visibility() {
+    let vl = VoxLoader::from_bytes(&forest_vox());
+    let rock = vl.scene().find_node_by_name("prop_8").unwrap();
+    assert_eq!((rock.id, rock.child, rock.translation), (8, 9, [5, 0, 0]));
+    assert!(vl.scene().find_node_by_name("door_left").is_none());
+
+    let mut scene = vl.scene().clone();
+    if let Some(SceneNode::Transform(node)) = scene.nodes.get_mut(&4) {
+        node.hidden = true;
+    }
+    assert_eq!(scene.visible_models(), vec![0, 0, 1]);
+    assert_eq!(scene.frame(0).iter().map(|instance| instance.translation[0]).collect::<Vec<_>>(), vec![0, 20, 5]);
+}
+
+#[test]
+fn material_pbr_mapping() {
+    let material = |kind: &str, pairs: &[(&str, &str)]| {
+        let mut attributes = Dict::new();
+        attributes.insert("_type", kind);
+        for &(key, value) in pairs {
+            attributes.insert(key, value);
+        }
+        Material::from_attributes(1, &attributes)
+    };
+    let metal = material("_metal", &[("_metal", "0.8"), ("_rough", "0.25")]).to_pbr();
+    assert_eq!((metal.metallic, metal.roughness, metal.alpha, metal.emissive), (0.8, 0.25, 1.0, 0.0));
+
+    let glass = material("_glass", &[("_trans", "0.75"), ("_ior", "0.3")]).to_pbr();
+    assert!(glass.is_transparent());
+    assert_eq!((glass.metallic, glass.alpha, glass.ior), (0.0, 0.25, 1.3));
+
+    let lamp = material("_emit", &[("_emit", "0.5"), ("_flux", "1"), ("_rough", "0.5")]);
+    assert_eq!(lamp.to_pbr().emissive, 1.0);
+    let mapping = PbrMapping { roughness_range: (0.25, 0.5), flux_weight: 0.0, emission_scale: 4.0, max_emission: None, ..PbrMapping::default() };
+    let pbr = lamp.to_pbr_with(&mapping);
+    assert_eq!((pbr.emissive, pbr.roughness, pbr.ior), (2.0, 0.375, 1.0));
+}
+
+#[test]
+fn emissive_voxel_extraction() {
+    let mut attributes = Dict::new();
+    attributes.insert("_type", "_emit");
+    attributes.insert("_emit", "0.5");
+    attributes.insert("_flux", "2");
+    let mut materials = std::collections::BTreeMap::new();
+    materials.insert(3, Material::from_attributes(3, &attributes));
+    materials.insert(4, Material::new(4));
+    let mut model = Model::new(Size { x: 4, y: 1, z: 1 });
+    model.set_voxel(0, 0, 0, 3);
+    model.set_voxel(1, 0, 0, 4);
+    model.set_voxel(2, 0, 0, 3);
+    model.set_voxel(2, 0, 0, 5);
+    assert_eq!(model.emissive_voxels(&materials), vec![(Voxel { x: 0, y: 0, z: 0, c: 3 }, 1.5)]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn transparent_submeshes() {
+    let mut attributes = Dict::new();
+    attributes.insert("_type", "_glass");
+    attributes.insert("_trans", "0.75");
+    let mut materials = std::collections::BTreeMap::new();
+    materials.insert(2, Material::from_attributes(2, &attributes));
+    let palette = Palette::default();
+    let mut model = Model::new(Size { x: 1, y: 1, z: 3 });
+    model.set_voxel(0, 0, 0, 1);
+    model.set_voxel(0, 0, 1, 2);
+    model.set_voxel(0, 0, 2, 2);
+
+    let split = mesher::greedy_split(&model, &palette, &materials, &MeshOptions::default());
+    // The top of the solid voxel shows through the glass; the glass hides
+    // nothing inside itself and nothing under it.
+    assert_eq!(split.opaque.triangle_count(), 12);
+    assert_eq!(split.transparent.triangle_count(), 10);
+    assert!(split.transparent.colors.iter().all(|color| color[3] == 64.0 / 255.0));
+    assert!(split.opaque.colors.iter().all(|color| color[3] == 1.0));
+    assert_eq!(mesher::culled_split(&model, &palette, &materials, &MeshOptions::default()).transparent.triangle_count(), 18);
+    assert_eq!(mesher::greedy(&model, &palette).triangle_count(), 20);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn chunked_world() {
+    let mut world = World::new(Size { x: 4, y: 4, z: 4 });
+    world.insert([0, 0, 0], solid_cube(4, 1));
+    world.insert([1, 0, 0], solid_cube(4, 2));
+    world.set_voxel([-1, 0, 0], 3);
+    assert_eq!(world.locate([-1, 5, 0]), ([-1, 1, 0], [3, 1, 0]));
+    assert_eq!(world.voxel_at([5, 3, 3]), Some(2));
+    assert_eq!(world.voxel_at([-1, 0, 0]), Some(3));
+    assert_eq!(world.voxel_at([-2, 0, 0]), None);
+    assert_eq!(world.chunk([-1, 0, 0]).unwrap().voxels.len(), 1);
+
+    // The wall shared with chunk [1, 0, 0] is not meshed, and the -X side
+    // loses the cell covered by the voxel in chunk [-1, 0, 0].
+    let palette = Palette::default();
+    let mesh = world.mesh_chunk([0, 0, 0], &palette, &MeshOptions::default());
+    assert_eq!(mesh.triangle_count(), 4 * 2 + 2 * 2);
+    assert!(mesh.positions.iter().all(|p| p.iter().all(|&v| (0.0..=4.0).contains(&v))));
+
+    let models = [solid_cube(2, 1)];
+    let mut scene = SceneGraph::default();
+    scene.insert(SceneNode::Transform(TransformNode { id: 0, child: 1, layer_id: 0, rotation: Rotation::IDENTITY, translation: [0; 3], name: None, hidden: false }));
+    scene.insert(SceneNode::Shape(ShapeNode { id: 1, models: vec![0] }));
+    let placed = World::from_scene(&scene, &models, Size { x: 1, y: 1, z: 1 });
+    assert_eq!(placed.chunks().count(), 8);
+    assert_eq!(placed.voxel_at([-1, -1, -1]), Some(1));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn directory_world_streaming() {
+    let naming = NamingScheme::default();
+    assert_eq!(naming.name([-2, 3, 0]), "chunk_-2_3.vox");
+    assert_eq!(naming.parse("chunk_-2_3.vox"), Some([-2, 3, 0]));
+    assert_eq!(naming.parse("chunk_2.vox"), None);
+    assert_eq!(NamingScheme::new("t{z}-{x}").parse("t4-7"), Some([7, 0, 4]));
+
+    let dir = std::env::temp_dir().join("vox_loader_world_tiles");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for x in 0..4 {
+        let tile = VoxLoader::from_models(vec![solid_cube(2, x as u8 + 1)], Palette::default());
+        VoxWriter::write(&tile, dir.join(naming.name([x, 0, 0]))).unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "not a tile").unwrap();
+
+    let mut loader = WorldLoader::open_dir(&dir, naming).unwrap();
+    assert_eq!(loader.available().len(), 4);
+    assert_eq!(loader.world().chunk_size(), Size { x: 2, y: 2, z: 2 });
+    assert!(loader.world().chunks().next().is_none());
+
+    let events = loader.update([0, 0, 0]).unwrap();
+    assert_eq!(events.loaded, vec![[0, 0, 0], [1, 0, 0]]);
+    assert_eq!(loader.world().voxel_at([3, 1, 1]), Some(2));
+
+    // Two chunks on, [0, 0, 0] is still inside the unload radius.
+    let events = loader.update([4, 0, 0]).unwrap();
+    assert_eq!(events, StreamEvents { loaded: vec![[2, 0, 0], [3, 0, 0]], unloaded: vec![] });
+    let events = loader.update([6, 0, 0]).unwrap();
+    assert_eq!(events, StreamEvents { loaded: vec![], unloaded: vec![[0, 0, 0]] });
+    loader.unload_radius = 1;
+    assert_eq!(loader.update([7, 0, 0]).unwrap().unloaded, vec![[1, 0, 0]]);
+    assert!(loader.is_loaded([2, 0, 0]) && loader.palette().is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn mesh_simplification() {
+    let mut palette = Palette::default();
+    palette.set(1, Color::new(100, 100, 100, 255));
+    palette.set(2, Color::new(102, 100, 100, 255));
+    let mut bar = Model::new(Size { x: 4, y: 1, z: 1 });
+    for x in 0..4 {
+        bar.set_voxel(x, 0, 0, if x < 2 { 1 } else { 2 });
+    }
+    let culled = mesher::culled(&bar, &palette);
+    assert_eq!(culled.triangle_count(), 36);
+    let exact = culled.simplify(&SimplifyOptions::default());
+    assert_eq!(exact.triangle_count(), 20);
+    assert_eq!(exact.simplify(&SimplifyOptions::default()), exact);
+
+    let merged = culled.simplify(&SimplifyOptions { color_tolerance: 0.0, target_triangles: Some(12) });
+    assert_eq!(merged.triangle_count(), 12);
+    let top = merged.normals.iter().position(|n| *n == [0.0, 0.0, 1.0]).unwrap();
+    assert_eq!(merged.colors[top][0], 101.0 / 255.0);
+    assert!(merged.positions.contains(&[4.0, 1.0, 1.0]));
+    for triangle in merged.indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| merged.positions[triangle[k] as usize]);
+        let (e1, e2) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+        let cross = [e1[1] * e2[2] - e1[2] * e2[1], e1[2] * e2[0] - e1[0] * e2[2], e1[0] * e2[1] - e1[1] * e2[0]];
+        let normal = merged.normals[triangle[0] as usize];
+        assert!(cross.iter().zip(normal.iter()).map(|(x, n)| x * n).sum::<f32>() > 0.0);
+    }
+
+    let smooth = mesher::marching_cubes(&solid_cube(3, 2), &palette, 0.3);
+    assert_eq!(smooth.simplify(&SimplifyOptions { color_tolerance: 1.0, target_triangles: Some(1) }), smooth);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn vertex_welding() {
+    let palette = Palette::default();
+    let mut mesh = mesher::culled(&solid_cube(1, 1), &palette);
+    assert_eq!(mesh.weld(0.0), 0);
+
+    // Greedy quads carry their own UVs, so none of their corners agree in
+    // every attribute, and a coarse epsilon still keeps the six face
+    // normals of the cube apart.
+    let cube = mesher::greedy(&solid_cube(3, 1), &palette);
+    assert_eq!(cube.clone().weld(0.0), 0);
+    assert_eq!(cube.clone().weld(100.0), 0);
+
+    // A second copy of the mesh, nudged or recolored, on top of the first.
+    let doubled = |change: &dyn Fn(&mut VoxMesh)| {
+        let mut copy = cube.clone();
+        change(&mut copy);
+        let mut mesh = cube.clone();
+        let base = mesh.vertex_count() as u32;
+        mesh.positions.extend(copy.positions);
+        mesh.normals.extend(copy.normals);
+        mesh.uvs.extend(copy.uvs);
+        mesh.colors.extend(copy.colors);
+        mesh.indices.extend(copy.indices.iter().map(|&index| index + base));
+        mesh
+    };
+    let mut nudged = doubled(&|copy| copy.positions.iter_mut().for_each(|p| p[0] += 1e-4));
+    assert_eq!(nudged.clone().weld(0.0), 0);
+    assert_eq!(nudged.weld(1e-3), cube.vertex_count());
+    assert_eq!(nudged.triangle_count(), 2 * cube.triangle_count());
+    for (welded, original) in nudged.indices.iter().zip(cube.indices.iter().chain(&cube.indices)) {
+        assert_eq!(nudged.positions[*welded as usize], cube.positions[*original as usize]);
+    }
+    let recolored = |copy: &mut VoxMesh| copy.colors.iter_mut().for_each(|color| color[0] -= 1.0 / 255.0);
+    assert_eq!(doubled(&recolored).weld(0.01), 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn per_triangle_material_ids() {
+    let palette = Palette::default();
+    let bar = Model::from_voxels(Size { x: 2, y: 1, z: 1 }, vec![Voxel { x: 0, y: 0, z: 0, c: 3 }, Voxel { x: 1, y: 0, z: 0, c: 7 }]);
+    assert!(mesher::greedy(&bar, &palette).material_ids.is_empty());
+
+    let options = MeshOptions { material_ids: true, ..MeshOptions::default() };
+    let mut mesh = mesher::greedy_with(&bar, &palette, &options);
+    assert_eq!(mesh.material_ids.len(), mesh.triangle_count());
+    assert_eq!(mesh.material_ids.iter().filter(|&&c| c == 3).count(), 10);
+    let culled = mesher::culled_with(&bar, &palette, &options);
+    assert_eq!(culled.material_ids.len(), culled.triangle_count());
+
+    // Simplifying never merges faces of different palette indices.
+    let simplified = mesh.simplify(&SimplifyOptions { color_tolerance: 1.0, target_triangles: None });
+    assert_eq!((simplified.triangle_count(), simplified.material_ids.len()), (20, 20));
+    let ids = mesh.material_ids.clone();
+    mesh.weld(0.0);
+    assert_eq!(mesh.material_ids, ids);
+}
+
+#[cfg(feature = "tangents")]
+#[test]
+fn mikktspace_tangents() {
+    let palette = Palette::default();
+    let mut mesh = mesher::greedy(&solid_cube(2, 1), &palette);
+    mesh.generate_tangents();
+    assert_eq!(mesh.tangents.len(), mesh.vertex_count());
+    for (tangent, normal) in mesh.tangents.iter().zip(&mesh.normals) {
+        let along: f32 = (0..3).map(|axis| tangent[axis] * normal[axis]).sum();
+        let length: f32 = (0..3).map(|axis| tangent[axis] * tangent[axis]).sum();
+        assert!(along.abs() < 1e-5 && (length - 1.0).abs() < 1e-5 && tangent[3].abs() == 1.0);
+    }
+    // Top faces run their U coordinate along +X.
+    let top = mesh.normals.iter().position(|n| *n == [0.0, 0.0, 1.0]).unwrap();
+    assert_eq!(mesh.tangents[top], [1.0, 0.0, 0.0, 1.0]);
+
+    let mut welded = mesh.clone();
+    welded.weld(0.0);
+    for (welded_index, index) in welded.indices.iter().zip(&mesh.indices) {
+        assert_eq!(welded.tangents[*welded_index as usize], mesh.tangents[*index as usize]);
+    }
+
+    let mut smooth = mesher::marching_cubes(&solid_cube(3, 2), &palette, 0.3);
+    smooth.generate_tangents();
+    assert_eq!(smooth.tangents.len(), smooth.vertex_count());
+}
+
+#[cfg(feature = "usd")]
+#[test]
+fn usda_scene_export() {
+    let stage = usda::to_usda(&VoxLoader::from_bytes(&forest_vox()));
+    assert!(stage.starts_with("#usda 1.0\n"));
+    assert!(stage.contains("upAxis = \"Z\""));
+    assert_eq!(stage.matches("def Mesh").count(), 2);
+    assert_eq!(stage.matches("prepend references = </Prototypes/model_0>").count(), 3);
+    assert_eq!(stage.matches("def Xform \"prop_").count(), 5);
+    assert!(stage.contains("( (1, 0, 0, 0), (0, 1, 0, 0), (0, 0, 1, 0), (20, 0, 0, 1) )"));
+    assert_eq!(stage.matches('{').count(), stage.matches('}').count());
+    assert!(!stage.contains("visibility"));
+
+    let model = usda::model_to_usda(&solid_cube(1, 1), &Palette::default());
+    assert!(model.contains("defaultPrim = \"model\""));
+    assert!(model.contains("int[] faceVertexCounts = [3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3]"));
 }
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use dict::Dict;
+use model::{Model, Voxel};
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MaterialType {
+    Diffuse,
+    Metal,
+    Glass,
+    Emit,
+    Blend,
+    Media,
+    Cloud,
+    Other(String),
+}
+
+impl MaterialType {
+    pub fn from_name(name: &str) -> MaterialType {
+        match name {
+            "_diffuse" => MaterialType::Diffuse,
+            "_metal" => MaterialType::Metal,
+            "_glass" => MaterialType::Glass,
+            "_emit" => MaterialType::Emit,
+            "_blend" => MaterialType::Blend,
+            "_media" => MaterialType::Media,
+            "_cloud" => MaterialType::Cloud,
+            other => MaterialType::Other(other.to_string()),
+        }
+    }
+    /// The `_type` value, the inverse of `from_name`.
+    pub fn name(&self) -> &str {
+        match *self {
+            MaterialType::Diffuse => "_diffuse",
+            MaterialType::Metal => "_metal",
+            MaterialType::Glass => "_glass",
+            MaterialType::Emit => "_emit",
+            MaterialType::Blend => "_blend",
+            MaterialType::Media => "_media",
+            MaterialType::Cloud => "_cloud",
+            MaterialType::Other(ref name) => name,
+        }
+    }
+}
+
+/// Render properties for one palette index, as stored in a MATL chunk.
+/// Properties missing from the file are left at zero.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Material {
+    pub id: u32,
+    pub material_type: MaterialType,
+    pub weight: f32,
+    pub metalness: f32,
+    pub plastic: f32,
+    pub roughness: f32,
+    pub specular: f32,
+    /// Stored by MagicaVoxel as the refractive index minus one.
+    pub ior: f32,
+    pub attenuation: f32,
+    pub flux: f32,
+    pub emission: f32,
+    pub low_dynamic_range: f32,
+    pub transparency: f32,
+    pub density: f32,
+}
+
+impl Material {
+    pub fn new(id: u32) -> Material {
+        Material {
+            id,
+            material_type: MaterialType::Diffuse,
+            weight: 0.0,
+            metalness: 0.0,
+            plastic: 0.0,
+            roughness: 0.0,
+            specular: 0.0,
+            ior: 0.0,
+            attenuation: 0.0,
+            flux: 0.0,
+            emission: 0.0,
+            low_dynamic_range: 0.0,
+            transparency: 0.0,
+            density: 0.0,
+        }
+    }
+
+    pub fn from_attributes(id: u32, attributes: &Dict) -> Material {
+        let mut material = Material::new(id);
+        for (key, value) in attributes.iter() {
+            if key == "_type" {
+                material.material_type = MaterialType::from_name(value);
+                continue;
+            }
+            let value: f32 = match value.trim().parse() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            match key {
+                "_weight" => material.weight = value,
+                "_metal" => material.metalness = value,
+                "_plastic" => material.plastic = value,
+                "_rough" => material.roughness = value,
+                "_spec" => material.specular = value,
+                "_ior" => material.ior = value,
+                "_att" => material.attenuation = value,
+                "_flux" => material.flux = value,
+                "_emit" => material.emission = value,
+                "_ldr" => material.low_dynamic_range = value,
+                "_trans" | "_alpha" => material.transparency = value,
+                "_d" => material.density = value,
+                _ => {}
+            }
+        }
+        material
+    }
+
+    /// The MATL attributes for the material: `_type` followed by every
+    /// property that is not zero.
+    pub fn to_attributes(&self) -> Dict {
+        let mut attributes = Dict::new();
+        attributes.insert("_type", self.material_type.name());
+        let properties = [
+            ("_weight", self.weight),
+            ("_metal", self.metalness),
+            ("_plastic", self.plastic),
+            ("_rough", self.roughness),
+            ("_spec", self.specular),
+            ("_ior", self.ior),
+            ("_att", self.attenuation),
+            ("_flux", self.flux),
+            ("_emit", self.emission),
+            ("_ldr", self.low_dynamic_range),
+            ("_trans", self.transparency),
+            ("_d", self.density),
+        ];
+        for &(key, value) in properties.iter().filter(|&&(_, value)| value != 0.0) {
+            attributes.insert(key, value.to_string());
+        }
+        attributes
+    }
+
+    /// Builds a material from a legacy MATT chunk (MagicaVoxel 0.98 and
+    /// earlier). The type weight becomes metalness, transparency or emission
+    /// depending on the type, and `properties` holds one value per set bit of
+    /// `property_bits` in the MATT bit order.
+    pub fn from_legacy(id: u32, material_type: u32, weight: f32, property_bits: u32, properties: &[f32]) -> Material {
+        let mut material = Material::new(id);
+        material.weight = weight;
+        match material_type {
+            1 => {
+                material.material_type = MaterialType::Metal;
+                material.metalness = weight;
+            }
+            2 => {
+                material.material_type = MaterialType::Glass;
+                material.transparency = weight;
+            }
+            3 => {
+                material.material_type = MaterialType::Emit;
+                material.emission = weight;
+            }
+            _ => {}
+        }
+        let mut values = properties.iter().cloned();
+        for bit in 0..7 {
+            if property_bits & (1 << bit) == 0 {
+                continue;
+            }
+            let value = values.next().unwrap_or(0.0);
+            match bit {
+                0 => material.plastic = value,
+                1 => material.roughness = value,
+                2 => material.specular = value,
+                3 => material.ior = value,
+                4 => material.attenuation = value,
+                5 => material.flux = value,
+                _ => material.low_dynamic_range = value,
+            }
+        }
+        material
+    }
+}
+
+/// How `Material::to_pbr_with` turns MagicaVoxel's parameters into
+/// metallic-roughness values. Every curve is linear so the mapping stays
+/// predictable across engines; `Default` matches what the glTF exporter
+/// writes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PbrMapping {
+    /// The roughness `_rough` 0 and 1 map to; values between are
+    /// interpolated. MagicaVoxel renders `_rough` 0 as a perfect mirror,
+    /// which some engines want to keep slightly blurred.
+    pub roughness_range: (f32, f32),
+    /// How much `_flux` (0 to 4 in the editor) brightens an emitter:
+    /// the emissive strength is `_emit * (1 + flux_weight * _flux)`.
+    /// Zero ignores flux.
+    pub flux_weight: f32,
+    /// Multiplies the emissive strength, for engines that expect emission
+    /// in physical units rather than 0 to 1.
+    pub emission_scale: f32,
+    /// Caps the emissive strength after scaling. glTF's `emissiveFactor`
+    /// stops at 1 unless `KHR_materials_emissive_strength` is used.
+    pub max_emission: Option<f32>,
+    /// The refractive index of glass that stores none.
+    pub default_ior: f32,
+}
+
+impl Default for PbrMapping {
+    fn default() -> PbrMapping {
+        PbrMapping {
+            roughness_range: (0.0, 1.0),
+            flux_weight: 1.0,
+            emission_scale: 1.0,
+            max_emission: Some(1.0),
+            default_ior: 1.5,
+        }
+    }
+}
+
+/// Metallic-roughness values for one material, from `Material::to_pbr`.
+/// The base color stays with the palette entry; multiply it by `emissive`
+/// for the emissive color.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PbrMaterial {
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Opacity, below 1 for glass and blend materials with transparency.
+    pub alpha: f32,
+    /// Emissive strength, 0 for anything but emitters.
+    pub emissive: f32,
+    /// Index of refraction, 1 for materials that are not transparent.
+    pub ior: f32,
+}
+
+impl PbrMaterial {
+    pub fn is_transparent(&self) -> bool {
+        self.alpha < 1.0
+    }
+}
+
+impl Material {
+    /// The material as metallic-roughness values under the default
+    /// `PbrMapping`.
+    pub fn to_pbr(&self) -> PbrMaterial {
+        self.to_pbr_with(&PbrMapping::default())
+    }
+
+    /// The material as metallic-roughness values. Only metal and blend
+    /// materials are metallic, only glass and blend materials let light
+    /// through, and only emit materials glow; every type keeps its
+    /// roughness. Parameters are clamped to 0 to 1 first.
+    pub fn to_pbr_with(&self, mapping: &PbrMapping) -> PbrMaterial {
+        let unit = |value: f32| value.clamp(0.0, 1.0);
+        let (low, high) = mapping.roughness_range;
+        let mut pbr = PbrMaterial {
+            metallic: 0.0,
+            roughness: low + (high - low) * unit(self.roughness),
+            alpha: 1.0,
+            emissive: 0.0,
+            ior: 1.0,
+        };
+        match self.material_type {
+            MaterialType::Metal => pbr.metallic = unit(self.metalness),
+            MaterialType::Glass => pbr.alpha = 1.0 - unit(self.transparency),
+            MaterialType::Blend => {
+                pbr.metallic = unit(self.metalness);
+                pbr.alpha = 1.0 - unit(self.transparency);
+            }
+            MaterialType::Emit => {
+                let emissive = unit(self.emission) * (1.0 + mapping.flux_weight * self.flux.max(0.0)) * mapping.emission_scale;
+                pbr.emissive = mapping.max_emission.map_or(emissive, |max| emissive.min(max));
+            }
+            _ => {}
+        }
+        if pbr.is_transparent() {
+            pbr.ior = if self.ior > 0.0 { 1.0 + self.ior } else { mapping.default_ior };
+        }
+        pbr
+    }
+}
+
+impl Model {
+    /// The voxels whose palette index has an emit material, with their
+    /// emissive strength under the default `PbrMapping` left uncapped, for
+    /// placing lights. Duplicate voxels at one coordinate count once.
+    pub fn emissive_voxels(&self, materials: &BTreeMap<u32, Material>) -> Vec<(Voxel, f32)> {
+        let mapping = PbrMapping { max_emission: None, ..PbrMapping::default() };
+        let mut strengths = [0.0f32; 256];
+        for (&id, material) in materials.range(1..256) {
+            strengths[id as usize] = material.to_pbr_with(&mapping).emissive;
+        }
+        self.voxels
+            .iter()
+            .filter(|v| strengths[v.c as usize] > 0.0 && self.voxel_at(v.x as u32, v.y as u32, v.z as u32) == Some(v.c))
+            .map(|&v| (v, strengths[v.c as usize]))
+            .collect()
+    }
+}
@@ -0,0 +1,58 @@
+use glam::{IVec3, Mat3, Mat4, Vec3};
+use scene::{Rotation, TransformNode};
+
+impl Rotation {
+    /// The rotation as a glam matrix. Packed rotations may be reflections,
+    /// which come out with a negative determinant.
+    pub fn to_mat3(self) -> Mat3 {
+        let m = self.to_matrix();
+        Mat3::from_cols_array_2d(&[0, 1, 2].map(|col| [0, 1, 2].map(|row| m[row][col] as f32)))
+    }
+
+    /// Packs a matrix whose entries are all within 0.001 of 0, 1 or -1 and
+    /// form a signed permutation, or returns `None`.
+    pub fn from_mat3(m: Mat3) -> Option<Rotation> {
+        let cols = m.to_cols_array_2d();
+        let mut rows = [[0; 3]; 3];
+        for (col, values) in cols.iter().enumerate() {
+            for (row, &value) in values.iter().enumerate() {
+                let rounded = value.round();
+                if (value - rounded).abs() > 0.001 {
+                    return None;
+                }
+                rows[row][col] = rounded as i32;
+            }
+        }
+        Rotation::from_matrix(rows)
+    }
+}
+
+impl TransformNode {
+    pub fn translation_ivec3(&self) -> IVec3 {
+        IVec3::from_array(self.translation)
+    }
+
+    /// The node's rotation followed by its translation, for feeding scene
+    /// transforms to glam-based engines.
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_translation(self.translation_ivec3().as_vec3()) * Mat4::from_mat3(self.rotation.to_mat3())
+    }
+
+    /// Sets rotation and translation from an affine matrix, rounding the
+    /// translation to whole voxels. Returns false, leaving the node as it
+    /// was, if the upper 3x3 is not a signed permutation or the bottom row
+    /// is not `0, 0, 0, 1`.
+    pub fn set_mat4(&mut self, m: Mat4) -> bool {
+        let affine = m.row(3).abs_diff_eq(glam::Vec4::W, 0.001);
+        let rotation = Rotation::from_mat3(Mat3::from_mat4(m));
+        match rotation {
+            Some(rotation) if affine => {
+                let t: Vec3 = m.w_axis.truncate().round();
+                self.rotation = rotation;
+                self.translation = t.as_ivec3().to_array();
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use byteorder::{LittleEndian, WriteBytesExt};
+use model::Model;
+use ops::face_mask;
+use palette::Palette;
+
+/// A cube face: its outward normal and four corners of the unit cube,
+/// counter-clockwise when seen from outside.
+pub(crate) struct Face {
+    pub normal: [i32; 3],
+    pub corners: [[i32; 3]; 4],
+}
+
+/// The six faces, in the bit order of `face_mask`.
+pub(crate) const FACES: [Face; 6] = [
+    Face { normal: [1, 0, 0], corners: [[1, 0, 0], [1, 1, 0], [1, 1, 1], [1, 0, 1]] },
+    Face { normal: [-1, 0, 0], corners: [[0, 0, 0], [0, 0, 1], [0, 1, 1], [0, 1, 0]] },
+    Face { normal: [0, 1, 0], corners: [[0, 1, 0], [0, 1, 1], [1, 1, 1], [1, 1, 0]] },
+    Face { normal: [0, -1, 0], corners: [[0, 0, 0], [1, 0, 0], [1, 0, 1], [0, 0, 1]] },
+    Face { normal: [0, 0, 1], corners: [[0, 0, 1], [1, 0, 1], [1, 1, 1], [0, 1, 1]] },
+    Face { normal: [0, 0, -1], corners: [[0, 0, 0], [0, 1, 0], [1, 1, 0], [1, 0, 0]] },
+];
+
+/// Writes `model` as a Wavefront OBJ at `path` with its materials in a
+/// sibling `.mtl` file. Every voxel face that borders empty space becomes one
+/// quad, grouped by palette index.
+pub fn export_obj<P: AsRef<Path>>(model: &Model, palette: &Palette, path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path.file_name().map_or("model.mtl".into(), |name| name.to_string_lossy());
+    let (obj, mtl) = to_obj(model, palette, &mtl_name);
+    File::create(path)?.write_all(obj.as_bytes())?;
+    File::create(&mtl_path)?.write_all(mtl.as_bytes())
+}
+
+/// Builds the OBJ and MTL file contents, with the OBJ referencing the
+/// material library as `mtl_name`.
+pub fn to_obj(model: &Model, palette: &Palette, mtl_name: &str) -> (String, String) {
+    let grid = model.to_dense_grid();
+    let mut faces: BTreeMap<u8, Vec<(usize, [i32; 3])>> = BTreeMap::new();
+    for voxel in &model.voxels {
+        let p = [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+        let visible = face_mask(&grid, p[0], p[1], p[2]);
+        for direction in 0..FACES.len() {
+            if visible & 1 << direction != 0 {
+                faces.entry(voxel.c).or_default().push((direction, p));
+            }
+        }
+    }
+
+    let mut obj = String::new();
+    let mut mtl = String::new();
+    writeln!(obj, "mtllib {}", mtl_name).unwrap();
+    for face in FACES.iter() {
+        writeln!(obj, "vn {} {} {}", face.normal[0], face.normal[1], face.normal[2]).unwrap();
+    }
+    // Corners lie on the integer lattice and OBJ keeps normals apart, so
+    // every corner position is written once and shared by all its faces.
+    let mut vertices: BTreeMap<[i32; 3], usize> = BTreeMap::new();
+    for (&c, quads) in &faces {
+        let color = palette.get(c);
+        writeln!(mtl, "newmtl color_{}", c).unwrap();
+        writeln!(mtl, "Kd {:.6} {:.6} {:.6}", color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0).unwrap();
+        writeln!(mtl, "d {:.6}", color.a as f32 / 255.0).unwrap();
+
+        writeln!(obj, "usemtl color_{}", c).unwrap();
+        for &(direction, p) in quads {
+            let corners = FACES[direction].corners.map(|corner| {
+                let position = [p[0] + corner[0], p[1] + corner[1], p[2] + corner[2]];
+                let next = vertices.len() + 1;
+                *vertices.entry(position).or_insert_with(|| {
+                    writeln!(obj, "v {} {} {}", position[0], position[1], position[2]).unwrap();
+                    next
+                })
+            });
+            let n = direction + 1;
+            writeln!(
+                obj,
+                "f {}//{} {}//{} {}//{} {}//{}",
+                corners[0], n, corners[1], n, corners[2], n, corners[3], n
+            ).unwrap();
+        }
+    }
+    (obj, mtl)
+}
+
+/// Builds a binary STL of the voxel surface, `scale_mm` millimetres per
+/// voxel. Faces lie on the integer voxel lattice, so adjacent quads share
+/// their edges exactly and the result is closed, ready for slicers.
+pub fn export_stl(model: &Model, scale_mm: f32) -> Vec<u8> {
+    let grid = model.to_dense_grid();
+    let mut triangles: Vec<([i32; 3], [[i32; 3]; 3])> = Vec::new();
+    for voxel in &model.voxels {
+        let p = [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+        let visible = face_mask(&grid, p[0], p[1], p[2]);
+        for (bit, face) in FACES.iter().enumerate() {
+            if visible & 1 << bit == 0 {
+                continue;
+            }
+            let mut corners = face.corners;
+            for corner in corners.iter_mut() {
+                for axis in 0..3 {
+                    corner[axis] += p[axis];
+                }
+            }
+            triangles.push((face.normal, [corners[0], corners[1], corners[2]]));
+            triangles.push((face.normal, [corners[0], corners[2], corners[3]]));
+        }
+    }
+
+    let mut stl = vec![0; 80];
+    stl[..19].copy_from_slice(b"vox_loader STL mesh");
+    stl.write_u32::<LittleEndian>(triangles.len() as u32).unwrap();
+    for (normal, vertices) in &triangles {
+        for &n in normal {
+            stl.write_f32::<LittleEndian>(n as f32).unwrap();
+        }
+        for vertex in vertices {
+            for &v in vertex {
+                stl.write_f32::<LittleEndian>(v as f32 * scale_mm).unwrap();
+            }
+        }
+        stl.write_u16::<LittleEndian>(0).unwrap();
+    }
+    stl
+}
+
+pub fn write_stl<P: AsRef<Path>>(model: &Model, scale_mm: f32, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(&export_stl(model, scale_mm))
+}
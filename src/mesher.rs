@@ -0,0 +1,772 @@
+use std::collections::{BTreeMap, HashMap};
+use grid::VoxGrid;
+use material::Material;
+use mesh::FACES;
+use model::{Model, Size};
+use ops::face_mask;
+use palette::{Color, Palette};
+use world::World;
+
+/// Number of floats per vertex in `VoxMesh::interleaved`: position (3),
+/// normal (3), uv (2) and color (4).
+pub const INTERLEAVED_STRIDE: usize = 12;
+
+/// Where the meshers write baked ambient occlusion.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AmbientOcclusion {
+    #[default]
+    Off,
+    /// Darkens the RGB of `VoxMesh::colors`.
+    VertexColors,
+    /// Fills `VoxMesh::ao` and leaves the colors untouched.
+    Attribute,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MeshOptions {
+    pub ambient_occlusion: AmbientOcclusion,
+    /// How much a fully occluded corner is darkened, from 0 (not at all)
+    /// to 1 (black).
+    pub ao_strength: f32,
+    /// Fill `VoxMesh::material_ids` with the palette index of each
+    /// triangle.
+    pub material_ids: bool,
+}
+
+impl Default for MeshOptions {
+    fn default() -> MeshOptions {
+        MeshOptions {
+            ambient_occlusion: AmbientOcclusion::Off,
+            ao_strength: 0.6,
+            material_ids: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum IndexBuffer {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+/// Triangle mesh output shared by the meshers. Vertices are not shared
+/// between faces so every face keeps its own normal and color. Quads get
+/// UVs in voxel units, so a texture repeats once per voxel across merged
+/// faces.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct VoxMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 4]>,
+    /// Per-vertex light factor in `0.0..=1.0`, only filled when meshing
+    /// with `AmbientOcclusion::Attribute`.
+    pub ao: Vec<f32>,
+    pub indices: Vec<u32>,
+    /// The palette index each triangle was cut from, one per triangle in
+    /// index buffer order, for texture arrays and material batching. Only
+    /// filled when meshing with `MeshOptions::material_ids`.
+    pub material_ids: Vec<u8>,
+    /// Per-vertex MikkTSpace tangents, `[x, y, z, w]` with the bitangent
+    /// sign in `w` as glTF expects. Only filled by `generate_tangents`,
+    /// with the `tangents` feature.
+    pub tangents: Vec<[f32; 4]>,
+}
+
+impl VoxMesh {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// The index buffer as 16-bit indices, or `None` if the mesh has too
+    /// many vertices for them.
+    pub fn indices_u16(&self) -> Option<Vec<u16>> {
+        if self.positions.len() > u16::MAX as usize + 1 {
+            return None;
+        }
+        Some(self.indices.iter().map(|&index| index as u16).collect())
+    }
+
+    /// The narrowest index buffer that can address every vertex.
+    pub fn index_buffer(&self) -> IndexBuffer {
+        match self.indices_u16() {
+            Some(indices) => IndexBuffer::U16(indices),
+            None => IndexBuffer::U32(self.indices.clone()),
+        }
+    }
+
+    /// All vertex attributes packed per vertex, `INTERLEAVED_STRIDE` floats
+    /// each.
+    pub fn interleaved(&self) -> Vec<f32> {
+        let mut data = Vec::with_capacity(self.positions.len() * INTERLEAVED_STRIDE);
+        for i in 0..self.positions.len() {
+            data.extend_from_slice(&self.positions[i]);
+            data.extend_from_slice(&self.normals[i]);
+            data.extend_from_slice(&self.uvs[i]);
+            data.extend_from_slice(&self.colors[i]);
+        }
+        data
+    }
+
+    /// Merges vertices whose positions agree once snapped to multiples of
+    /// `epsilon` (exactly, for 0) and whose other attributes are equal, and
+    /// rewrites the index buffer to match. Normals, UVs, colors, occlusion
+    /// and tangents are compared exactly, so neither hard edges nor
+    /// neighbouring palette colors are merged. Each merged vertex keeps the
+    /// values of its first occurrence. Returns how many vertices were
+    /// removed.
+    pub fn weld(&mut self, epsilon: f32) -> usize {
+        let exact = |value: f32| (value + 0.0).to_bits();
+        let snap = |value: f32| if epsilon > 0.0 { (value / epsilon).round().to_bits() } else { exact(value) };
+        let has_ao = self.ao.len() == self.positions.len();
+        let has_tangents = self.tangents.len() == self.positions.len();
+        let mut seen: HashMap<Vec<u32>, u32> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.positions.len());
+        let mut welded = VoxMesh::default();
+        for i in 0..self.positions.len() {
+            let mut key: Vec<u32> = self.positions[i].iter().map(|&value| snap(value)).collect();
+            key.extend(self.normals[i].iter().chain(&self.uvs[i]).chain(&self.colors[i]).map(|&value| exact(value)));
+            if has_ao {
+                key.push(exact(self.ao[i]));
+            }
+            if has_tangents {
+                key.extend(self.tangents[i].iter().map(|&value| exact(value)));
+            }
+            let next = welded.positions.len() as u32;
+            let index = *seen.entry(key).or_insert(next);
+            if index == next {
+                welded.positions.push(self.positions[i]);
+                welded.normals.push(self.normals[i]);
+                welded.uvs.push(self.uvs[i]);
+                welded.colors.push(self.colors[i]);
+                if has_ao {
+                    welded.ao.push(self.ao[i]);
+                }
+                if has_tangents {
+                    welded.tangents.push(self.tangents[i]);
+                }
+            }
+            remap.push(index);
+        }
+        welded.indices = self.indices.iter().map(|&index| remap[index as usize]).collect();
+        welded.material_ids = std::mem::take(&mut self.material_ids);
+        let removed = self.positions.len() - welded.positions.len();
+        *self = welded;
+        removed
+    }
+
+    /// Appends a quad given counter-clockwise corners as two triangles.
+    /// `extent` is the quad's width and height in voxels, used for its UVs.
+    pub fn push_quad(&mut self, corners: [[f32; 3]; 4], normal: [f32; 3], color: Color, extent: [f32; 2]) {
+        let base = self.positions.len() as u32;
+        let uvs = [[0.0, 0.0], [extent[0], 0.0], [extent[0], extent[1]], [0.0, extent[1]]];
+        for (corner, uv) in corners.iter().zip(uvs.iter()) {
+            self.positions.push(*corner);
+            self.normals.push(normal);
+            self.uvs.push(*uv);
+            self.colors.push(color_to_f32(color));
+        }
+        self.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    pub fn push_triangle(&mut self, corners: [[f32; 3]; 3], normals: [[f32; 3]; 3], color: Color) {
+        let base = self.positions.len() as u32;
+        for (corner, normal) in corners.iter().zip(normals.iter()) {
+            self.positions.push(*corner);
+            self.normals.push(*normal);
+            self.uvs.push([0.0, 0.0]);
+            self.colors.push(color_to_f32(color));
+        }
+        self.indices.extend_from_slice(&[base, base + 1, base + 2]);
+    }
+
+    /// Applies corner occlusion levels (0 darkest, 3 unoccluded) to the
+    /// quad pushed last. The quad is re-split along its other diagonal when
+    /// that keeps the shading symmetric.
+    fn occlude_last_quad(&mut self, levels: [u8; 4], options: &MeshOptions) {
+        if options.ambient_occlusion == AmbientOcclusion::Off {
+            return;
+        }
+        let base = self.positions.len() - 4;
+        for (i, &level) in levels.iter().enumerate() {
+            let factor = 1.0 - options.ao_strength * (3 - level) as f32 / 3.0;
+            match options.ambient_occlusion {
+                AmbientOcclusion::VertexColors => {
+                    for channel in &mut self.colors[base + i][..3] {
+                        *channel *= factor;
+                    }
+                }
+                _ => self.ao.push(factor),
+            }
+        }
+        if levels[0] as u32 + levels[2] as u32 > levels[1] as u32 + levels[3] as u32 {
+            let base = base as u32;
+            let start = self.indices.len() - 6;
+            self.indices[start..].copy_from_slice(&[base + 1, base + 2, base + 3, base + 1, base + 3, base]);
+        }
+    }
+}
+
+/// The two meshes from `culled_split` and `greedy_split`. Draw
+/// `transparent` after `opaque` with alpha blending; its vertex colors carry
+/// the material's opacity in alpha.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SplitMesh {
+    pub opaque: VoxMesh,
+    pub transparent: VoxMesh,
+}
+
+impl SplitMesh {
+    fn mesh_for(&mut self, surfaces: &Surfaces, c: u8) -> &mut VoxMesh {
+        if surfaces.transparent[c as usize] {
+            &mut self.transparent
+        } else {
+            &mut self.opaque
+        }
+    }
+}
+
+/// How each palette index is drawn: its vertex color, and whether light
+/// passes through it.
+pub(crate) struct Surfaces {
+    pub(crate) colors: [Color; 256],
+    pub(crate) transparent: [bool; 256],
+}
+
+impl Surfaces {
+    pub(crate) fn opaque(palette: &Palette) -> Surfaces {
+        Surfaces { colors: std::array::from_fn(|c| palette.get(c as u8)), transparent: [false; 256] }
+    }
+
+    /// Indices whose material is see-through under `Material::to_pbr` are
+    /// transparent, with the material's opacity folded into their alpha.
+    pub(crate) fn with_materials(palette: &Palette, materials: &BTreeMap<u32, Material>) -> Surfaces {
+        let mut surfaces = Surfaces::opaque(palette);
+        for (&id, material) in materials.range(1..256) {
+            let pbr = material.to_pbr();
+            if pbr.is_transparent() {
+                let color = &mut surfaces.colors[id as usize];
+                color.a = (color.a as f32 * pbr.alpha).round() as u8;
+                surfaces.transparent[id as usize] = true;
+            }
+        }
+        surfaces
+    }
+
+    /// A face of voxel `c` at `p` is drawn unless its neighbour hides it: an
+    /// opaque voxel, or a transparent one of the same index.
+    pub(crate) fn is_face_visible(&self, grid: &VoxGrid, p: [i32; 3], c: u8, normal: [i32; 3]) -> bool {
+        match grid.get(p[0] + normal[0], p[1] + normal[1], p[2] + normal[2]) {
+            0 => true,
+            neighbour => self.shows_through(c, neighbour),
+        }
+    }
+
+    /// Whether a face of voxel `c` shows through the solid voxel in front
+    /// of it: only a transparent one of another index lets it, so the
+    /// inside of a glass block is not drawn but what is behind the glass is.
+    pub(crate) fn shows_through(&self, c: u8, neighbour: u8) -> bool {
+        self.transparent[neighbour as usize] && neighbour != c
+    }
+
+    /// The faces of voxel `c` at `p` to draw: those set in its
+    /// `face_mask`, plus the faces that show through a neighbour.
+    pub(crate) fn visible_faces(&self, grid: &VoxGrid, p: [i32; 3], c: u8) -> u8 {
+        let mask = face_mask(grid, p[0], p[1], p[2]);
+        FACES.iter().enumerate().filter(|&(bit, _)| mask & 1 << bit == 0).fold(mask, |mask, (bit, face)| {
+            let n = face.normal;
+            if self.shows_through(c, grid.get(p[0] + n[0], p[1] + n[1], p[2] + n[2])) {
+                mask | 1 << bit
+            } else {
+                mask
+            }
+        })
+    }
+}
+
+fn color_to_f32(color: Color) -> [f32; 4] {
+    [
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0,
+        color.a as f32 / 255.0,
+    ]
+}
+
+/// Culled meshing: one quad per voxel face that borders empty space, with
+/// faces between two solid voxels dropped.
+pub fn culled(model: &Model, palette: &Palette) -> VoxMesh {
+    culled_with(model, palette, &MeshOptions::default())
+}
+
+/// `culled` with baked ambient occlusion and other options.
+pub fn culled_with(model: &Model, palette: &Palette, options: &MeshOptions) -> VoxMesh {
+    culled_surfaces(model, &Surfaces::opaque(palette), options).opaque
+}
+
+/// `culled_with`, putting faces of glass and other see-through materials
+/// in a mesh of their own.
+pub fn culled_split(model: &Model, palette: &Palette, materials: &BTreeMap<u32, Material>, options: &MeshOptions) -> SplitMesh {
+    culled_surfaces(model, &Surfaces::with_materials(palette, materials), options)
+}
+
+fn culled_surfaces(model: &Model, surfaces: &Surfaces, options: &MeshOptions) -> SplitMesh {
+    let grid = model.to_dense_grid();
+    let mut split = SplitMesh::default();
+    for voxel in &model.voxels {
+        let p = [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+        let mesh = split.mesh_for(surfaces, voxel.c);
+        let visible = surfaces.visible_faces(&grid, p, voxel.c);
+        for (bit, face) in FACES.iter().enumerate() {
+            if visible & 1 << bit == 0 {
+                continue;
+            }
+            let mut corners = [[0.0; 3]; 4];
+            for (corner, offset) in corners.iter_mut().zip(face.corners.iter()) {
+                for axis in 0..3 {
+                    corner[axis] = (p[axis] + offset[axis]) as f32;
+                }
+            }
+            let normal = [face.normal[0] as f32, face.normal[1] as f32, face.normal[2] as f32];
+            mesh.push_quad(corners, normal, surfaces.colors[voxel.c as usize], [1.0, 1.0]);
+            if options.material_ids {
+                mesh.material_ids.extend_from_slice(&[voxel.c; 2]);
+            }
+            if options.ambient_occlusion != AmbientOcclusion::Off {
+                mesh.occlude_last_quad(face_occlusion(&grid, p, face.normal, &face.corners), options);
+            }
+        }
+    }
+    split
+}
+
+/// Occlusion level of each corner of the face of voxel `p` facing `normal`,
+/// with corners given as offsets within the unit cube. A corner is 3 when
+/// nothing in front of the face touches it and drops by one per solid
+/// neighbour, down to 0 when both edge neighbours are solid.
+fn face_occlusion(grid: &VoxGrid, p: [i32; 3], normal: [i32; 3], corners: &[[i32; 3]; 4]) -> [u8; 4] {
+    let front = [p[0] + normal[0], p[1] + normal[1], p[2] + normal[2]];
+    let solid = |offset: [i32; 3]| grid.is_solid(front[0] + offset[0], front[1] + offset[1], front[2] + offset[2]);
+    let mut levels = [3; 4];
+    for (level, corner) in levels.iter_mut().zip(corners.iter()) {
+        let mut sides = [[0; 3]; 2];
+        let mut side = 0;
+        for axis in 0..3 {
+            if normal[axis] == 0 {
+                sides[side][axis] = corner[axis] * 2 - 1;
+                side += 1;
+            }
+        }
+        let a = solid(sides[0]);
+        let b = solid(sides[1]);
+        let diagonal = [sides[0][0] + sides[1][0], sides[0][1] + sides[1][1], sides[0][2] + sides[1][2]];
+        *level = if a && b { 0 } else { 3 - a as u8 - b as u8 - solid(diagonal) as u8 };
+    }
+    levels
+}
+
+/// Greedy meshing: visible faces of the same palette index that share a
+/// plane are merged into the largest rectangles found by a row-major sweep.
+pub fn greedy(model: &Model, palette: &Palette) -> VoxMesh {
+    greedy_with(model, palette, &MeshOptions::default())
+}
+
+/// `greedy` with baked ambient occlusion and other options. With occlusion
+/// on, only faces with the same corner shading are merged.
+pub fn greedy_with(model: &Model, palette: &Palette, options: &MeshOptions) -> VoxMesh {
+    greedy_surfaces(model, &Surfaces::opaque(palette), options).opaque
+}
+
+/// `greedy_with`, putting faces of glass and other see-through materials
+/// in a mesh of their own.
+pub fn greedy_split(model: &Model, palette: &Palette, materials: &BTreeMap<u32, Material>, options: &MeshOptions) -> SplitMesh {
+    greedy_surfaces(model, &Surfaces::with_materials(palette, materials), options)
+}
+
+fn greedy_surfaces(model: &Model, surfaces: &Surfaces, options: &MeshOptions) -> SplitMesh {
+    let size = [model.size.x as i32, model.size.y as i32, model.size.z as i32];
+    greedy_region(&model.to_dense_grid(), [0; 3], size, surfaces, options)
+}
+
+/// Greedy meshes the `size` cells of `grid` from `offset`, with vertices
+/// relative to `offset`. Cells around the region still hide faces and
+/// shade corners.
+fn greedy_region(grid: &VoxGrid, offset: [i32; 3], size: [i32; 3], surfaces: &Surfaces, options: &MeshOptions) -> SplitMesh {
+    let occlusion = options.ambient_occlusion != AmbientOcclusion::Off;
+    let mut split = SplitMesh::default();
+    for d in 0..3 {
+        let u = (d + 1) % 3;
+        let v = (d + 2) % 3;
+        let (su, sv) = (size[u] as usize, size[v] as usize);
+        let mut mask = vec![0u32; su * sv];
+        for &direction in &[1i32, -1] {
+            let mut normal = [0; 3];
+            normal[d] = direction;
+            let unit = |a: i32, b: i32| {
+                let mut offset = [0; 3];
+                offset[d] = if direction > 0 { 1 } else { 0 };
+                offset[u] = a;
+                offset[v] = b;
+                offset
+            };
+            let unit_corners = if direction > 0 {
+                [unit(0, 0), unit(1, 0), unit(1, 1), unit(0, 1)]
+            } else {
+                [unit(0, 0), unit(0, 1), unit(1, 1), unit(1, 0)]
+            };
+            for slice in 0..size[d] {
+                for j in 0..sv {
+                    for i in 0..su {
+                        let mut p = offset;
+                        p[d] += slice;
+                        p[u] += i as i32;
+                        p[v] += j as i32;
+                        let c = grid.get(p[0], p[1], p[2]);
+                        mask[i + j * su] = if c == 0 || !surfaces.is_face_visible(grid, p, c, normal) {
+                            0
+                        } else if occlusion {
+                            let levels = face_occlusion(grid, p, normal, &unit_corners);
+                            let packed = levels.iter().enumerate().fold(0, |packed, (k, &level)| packed | (level as u32) << (2 * k));
+                            c as u32 | packed << 8
+                        } else {
+                            c as u32
+                        };
+                    }
+                }
+                let plane = if direction > 0 { slice + 1 } else { slice };
+                merge_mask(&mut mask, su, sv, |i, j, w, h, key| {
+                    let mut origin = [0.0; 3];
+                    origin[d] = plane as f32;
+                    origin[u] = i as f32;
+                    origin[v] = j as f32;
+                    let mut du = [0.0; 3];
+                    du[u] = w as f32;
+                    let mut dv = [0.0; 3];
+                    dv[v] = h as f32;
+                    let corner = |a: f32, b: f32| [
+                        origin[0] + du[0] * a + dv[0] * b,
+                        origin[1] + du[1] * a + dv[1] * b,
+                        origin[2] + du[2] * a + dv[2] * b,
+                    ];
+                    let corners = if direction > 0 {
+                        [corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0)]
+                    } else {
+                        [corner(0.0, 0.0), corner(0.0, 1.0), corner(1.0, 1.0), corner(1.0, 0.0)]
+                    };
+                    let normal = [normal[0] as f32, normal[1] as f32, normal[2] as f32];
+                    let mesh = split.mesh_for(surfaces, key as u8);
+                    mesh.push_quad(corners, normal, surfaces.colors[key as u8 as usize], [w as f32, h as f32]);
+                    if options.material_ids {
+                        mesh.material_ids.extend_from_slice(&[key as u8; 2]);
+                    }
+                    if occlusion {
+                        let levels = [0, 1, 2, 3].map(|k| (key >> (8 + 2 * k) & 3) as u8);
+                        mesh.occlude_last_quad(levels, options);
+                    }
+                });
+            }
+        }
+    }
+    split
+}
+
+/// Greedily covers the non-zero cells of a `width` x `height` mask with
+/// rectangles of equal value, calling `emit(i, j, w, h, value)` for each and
+/// clearing the mask as it goes.
+fn merge_mask<F: FnMut(usize, usize, usize, usize, u32)>(mask: &mut [u32], width: usize, height: usize, mut emit: F) {
+    for j in 0..height {
+        let mut i = 0;
+        while i < width {
+            let c = mask[i + j * width];
+            if c == 0 {
+                i += 1;
+                continue;
+            }
+            let mut w = 1;
+            while i + w < width && mask[i + w + j * width] == c {
+                w += 1;
+            }
+            let mut h = 1;
+            'grow: while j + h < height {
+                for k in 0..w {
+                    if mask[i + k + (j + h) * width] != c {
+                        break 'grow;
+                    }
+                }
+                h += 1;
+            }
+            for y in 0..h {
+                for x in 0..w {
+                    mask[i + x + (j + y) * width] = 0;
+                }
+            }
+            emit(i, j, w, h, c);
+            i += w;
+        }
+    }
+}
+
+const CUBE_EDGES: [(usize, usize); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7),
+];
+
+/// The position in `CUBE_EDGES` of the edge between two corners, either
+/// way round.
+const CUBE_EDGE_INDEX: [[usize; 8]; 8] = cube_edge_index();
+
+const fn cube_edge_index() -> [[usize; 8]; 8] {
+    let mut table = [[usize::MAX; 8]; 8];
+    let mut edge = 0;
+    while edge < CUBE_EDGES.len() {
+        let (a, b) = CUBE_EDGES[edge];
+        table[a][b] = edge;
+        table[b][a] = edge;
+        edge += 1;
+    }
+    table
+}
+
+/// Cube faces as corner indices in cyclic order. Corner `i` sits at
+/// `(i & 1, i >> 1 & 1, i >> 2 & 1)`.
+const CUBE_FACES: [[usize; 4]; 6] = [
+    [0, 1, 3, 2], [4, 5, 7, 6],
+    [0, 1, 5, 4], [2, 3, 7, 6],
+    [0, 2, 6, 4], [1, 3, 7, 5],
+];
+
+/// Marching cubes over a smoothed occupancy field. Occupancy is box-blurred
+/// over each voxel's 3x3x3 neighborhood and the surface is extracted where
+/// the density crosses `iso`, so 0.5 follows the blocky outline loosely and
+/// lower or higher values grow or shrink the surface.
+///
+/// The triangulation is derived per cube from the face crossings rather than
+/// a fixed table. Ambiguous faces always separate their solid corners, so
+/// neighbouring cubes agree and closed models give closed surfaces.
+pub fn marching_cubes(model: &Model, palette: &Palette, iso: f32) -> VoxMesh {
+    let grid = model.to_dense_grid();
+    let size = [grid.size.x as i32, grid.size.y as i32, grid.size.z as i32];
+    // The density of every lattice point the cubes and their gradients
+    // reach, from 3 before the grid to 2 past its end on each axis.
+    let dims = [size[0] + 6, size[1] + 6, size[2] + 6];
+    let mut densities = Vec::with_capacity((dims[0] * dims[1] * dims[2]) as usize);
+    for z in -3..size[2] + 3 {
+        for y in -3..size[1] + 3 {
+            for x in -3..size[0] + 3 {
+                densities.push(box_density(&grid, x, y, z));
+            }
+        }
+    }
+    let density = |x: i32, y: i32, z: i32| -> f32 {
+        let (i, j, k) = (x + 3, y + 3, z + 3);
+        if i < 0 || j < 0 || k < 0 || i >= dims[0] || j >= dims[1] || k >= dims[2] {
+            return 0.0;
+        }
+        densities[(i + j * dims[0] + k * dims[0] * dims[1]) as usize]
+    };
+    let gradient = |p: [i32; 3]| -> [f32; 3] {
+        [
+            density(p[0] + 1, p[1], p[2]) - density(p[0] - 1, p[1], p[2]),
+            density(p[0], p[1] + 1, p[2]) - density(p[0], p[1] - 1, p[2]),
+            density(p[0], p[1], p[2] + 1) - density(p[0], p[1], p[2] - 1),
+        ]
+    };
+
+    let mut mesh = VoxMesh::default();
+    let mut points = Vec::new();
+    let mut normals = Vec::new();
+    for z in -2..size[2] + 1 {
+        for y in -2..size[1] + 1 {
+            for x in -2..size[0] + 1 {
+                let corners: [[i32; 3]; 8] = std::array::from_fn(|i| {
+                    let i = i as i32;
+                    [x + (i & 1), y + (i >> 1 & 1), z + (i >> 2 & 1)]
+                });
+                let values = corners.map(|p| density(p[0], p[1], p[2]));
+                let inside = values.map(|v| v > iso);
+                if inside.iter().all(|&v| v) || inside.iter().all(|&v| !v) {
+                    continue;
+                }
+                let color = corners
+                    .iter()
+                    .map(|p| grid.get(p[0], p[1], p[2]))
+                    .find(|&c| c != 0)
+                    .unwrap_or_else(|| nearest_color(&grid, [x, y, z]));
+                for polygon in cube_polygons(&inside) {
+                    points.clear();
+                    normals.clear();
+                    let mut outward = [0.0; 3];
+                    for &edge in &polygon {
+                        let (a, b) = CUBE_EDGES[edge];
+                        let t = ((iso - values[a]) / (values[b] - values[a])).clamp(0.0, 1.0);
+                        let (pa, pb) = (corners[a], corners[b]);
+                        let (ga, gb) = (gradient(pa), gradient(pb));
+                        let mut point = [0.0; 3];
+                        let mut normal = [0.0; 3];
+                        for axis in 0..3 {
+                            point[axis] = pa[axis] as f32 + 0.5 + t * (pb[axis] - pa[axis]) as f32;
+                            normal[axis] = -(ga[axis] + t * (gb[axis] - ga[axis]));
+                            let toward_empty = if inside[a] { pb[axis] - pa[axis] } else { pa[axis] - pb[axis] };
+                            outward[axis] += toward_empty as f32;
+                        }
+                        points.push(point);
+                        normals.push(normalize(normal, outward));
+                    }
+                    let polygon_normal = newell_normal(&points);
+                    if dot(polygon_normal, outward) < 0.0 {
+                        points.reverse();
+                        normals.reverse();
+                    }
+                    for i in 1..points.len() - 1 {
+                        mesh.push_triangle(
+                            [points[0], points[i], points[i + 1]],
+                            [normals[0], normals[i], normals[i + 1]],
+                            palette.get(color),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    mesh
+}
+
+/// The share of solid cells in the 3x3x3 block around `(x, y, z)`.
+fn box_density(grid: &VoxGrid, x: i32, y: i32, z: i32) -> f32 {
+    let mut solid = 0;
+    for dz in -1..2 {
+        for dy in -1..2 {
+            for dx in -1..2 {
+                if grid.is_solid(x + dx, y + dy, z + dz) {
+                    solid += 1;
+                }
+            }
+        }
+    }
+    solid as f32 / 27.0
+}
+
+/// Groups the crossing edges of one cube into closed polygons by linking
+/// the crossing segments found on each face.
+fn cube_polygons(inside: &[bool]) -> Vec<Vec<usize>> {
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    for face in CUBE_FACES.iter() {
+        let edges: Vec<usize> = (0..4).map(|k| CUBE_EDGE_INDEX[face[k]][face[(k + 1) % 4]]).collect();
+        let crossing: Vec<usize> = (0..4).filter(|&k| inside[face[k]] != inside[face[(k + 1) % 4]]).collect();
+        match crossing.len() {
+            2 => segments.push((edges[crossing[0]], edges[crossing[1]])),
+            4 => {
+                for k in 0..4 {
+                    if inside[face[k]] {
+                        segments.push((edges[(k + 3) % 4], edges[k]));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut polygons = Vec::new();
+    while let Some((start, mut next)) = segments.pop() {
+        let mut polygon = vec![start];
+        while next != start {
+            polygon.push(next);
+            let position = match segments.iter().position(|&(a, b)| a == next || b == next) {
+                Some(position) => position,
+                None => break,
+            };
+            let (a, b) = segments.swap_remove(position);
+            next = if a == next { b } else { a };
+        }
+        if polygon.len() >= 3 {
+            polygons.push(polygon);
+        }
+    }
+    polygons
+}
+
+fn nearest_color(grid: &VoxGrid, p: [i32; 3]) -> u8 {
+    for radius in 1..3 {
+        for dz in -radius..radius + 1 {
+            for dy in -radius..radius + 1 {
+                for dx in -radius..radius + 1 {
+                    let c = grid.get(p[0] + dx, p[1] + dy, p[2] + dz);
+                    if c != 0 {
+                        return c;
+                    }
+                }
+            }
+        }
+    }
+    0
+}
+
+fn newell_normal(points: &[[f32; 3]]) -> [f32; 3] {
+    let mut normal = [0.0; 3];
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    normal
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Normalizes `v`, falling back to `fallback` when `v` has no length.
+fn normalize(v: [f32; 3], fallback: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    if length > 1e-6 {
+        return [v[0] / length, v[1] / length, v[2] / length];
+    }
+    let length = dot(fallback, fallback).sqrt().max(1e-6);
+    [fallback[0] / length, fallback[1] / length, fallback[2] / length]
+}
+
+impl World {
+    /// Greedy meshes chunk `coords`, with vertices relative to
+    /// `World::chunk_origin`. Faces against voxels of the neighbouring
+    /// chunks are dropped, so the chunk meshes join without inner walls.
+    /// A missing chunk gives an empty mesh.
+    pub fn mesh_chunk(&self, coords: [i32; 3], palette: &Palette, options: &MeshOptions) -> VoxMesh {
+        let model = match self.chunk(coords) {
+            Some(model) => model,
+            None => return VoxMesh::default(),
+        };
+        let chunk = self.chunk_size();
+        let size = [chunk.x as i32, chunk.y as i32, chunk.z as i32];
+        // The chunk with a border of one cell taken from its neighbours.
+        let mut grid = VoxGrid::new(Size { x: chunk.x + 2, y: chunk.y + 2, z: chunk.z + 2 });
+        for voxel in &model.voxels {
+            let p = [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+            if (0..3).all(|axis| p[axis] < size[axis]) {
+                grid.set(p[0] + 1, p[1] + 1, p[2] + 1, voxel.c);
+            }
+        }
+        let origin = self.chunk_origin(coords);
+        for d in 0..3 {
+            let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+            for &plane in &[-1, size[d]] {
+                for a in -1..=size[u] {
+                    for b in -1..=size[v] {
+                        let mut p = [0; 3];
+                        p[d] = plane;
+                        p[u] = a;
+                        p[v] = b;
+                        let c = self.voxel_at([origin[0] + p[0], origin[1] + p[1], origin[2] + p[2]]).unwrap_or(0);
+                        grid.set(p[0] + 1, p[1] + 1, p[2] + 1, c);
+                    }
+                }
+            }
+        }
+        greedy_region(&grid, [1; 3], size, &Surfaces::opaque(palette), options).opaque
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(not(feature = "std"))]
+use std::cell::OnceCell as OnceLock;
+use std::collections::BTreeMap;
+use std::slice;
+use std::vec::Vec;
+use grid::VoxGrid;
+use palette::{Color, Palette};
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Voxel {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub c: u8
+}
+impl fmt::Debug for Voxel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Voxel {{ x: {}, y: {} z: {}, c: {} }}", self.x, self.y, self.z, self.c)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Size {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32
+}
+impl fmt::Debug for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Size {{ x: {}, y: {} z: {}}}", self.x, self.y, self.z)
+    }
+}
+impl Size {
+    /// The number of cells in a volume of this size, or `None` if it does
+    /// not fit in a `usize`.
+    pub fn cell_count(&self) -> Option<usize> {
+        (self.x as usize).checked_mul(self.y as usize)?.checked_mul(self.z as usize)
+    }
+}
+
+/// A single SIZE/XYZI pair. Each model keeps its own dimensions, so
+/// multi-model files no longer share one size.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Model {
+    pub size: Size,
+    pub voxels: Vec<Voxel>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    index: OnceLock<VoxGrid>,
+}
+impl PartialEq for Model {
+    fn eq(&self, other: &Model) -> bool {
+        self.size == other.size && self.voxels == other.voxels
+    }
+}
+impl fmt::Debug for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Model {{ size: {:?}, voxels: {:?} }}", self.size, self.voxels)
+    }
+}
+
+impl Model {
+    pub fn new(size: Size) -> Model {
+        Model::from_voxels(size, Vec::new())
+    }
+
+    pub fn from_voxels(size: Size, voxels: Vec<Voxel>) -> Model {
+        Model {
+            size,
+            voxels,
+            index: OnceLock::new(),
+        }
+    }
+
+    /// The color index at a coordinate, or `None` if the cell is empty or
+    /// outside the model. The first call builds a lookup grid that later
+    /// calls reuse.
+    pub fn voxel_at(&self, x: u32, y: u32, z: u32) -> Option<u8> {
+        if x >= self.size.x || y >= self.size.y || z >= self.size.z {
+            return None;
+        }
+        match self.index.get_or_init(|| self.to_dense_grid()).get(x as i32, y as i32, z as i32) {
+            0 => None,
+            c => Some(c),
+        }
+    }
+
+    /// Iterates `(x, y, z, palette_index)` for every voxel.
+    pub fn iter(&self) -> Voxels<'_> {
+        Voxels { inner: self.voxels.iter() }
+    }
+
+    /// Iterates `(x, y, z, color)`, resolving each index through `palette`.
+    pub fn iter_colored<'a>(&'a self, palette: &'a Palette) -> ColoredVoxels<'a> {
+        ColoredVoxels { inner: self.voxels.iter(), palette }
+    }
+
+    /// Sets the color index at a coordinate, replacing any voxel already
+    /// there; index 0 clears the cell. Returns false if the coordinate lies
+    /// outside the model or above 255, the most a voxel can hold.
+    pub fn set_voxel(&mut self, x: u32, y: u32, z: u32, c: u8) -> bool {
+        if x >= self.size.x.min(256) || y >= self.size.y.min(256) || z >= self.size.z.min(256) {
+            return false;
+        }
+        if c == 0 {
+            self.clear_voxel(x, y, z);
+            return true;
+        }
+        let (x, y, z) = (x as u8, y as u8, z as u8);
+        // Later duplicates win in the lookup grid, so update the last one.
+        match self.voxels.iter_mut().rev().find(|v| (v.x, v.y, v.z) == (x, y, z)) {
+            Some(voxel) => voxel.c = c,
+            None => self.voxels.push(Voxel { x, y, z, c }),
+        }
+        if let Some(grid) = self.index.get_mut() {
+            grid.set(x as i32, y as i32, z as i32, c);
+        }
+        true
+    }
+
+    /// Removes the voxel at a coordinate, returning the color index it had.
+    pub fn clear_voxel(&mut self, x: u32, y: u32, z: u32) -> Option<u8> {
+        let previous = self.voxel_at(x, y, z)?;
+        let (x, y, z) = (x as u8, y as u8, z as u8);
+        self.voxels.retain(|v| (v.x, v.y, v.z) != (x, y, z));
+        if let Some(grid) = self.index.get_mut() {
+            grid.set(x as i32, y as i32, z as i32, 0);
+        }
+        Some(previous)
+    }
+
+    /// Recolors every voxel of index `from` to `to`, removing them when `to`
+    /// is 0. Returns how many voxels changed.
+    pub fn replace_color(&mut self, from: u8, to: u8) -> usize {
+        if from == 0 || from == to {
+            return 0;
+        }
+        let mut changed = Vec::new();
+        for voxel in self.voxels.iter_mut().filter(|v| v.c == from) {
+            voxel.c = to;
+            changed.push((voxel.x, voxel.y, voxel.z));
+        }
+        if to == 0 {
+            self.voxels.retain(|v| v.c != 0);
+        }
+        if let Some(grid) = self.index.get_mut() {
+            for &(x, y, z) in &changed {
+                if grid.get(x as i32, y as i32, z as i32) == from {
+                    grid.set(x as i32, y as i32, z as i32, to);
+                }
+            }
+        }
+        changed.len()
+    }
+
+    /// Rewrites every color index `c` to `mapping[c]`, removing voxels that
+    /// map to 0. Pair it with `Palette::remap` on the same mapping, or with
+    /// a target palette and `Palette::mapping_to`. Returns how many voxels
+    /// changed.
+    pub fn remap_colors(&mut self, mapping: &[u8; 256]) -> usize {
+        let mut changed = 0;
+        for voxel in &mut self.voxels {
+            if mapping[voxel.c as usize] != voxel.c {
+                voxel.c = mapping[voxel.c as usize];
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.voxels.retain(|v| v.c != 0);
+            self.invalidate_index();
+        }
+        changed
+    }
+
+    /// A 64-bit FNV-1a hash of the size and the occupied cells, for cache
+    /// keys and deduplication. It does not depend on the order of `voxels`,
+    /// or on anything else in the file the model came from, and is the same
+    /// on every platform and release.
+    pub fn content_hash(&self) -> u64 {
+        let cells: BTreeMap<(u8, u8, u8), u8> = self.voxels.iter().map(|v| ((v.x, v.y, v.z), v.c)).collect();
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut feed = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for side in [self.size.x, self.size.y, self.size.z] {
+            feed(&side.to_le_bytes());
+        }
+        for (&(x, y, z), &c) in cells.iter().filter(|&(_, &c)| c != 0) {
+            feed(&[x, y, z, c]);
+        }
+        hash
+    }
+
+    /// Drops the lookup grid behind `voxel_at`. Call this after changing
+    /// `voxels` or `size` directly.
+    pub fn invalidate_index(&mut self) {
+        self.index = OnceLock::new();
+    }
+}
+
+pub struct Voxels<'a> {
+    inner: slice::Iter<'a, Voxel>,
+}
+
+impl<'a> Iterator for Voxels<'a> {
+    type Item = (u8, u8, u8, u8);
+
+    fn next(&mut self) -> Option<(u8, u8, u8, u8)> {
+        self.inner.next().map(|v| (v.x, v.y, v.z, v.c))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Voxels<'a> {}
+
+pub struct ColoredVoxels<'a> {
+    inner: slice::Iter<'a, Voxel>,
+    palette: &'a Palette,
+}
+
+impl<'a> Iterator for ColoredVoxels<'a> {
+    type Item = (u8, u8, u8, Color);
+
+    fn next(&mut self) -> Option<(u8, u8, u8, Color)> {
+        let palette = self.palette;
+        self.inner.next().map(|v| (v.x, v.y, v.z, palette.get(v.c)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for ColoredVoxels<'a> {}
+
+impl<'a> IntoIterator for &'a Model {
+    type Item = (u8, u8, u8, u8);
+    type IntoIter = Voxels<'a>;
+
+    fn into_iter(self) -> Voxels<'a> {
+        self.iter()
+    }
+}
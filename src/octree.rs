@@ -0,0 +1,177 @@
+use std::boxed::Box;
+use model::{Model, Size};
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum OctreeNode {
+    #[default]
+    Empty,
+    /// A cube filled entirely with one color index.
+    Solid(u8),
+    /// Eight children, indexed by `x | y << 1 | z << 2` of the child half.
+    Branch(Box<[OctreeNode; 8]>),
+}
+
+/// A cube of space covered by a single octree node.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OctreeCell {
+    pub origin: [u32; 3],
+    pub side: u32,
+    /// The color index filling the cell, 0 for empty space.
+    pub color: u8,
+}
+
+/// A sparse voxel octree. Uniform regions, empty or solid, collapse into a
+/// single node so large hollow models stay small and empty space can be
+/// skipped a whole node at a time.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Octree {
+    pub size: Size,
+    side: u32,
+    root: OctreeNode,
+}
+
+impl Octree {
+    pub fn from_model(model: &Model) -> Octree {
+        // Like the dense grid, the extent is capped at the 256 cells a voxel
+        // coordinate can reach, and voxels outside the size are dropped.
+        let extent = model.size.x.max(model.size.y).max(model.size.z).clamp(1, 256);
+        let mut tree = Octree {
+            size: model.size,
+            side: extent.next_power_of_two(),
+            root: OctreeNode::Empty,
+        };
+        let size = model.size;
+        for voxel in &model.voxels {
+            let inside = (voxel.x as u32) < size.x && (voxel.y as u32) < size.y && (voxel.z as u32) < size.z;
+            if voxel.c != 0 && inside {
+                let side = tree.side;
+                insert(&mut tree.root, side, [voxel.x as u32, voxel.y as u32, voxel.z as u32], voxel.c);
+            }
+        }
+        collapse(&mut tree.root);
+        tree
+    }
+
+    /// Edge length of the root cube, the model extent rounded up to a power
+    /// of two.
+    pub fn side(&self) -> u32 {
+        self.side
+    }
+
+    pub fn root(&self) -> &OctreeNode {
+        &self.root
+    }
+
+    pub fn get(&self, x: u32, y: u32, z: u32) -> u8 {
+        self.query(x, y, z).map_or(0, |cell| cell.color)
+    }
+
+    /// The leaf cell containing a point, or `None` outside the root cube.
+    /// For raymarching, an empty cell's side is the distance that can be
+    /// skipped safely.
+    pub fn query(&self, x: u32, y: u32, z: u32) -> Option<OctreeCell> {
+        if x >= self.side || y >= self.side || z >= self.side {
+            return None;
+        }
+        let mut node = &self.root;
+        let mut origin = [0, 0, 0];
+        let mut side = self.side;
+        loop {
+            match *node {
+                OctreeNode::Empty => return Some(OctreeCell { origin, side, color: 0 }),
+                OctreeNode::Solid(color) => return Some(OctreeCell { origin, side, color }),
+                OctreeNode::Branch(ref children) => {
+                    side /= 2;
+                    let mut child = 0;
+                    for (axis, &p) in [x, y, z].iter().enumerate() {
+                        if p >= origin[axis] + side {
+                            origin[axis] += side;
+                            child |= 1 << axis;
+                        }
+                    }
+                    node = &children[child];
+                }
+            }
+        }
+    }
+
+    /// Visits every non-empty leaf cell in depth-first order.
+    pub fn traverse<F: FnMut(OctreeCell)>(&self, mut visit: F) {
+        traverse(&self.root, [0, 0, 0], self.side, &mut visit);
+    }
+
+    pub fn node_count(&self) -> usize {
+        count(&self.root)
+    }
+}
+
+impl Model {
+    pub fn to_octree(&self) -> Octree {
+        Octree::from_model(self)
+    }
+}
+
+fn insert(node: &mut OctreeNode, side: u32, position: [u32; 3], color: u8) {
+    if side == 1 {
+        *node = OctreeNode::Solid(color);
+        return;
+    }
+    if let OctreeNode::Empty = *node {
+        *node = OctreeNode::Branch(Box::default());
+    }
+    if let OctreeNode::Branch(ref mut children) = *node {
+        let half = side / 2;
+        let child = (position[0] >= half) as usize | ((position[1] >= half) as usize) << 1 | ((position[2] >= half) as usize) << 2;
+        let local = [position[0] % half, position[1] % half, position[2] % half];
+        insert(&mut children[child], half, local, color);
+    }
+}
+
+fn collapse(node: &mut OctreeNode) {
+    let merged = match *node {
+        OctreeNode::Branch(ref mut children) => {
+            for child in children.iter_mut() {
+                collapse(child);
+            }
+            match children[0] {
+                OctreeNode::Branch(_) => None,
+                ref first => {
+                    if children.iter().all(|child| child == first) {
+                        Some(first.clone())
+                    } else {
+                        None
+                    }
+                }
+            }
+        }
+        _ => None,
+    };
+    if let Some(merged) = merged {
+        *node = merged;
+    }
+}
+
+fn traverse<F: FnMut(OctreeCell)>(node: &OctreeNode, origin: [u32; 3], side: u32, visit: &mut F) {
+    match *node {
+        OctreeNode::Empty => {}
+        OctreeNode::Solid(color) => visit(OctreeCell { origin, side, color }),
+        OctreeNode::Branch(ref children) => {
+            let half = side / 2;
+            for (index, child) in children.iter().enumerate() {
+                let child_origin = [
+                    origin[0] + (index as u32 & 1) * half,
+                    origin[1] + (index as u32 >> 1 & 1) * half,
+                    origin[2] + (index as u32 >> 2 & 1) * half,
+                ];
+                traverse(child, child_origin, half, visit);
+            }
+        }
+    }
+}
+
+fn count(node: &OctreeNode) -> usize {
+    match *node {
+        OctreeNode::Branch(ref children) => 1 + children.iter().map(count).sum::<usize>(),
+        _ => 1,
+    }
+}
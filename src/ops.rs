@@ -0,0 +1,314 @@
+use std::collections::VecDeque;
+use std::vec::Vec;
+use grid::VoxGrid;
+use model::{Model, Size, Voxel};
+use palette::{Palette, PaletteBuilder};
+
+const NEIGHBORS: [(i32, i32, i32); 6] = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+
+/// Which color wins where both models of a boolean operation have a voxel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Conflict {
+    KeepOurs,
+    KeepTheirs,
+}
+
+/// How `Model::merge` reconciles the palettes of the two models.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteMerge {
+    /// Both models already use the same palette; indices are kept as is.
+    Shared,
+    /// Colors of the other model map to the closest entry of ours.
+    Nearest,
+    /// Colors of the other model missing from ours take palette entries
+    /// our voxels do not use, falling back to the closest entry once none
+    /// are left.
+    Append,
+}
+
+impl Model {
+    /// Composes `other`, placed at `offset`, into this model. The result
+    /// grows to cover both, up to 256 voxels per axis, with its origin at
+    /// the lowest corner of either; where both have a voxel, `other` wins.
+    /// Returns the merged model and palette.
+    pub fn merge(&self, palette: &Palette, other: &Model, other_palette: &Palette, offset: [i32; 3], strategy: PaletteMerge) -> (Model, Palette) {
+        let mut merged_palette = palette.clone();
+        let mut remap: Vec<u8> = (0..=255).collect();
+        if strategy != PaletteMerge::Shared {
+            let mut used = [false; 256];
+            for v in &self.voxels {
+                used[v.c as usize] = true;
+            }
+            let free: Vec<u8> = (1..=255u8).filter(|&index| !used[index as usize]).collect();
+            let mut free = free.into_iter();
+            let mut nearest = PaletteBuilder::from_palette(palette);
+            let mut fallback: Option<PaletteBuilder> = None;
+            let mut wanted = [false; 256];
+            for v in &other.voxels {
+                wanted[v.c as usize] = true;
+            }
+            for index in (1..=255u8).filter(|&index| wanted[index as usize]) {
+                let color = other_palette.get(index);
+                let exact = (1..=255u8).find(|&ours| used[ours as usize] && merged_palette.get(ours) == color);
+                remap[index as usize] = match (strategy, exact) {
+                    (_, Some(ours)) => ours,
+                    (PaletteMerge::Append, None) => match free.next() {
+                        Some(slot) => {
+                            merged_palette.set(slot, color);
+                            used[slot as usize] = true;
+                            slot
+                        }
+                        // Appended colors now fill the free slots, so only
+                        // the slots in use are candidates.
+                        None => fallback.get_or_insert_with(|| PaletteBuilder::from_used(&merged_palette, &used)).index_of(color),
+                    },
+                    _ => nearest.index_of(color),
+                };
+            }
+        }
+
+        let sides = [self.size.x as i32, self.size.y as i32, self.size.z as i32];
+        let other_sides = [other.size.x as i32, other.size.y as i32, other.size.z as i32];
+        let mut min = [0i32; 3];
+        let mut size = [0u32; 3];
+        for axis in 0..3 {
+            min[axis] = offset[axis].min(0);
+            size[axis] = ((sides[axis].max(offset[axis] + other_sides[axis]) - min[axis]) as u32).min(256);
+        }
+        let size = Size { x: size[0], y: size[1], z: size[2] };
+        let mut grid = VoxGrid::new(size);
+        for (x, y, z, c) in self.iter() {
+            grid.set(x as i32 - min[0], y as i32 - min[1], z as i32 - min[2], c);
+        }
+        for (x, y, z, c) in other.iter() {
+            let (x, y, z) = (x as i32 + offset[0] - min[0], y as i32 + offset[1] - min[1], z as i32 + offset[2] - min[2]);
+            grid.set(x, y, z, remap[c as usize]);
+        }
+        (grid.to_model(), merged_palette)
+    }
+
+    /// All voxels of either model, with `other` placed at `offset` in this
+    /// model's space. The result keeps this model's size, so parts of
+    /// `other` outside it are dropped.
+    pub fn union(&self, other: &Model, offset: [i32; 3], conflict: Conflict) -> Model {
+        let mut grid = self.to_dense_grid();
+        for (x, y, z, c) in placed(other, offset, self.size) {
+            if conflict == Conflict::KeepTheirs || !grid.is_solid(x, y, z) {
+                grid.set(x, y, z, c);
+            }
+        }
+        grid.to_model()
+    }
+
+    /// This model with every cell covered by `other` at `offset` removed.
+    pub fn subtract(&self, other: &Model, offset: [i32; 3]) -> Model {
+        let mut grid = self.to_dense_grid();
+        for (x, y, z, _) in placed(other, offset, self.size) {
+            grid.set(x, y, z, 0);
+        }
+        grid.to_model()
+    }
+
+    /// Only the cells both models fill, with `other` at `offset`.
+    pub fn intersect(&self, other: &Model, offset: [i32; 3], conflict: Conflict) -> Model {
+        let ours = self.to_dense_grid();
+        let mut grid = VoxGrid::new(ours.size);
+        for (x, y, z, c) in placed(other, offset, self.size) {
+            match ours.get(x, y, z) {
+                0 => {}
+                ours if conflict == Conflict::KeepOurs => {
+                    grid.set(x, y, z, ours);
+                }
+                _ => {
+                    grid.set(x, y, z, c);
+                }
+            }
+        }
+        grid.to_model()
+    }
+
+    /// The cells 6-connected to `seed` that share its color index. An empty
+    /// seed selects the connected empty space, which tells enclosed
+    /// interiors apart from the outside.
+    pub fn flood_select(&self, seed: [u32; 3]) -> Vec<[u32; 3]> {
+        let grid = self.to_dense_grid();
+        let (x, y, z) = (seed[0] as i32, seed[1] as i32, seed[2] as i32);
+        if grid.index(x, y, z).is_none() {
+            return Vec::new();
+        }
+        let target = grid.get(x, y, z);
+        flood(&grid, seed, |c| c == target)
+    }
+
+    /// Recolors the region `flood_select` finds from `seed`; color 0 erases
+    /// it. Returns the number of cells changed.
+    pub fn flood_fill(&mut self, seed: [u32; 3], c: u8) -> usize {
+        let region = self.flood_select(seed);
+        if region.first().and_then(|p| self.voxel_at(p[0], p[1], p[2])).unwrap_or(0) == c {
+            return 0;
+        }
+        let mut grid = self.to_dense_grid();
+        for p in &region {
+            grid.set(p[0] as i32, p[1] as i32, p[2] as i32, c);
+        }
+        *self = grid.to_model();
+        region.len()
+    }
+
+    /// Keeps only voxels within `thickness` face steps of an empty cell or
+    /// the model border, so a thickness of 1 leaves a one-voxel shell.
+    pub fn hollowed(&self, thickness: u32) -> Model {
+        let grid = self.to_dense_grid();
+        let mut depth = vec![0u32; grid.data().len()];
+        let mut queue = VecDeque::new();
+        for (x, y, z, _) in grid.to_model().iter() {
+            let (x, y, z) = (x as i32, y as i32, z as i32);
+            if let Some(index) = grid.index(x, y, z) {
+                if NEIGHBORS.iter().any(|&(dx, dy, dz)| !grid.is_solid(x + dx, y + dy, z + dz)) {
+                    depth[index] = 1;
+                    queue.push_back((x, y, z, index));
+                }
+            }
+        }
+        while let Some((x, y, z, index)) = queue.pop_front() {
+            let next_depth = depth[index] + 1;
+            if next_depth > thickness {
+                continue;
+            }
+            for &(dx, dy, dz) in &NEIGHBORS {
+                let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+                if let Some(index) = grid.index(nx, ny, nz) {
+                    if grid.is_solid(nx, ny, nz) && depth[index] == 0 {
+                        depth[index] = next_depth;
+                        queue.push_back((nx, ny, nz, index));
+                    }
+                }
+            }
+        }
+        // Voxels outside the size are not in the grid and are dropped.
+        let voxels = self
+            .voxels
+            .iter()
+            .filter(|v| grid.index(v.x as i32, v.y as i32, v.z as i32).is_some_and(|index| depth[index] != 0))
+            .cloned()
+            .collect();
+        Model::from_voxels(self.size, voxels)
+    }
+
+    /// The voxels with at least one empty face neighbor; cells outside the
+    /// model count as empty.
+    pub fn surface_voxels(&self) -> Vec<Voxel> {
+        self.surface_faces().into_iter().map(|(voxel, _)| voxel).collect()
+    }
+
+    /// Surface voxels with a mask of their visible faces, as in
+    /// `face_masks`.
+    pub fn surface_faces(&self) -> Vec<(Voxel, u8)> {
+        let grid = self.to_dense_grid();
+        grid.to_model()
+            .voxels
+            .into_iter()
+            .map(|v| (v, face_mask(&grid, v.x as i32, v.y as i32, v.z as i32)))
+            .filter(|&(_, mask)| mask != 0)
+            .collect()
+    }
+
+    /// The visible faces of each entry of `voxels`, in the same order. Bits
+    /// 0 to 5 are set when the neighbor toward +X, -X, +Y, -Y, +Z or -Z is
+    /// empty, so 0 marks a buried voxel; cells outside the model count as
+    /// empty. The bits follow the face order of the meshers.
+    pub fn face_masks(&self) -> Vec<u8> {
+        let grid = self.to_dense_grid();
+        self.voxels.iter().map(|v| face_mask(&grid, v.x as i32, v.y as i32, v.z as i32)).collect()
+    }
+
+    /// Labels each entry of `voxels` with the 6-connected solid region it
+    /// belongs to, regardless of color. Labels count up from 0 in the
+    /// order regions are first met. A voxel outside the size is a region
+    /// of its own.
+    pub fn connected_components(&self) -> Vec<u32> {
+        let grid = self.to_dense_grid();
+        let mut labels = vec![u32::MAX; grid.data().len()];
+        let mut count = 0;
+        self.voxels
+            .iter()
+            .map(|v| {
+                let index = match grid.index(v.x as i32, v.y as i32, v.z as i32) {
+                    Some(index) => index,
+                    None => {
+                        count += 1;
+                        return count - 1;
+                    }
+                };
+                if labels[index] == u32::MAX {
+                    for p in flood(&grid, [v.x as u32, v.y as u32, v.z as u32], |c| c != 0) {
+                        if let Some(filled) = grid.index(p[0] as i32, p[1] as i32, p[2] as i32) {
+                            labels[filled] = count;
+                        }
+                    }
+                    count += 1;
+                }
+                labels[index]
+            })
+            .collect()
+    }
+
+    /// One model per connected region, in label order. Each keeps this
+    /// model's size so the pieces stay in place.
+    pub fn split_components(&self) -> Vec<Model> {
+        let mut models: Vec<Model> = Vec::new();
+        for (voxel, label) in self.voxels.iter().zip(self.connected_components()) {
+            if label as usize == models.len() {
+                models.push(Model::new(self.size));
+            }
+            models[label as usize].voxels.push(*voxel);
+        }
+        models
+    }
+}
+
+/// The cells reachable from `seed` through face neighbors whose color
+/// index passes `matches`, in visiting order.
+pub(crate) fn flood<F: Fn(u8) -> bool>(grid: &VoxGrid, seed: [u32; 3], matches: F) -> Vec<[u32; 3]> {
+    let mut visited = vec![false; grid.data().len()];
+    let mut region = Vec::new();
+    let mut stack = vec![seed];
+    while let Some(p) = stack.pop() {
+        let (x, y, z) = (p[0] as i32, p[1] as i32, p[2] as i32);
+        let index = match grid.index(x, y, z) {
+            Some(index) if !visited[index] && matches(grid.get(x, y, z)) => index,
+            _ => continue,
+        };
+        visited[index] = true;
+        region.push(p);
+        for &(dx, dy, dz) in &NEIGHBORS {
+            let next = [x + dx, y + dy, z + dz];
+            if next.iter().all(|&coordinate| coordinate >= 0) {
+                stack.push([next[0] as u32, next[1] as u32, next[2] as u32]);
+            }
+        }
+    }
+    region
+}
+
+/// The voxels of `other` moved by `offset` that land inside `size`.
+fn placed(other: &Model, offset: [i32; 3], size: Size) -> impl Iterator<Item = (i32, i32, i32, u8)> + '_ {
+    other
+        .iter()
+        .map(move |(x, y, z, c)| (x as i32 + offset[0], y as i32 + offset[1], z as i32 + offset[2], c))
+        .filter(move |&(x, y, z, _)| in_bounds(size, x, y, z))
+}
+
+fn in_bounds(size: Size, x: i32, y: i32, z: i32) -> bool {
+    x >= 0 && y >= 0 && z >= 0 && (x as u32) < size.x && (y as u32) < size.y && (z as u32) < size.z
+}
+
+/// The visible-face mask of the cell at `(x, y, z)`, as in
+/// `Model::face_masks`.
+pub(crate) fn face_mask(grid: &VoxGrid, x: i32, y: i32, z: i32) -> u8 {
+    NEIGHBORS
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(dx, dy, dz))| !grid.is_solid(x + dx, y + dy, z + dz))
+        .fold(0u8, |mask, (bit, _)| mask | 1 << bit)
+}
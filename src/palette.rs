@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(not(feature = "std"))]
+use std::cell::OnceCell as OnceLock;
+use std::vec::Vec;
+use color_tree::ColorTree;
+
+/// Byte order used when packing a `Color` into a `u32`, listed from the most
+/// significant byte down. `Rgba` packs red into the high byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PackOrder {
+    Rgba,
+    Argb,
+    Abgr,
+    Bgra,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+impl fmt::Debug for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Color {{ r: {}, g: {}, b: {}, a: {} }}", self.r, self.g, self.b, self.a)
+    }
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    pub fn from_u32(packed: u32, order: PackOrder) -> Color {
+        let bytes = packed.to_be_bytes();
+        match order {
+            PackOrder::Rgba => Color::new(bytes[0], bytes[1], bytes[2], bytes[3]),
+            PackOrder::Argb => Color::new(bytes[1], bytes[2], bytes[3], bytes[0]),
+            PackOrder::Abgr => Color::new(bytes[3], bytes[2], bytes[1], bytes[0]),
+            PackOrder::Bgra => Color::new(bytes[2], bytes[1], bytes[0], bytes[3]),
+        }
+    }
+
+    pub fn as_u32(self, order: PackOrder) -> u32 {
+        let bytes = match order {
+            PackOrder::Rgba => [self.r, self.g, self.b, self.a],
+            PackOrder::Argb => [self.a, self.r, self.g, self.b],
+            PackOrder::Abgr => [self.a, self.b, self.g, self.r],
+            PackOrder::Bgra => [self.b, self.g, self.r, self.a],
+        };
+        u32::from_be_bytes(bytes)
+    }
+}
+
+/// 256 colors indexed by voxel color index. Index 0 is the empty voxel and is
+/// always transparent, so the first RGBA chunk entry lives at index 1.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Palette {
+    colors: Vec<Color>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    search: OnceLock<ColorTree>,
+}
+impl PartialEq for Palette {
+    fn eq(&self, other: &Palette) -> bool {
+        self.colors == other.colors
+    }
+}
+impl fmt::Debug for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Palette {{ colors: {:?} }}", self.colors)
+    }
+}
+
+impl Palette {
+    /// Builds a palette from the 256 entries of an RGBA chunk, shifting them
+    /// up by one index. The last chunk entry has no voxel index and is dropped.
+    pub fn from_rgba_chunk(entries: &[Color]) -> Palette {
+        let mut colors = vec![Color::default(); 256];
+        for (index, color) in entries.iter().take(255).enumerate() {
+            colors[index + 1] = *color;
+        }
+        Palette { colors, search: OnceLock::new() }
+    }
+
+    /// The 256 entries to store in an RGBA chunk, the inverse of
+    /// `from_rgba_chunk`.
+    pub fn to_rgba_chunk(&self) -> Vec<Color> {
+        let mut entries: Vec<Color> = self.colors[1..].to_vec();
+        entries.push(Color::default());
+        entries
+    }
+
+    pub fn get(&self, index: u8) -> Color {
+        self.colors[index as usize]
+    }
+
+    pub fn set(&mut self, index: u8, color: Color) {
+        if index != 0 {
+            self.colors[index as usize] = color;
+            self.search = OnceLock::new();
+        }
+    }
+
+    /// Moves every entry `i` to `mapping[i]`, as `Model::remap_colors` does
+    /// with voxel indices. When several entries land on the same index the
+    /// highest one wins; entries nothing maps to keep their color.
+    pub fn remap(&mut self, mapping: &[u8; 256]) {
+        let old = self.colors.clone();
+        for index in 1..256 {
+            self.set(mapping[index], old[index]);
+        }
+    }
+
+    /// For each of our indices, the index of the closest color in `target`,
+    /// for moving models onto a shared palette. Index 0 stays 0.
+    pub fn mapping_to(&self, target: &Palette) -> [u8; 256] {
+        let mut builder = PaletteBuilder::from_palette(target);
+        let mut mapping = [0; 256];
+        for index in 1..=255u8 {
+            mapping[index as usize] = builder.index_of(self.get(index));
+        }
+        mapping
+    }
+
+    /// The index of the entry closest to an RGB color, ignoring alpha and
+    /// never picking index 0. The first call builds a k-d tree that later
+    /// calls reuse until the palette changes.
+    pub fn closest_index(&self, r: u8, g: u8, b: u8) -> u8 {
+        self.search.get_or_init(|| ColorTree::new(&self.colors)).closest([r, g, b])
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn as_u32(&self, order: PackOrder) -> Vec<u32> {
+        self.colors.iter().map(|color| color.as_u32(order)).collect()
+    }
+
+    /// Lays the 256 colors out row by row in a `width` texels wide RGBA
+    /// texture, e.g. 256x1 or 16x16.
+    pub fn to_texture_atlas(&self, width: u32) -> TextureAtlas {
+        let width = width.clamp(1, 256);
+        let height = 256u32.div_ceil(width);
+        let mut pixels = vec![0; (width * height * 4) as usize];
+        let mut uvs = Vec::with_capacity(256);
+        for (index, color) in self.colors.iter().enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            let at = ((x + y * width) * 4) as usize;
+            pixels[at..at + 4].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+            uvs.push([(x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32]);
+        }
+        TextureAtlas { width, height, pixels, uvs }
+    }
+
+    pub(crate) fn magica_voxel_default() -> Palette {
+        Palette {
+            colors: DEFAULT_PALETTE.iter().map(|&packed| Color::from_u32(packed, PackOrder::Rgba)).collect(),
+            search: OnceLock::new(),
+        }
+    }
+}
+
+/// Collects the distinct colors of a true-color source into a palette,
+/// handing out indices from 1. Once all 255 slots are taken, further colors
+/// map to the closest existing entry.
+pub(crate) struct PaletteBuilder {
+    palette: Palette,
+    indices: BTreeMap<Color, u8>,
+    /// Colors that did not get an entry of their own, with their closest.
+    matches: BTreeMap<Color, u8>,
+    open: bool,
+}
+
+impl PaletteBuilder {
+    pub fn new() -> PaletteBuilder {
+        PaletteBuilder {
+            palette: Palette { colors: vec![Color::default(); 256], search: OnceLock::new() },
+            indices: BTreeMap::new(),
+            matches: BTreeMap::new(),
+            open: true,
+        }
+    }
+
+    /// A builder that only maps onto `colors`, e.g. the output of
+    /// `median_cut`, and never adds entries of its own.
+    #[cfg(feature = "std")]
+    pub fn with_colors(colors: &[Color]) -> PaletteBuilder {
+        let mut builder = PaletteBuilder::new();
+        for &color in colors.iter().take(255) {
+            builder.index_of(color);
+        }
+        builder.open = false;
+        builder
+    }
+
+    /// A builder that maps onto the entries of `palette` at their existing
+    /// indices, leaving the palette unchanged.
+    pub fn from_palette(palette: &Palette) -> PaletteBuilder {
+        PaletteBuilder::from_used(palette, &[true; 256])
+    }
+
+    /// `from_palette` limited to the indices marked in `used`.
+    pub fn from_used(palette: &Palette, used: &[bool; 256]) -> PaletteBuilder {
+        let mut builder = PaletteBuilder::new();
+        for index in (1..=255).rev().filter(|&index| used[index as usize]) {
+            builder.indices.insert(palette.get(index), index);
+        }
+        builder.palette = palette.clone();
+        builder.open = false;
+        builder
+    }
+
+    pub fn index_of(&mut self, color: Color) -> u8 {
+        if let Some(&index) = self.indices.get(&color).or_else(|| self.matches.get(&color)) {
+            return index;
+        }
+        if !self.open || self.indices.len() == 255 {
+            let index = self.closest(color);
+            self.matches.insert(color, index);
+            return index;
+        }
+        let index = self.indices.len() as u8 + 1;
+        self.palette.set(index, color);
+        self.indices.insert(color, index);
+        index
+    }
+
+    fn closest(&self, color: Color) -> u8 {
+        let distance = |other: Color| {
+            let d = |a: u8, b: u8| (a as i32 - b as i32) * (a as i32 - b as i32);
+            d(color.r, other.r) + d(color.g, other.g) + d(color.b, other.b) + d(color.a, other.a)
+        };
+        self.indices.values().cloned().min_by_key(|&index| (distance(self.palette.get(index)), index)).unwrap_or(1)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn finish(self) -> Palette {
+        self.palette
+    }
+}
+
+/// Reduces `colors` to at most `count` representatives by repeatedly
+/// splitting the box with the widest channel range at its median, then
+/// averaging each box.
+#[cfg(feature = "std")]
+pub(crate) fn median_cut(colors: &[Color], count: usize) -> Vec<Color> {
+    let channel = |color: &Color, axis: usize| [color.r, color.g, color.b, color.a][axis];
+    let range = |colors: &[Color], axis: usize| {
+        let values = colors.iter().map(|color| channel(color, axis));
+        values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+    };
+    let mut boxes = vec![colors.to_vec()];
+    while boxes.len() < count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|&(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| (index, (0..4).map(|axis| (range(colors, axis), axis)).max().unwrap()))
+            .max_by_key(|&(_, (width, _))| width);
+        let (index, (width, axis)) = match widest {
+            Some(widest) => widest,
+            None => break,
+        };
+        if width == 0 {
+            break;
+        }
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|color| channel(color, axis));
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    boxes
+        .iter()
+        .filter(|colors| !colors.is_empty())
+        .map(|colors| {
+            let n = colors.len() as u64;
+            let mean = |axis: usize| (colors.iter().map(|color| channel(color, axis) as u64).sum::<u64>() / n) as u8;
+            Color::new(mean(0), mean(1), mean(2), mean(3))
+        })
+        .collect()
+}
+
+/// A palette baked into a small RGBA texture, with the UV of each palette
+/// index at the center of its texel.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextureAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    uvs: Vec<[f32; 2]>,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::magica_voxel_default()
+    }
+}
+
+impl TextureAtlas {
+    pub fn uv(&self, index: u8) -> [f32; 2] {
+        self.uvs[index as usize]
+    }
+}
+
+/// MagicaVoxel's default palette, used when a file has no RGBA chunk. Colors
+/// are packed as `PackOrder::Rgba` and indexed like `Palette`, so entry 0 is
+/// the empty voxel.
+pub const DEFAULT_PALETTE: [u32; 256] = [
+    0x0, 0xffffffff, 0xffffccff, 0xffff99ff, 0xffff66ff, 0xffff33ff, 0xffff00ff, 0xffccffff,
+    0xffccccff, 0xffcc99ff, 0xffcc66ff, 0xffcc33ff, 0xffcc00ff, 0xff99ffff, 0xff99ccff, 0xff9999ff,
+    0xff9966ff, 0xff9933ff, 0xff9900ff, 0xff66ffff, 0xff66ccff, 0xff6699ff, 0xff6666ff, 0xff6633ff,
+    0xff6600ff, 0xff33ffff, 0xff33ccff, 0xff3399ff, 0xff3366ff, 0xff3333ff, 0xff3300ff, 0xff00ffff,
+    0xff00ccff, 0xff0099ff, 0xff0066ff, 0xff0033ff, 0xff0000ff, 0xccffffff, 0xccffccff, 0xccff99ff,
+    0xccff66ff, 0xccff33ff, 0xccff00ff, 0xccccffff, 0xccccccff, 0xcccc99ff, 0xcccc66ff, 0xcccc33ff,
+    0xcccc00ff, 0xcc99ffff, 0xcc99ccff, 0xcc9999ff, 0xcc9966ff, 0xcc9933ff, 0xcc9900ff, 0xcc66ffff,
+    0xcc66ccff, 0xcc6699ff, 0xcc6666ff, 0xcc6633ff, 0xcc6600ff, 0xcc33ffff, 0xcc33ccff, 0xcc3399ff,
+    0xcc3366ff, 0xcc3333ff, 0xcc3300ff, 0xcc00ffff, 0xcc00ccff, 0xcc0099ff, 0xcc0066ff, 0xcc0033ff,
+    0xcc0000ff, 0x99ffffff, 0x99ffccff, 0x99ff99ff, 0x99ff66ff, 0x99ff33ff, 0x99ff00ff, 0x99ccffff,
+    0x99ccccff, 0x99cc99ff, 0x99cc66ff, 0x99cc33ff, 0x99cc00ff, 0x9999ffff, 0x9999ccff, 0x999999ff,
+    0x999966ff, 0x999933ff, 0x999900ff, 0x9966ffff, 0x9966ccff, 0x996699ff, 0x996666ff, 0x996633ff,
+    0x996600ff, 0x9933ffff, 0x9933ccff, 0x993399ff, 0x993366ff, 0x993333ff, 0x993300ff, 0x9900ffff,
+    0x9900ccff, 0x990099ff, 0x990066ff, 0x990033ff, 0x990000ff, 0x66ffffff, 0x66ffccff, 0x66ff99ff,
+    0x66ff66ff, 0x66ff33ff, 0x66ff00ff, 0x66ccffff, 0x66ccccff, 0x66cc99ff, 0x66cc66ff, 0x66cc33ff,
+    0x66cc00ff, 0x6699ffff, 0x6699ccff, 0x669999ff, 0x669966ff, 0x669933ff, 0x669900ff, 0x6666ffff,
+    0x6666ccff, 0x666699ff, 0x666666ff, 0x666633ff, 0x666600ff, 0x6633ffff, 0x6633ccff, 0x663399ff,
+    0x663366ff, 0x663333ff, 0x663300ff, 0x6600ffff, 0x6600ccff, 0x660099ff, 0x660066ff, 0x660033ff,
+    0x660000ff, 0x33ffffff, 0x33ffccff, 0x33ff99ff, 0x33ff66ff, 0x33ff33ff, 0x33ff00ff, 0x33ccffff,
+    0x33ccccff, 0x33cc99ff, 0x33cc66ff, 0x33cc33ff, 0x33cc00ff, 0x3399ffff, 0x3399ccff, 0x339999ff,
+    0x339966ff, 0x339933ff, 0x339900ff, 0x3366ffff, 0x3366ccff, 0x336699ff, 0x336666ff, 0x336633ff,
+    0x336600ff, 0x3333ffff, 0x3333ccff, 0x333399ff, 0x333366ff, 0x333333ff, 0x333300ff, 0x3300ffff,
+    0x3300ccff, 0x330099ff, 0x330066ff, 0x330033ff, 0x330000ff, 0xffffff, 0xffccff, 0xff99ff,
+    0xff66ff, 0xff33ff, 0xff00ff, 0xccffff, 0xccccff, 0xcc99ff, 0xcc66ff, 0xcc33ff,
+    0xcc00ff, 0x99ffff, 0x99ccff, 0x9999ff, 0x9966ff, 0x9933ff, 0x9900ff, 0x66ffff,
+    0x66ccff, 0x6699ff, 0x6666ff, 0x6633ff, 0x6600ff, 0x33ffff, 0x33ccff, 0x3399ff,
+    0x3366ff, 0x3333ff, 0x3300ff, 0xffff, 0xccff, 0x99ff, 0x66ff, 0x33ff,
+    0xee0000ff, 0xdd0000ff, 0xbb0000ff, 0xaa0000ff, 0x880000ff, 0x770000ff, 0x550000ff, 0x440000ff,
+    0x220000ff, 0x110000ff, 0xee00ff, 0xdd00ff, 0xbb00ff, 0xaa00ff, 0x8800ff, 0x7700ff,
+    0x5500ff, 0x4400ff, 0x2200ff, 0x1100ff, 0xeeff, 0xddff, 0xbbff, 0xaaff,
+    0x88ff, 0x77ff, 0x55ff, 0x44ff, 0x22ff, 0x11ff, 0xeeeeeeff, 0xddddddff,
+    0xbbbbbbff, 0xaaaaaaff, 0x888888ff, 0x777777ff, 0x555555ff, 0x444444ff, 0x222222ff, 0x111111ff,
+];
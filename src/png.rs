@@ -0,0 +1,25 @@
+use std::io;
+use std::io::Cursor;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, ImageFormat};
+
+/// Encodes RGBA pixels as a PNG.
+pub(crate) fn encode(width: u32, height: u32, rgba: &[u8]) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png).write_image(rgba, width, height, ExtendedColorType::Rgba8).map_err(to_io)?;
+    Ok(png)
+}
+
+/// Decodes a PNG of any color type, bit depth or interlacing into 8-bit
+/// RGBA pixels.
+pub(crate) fn decode(bytes: &[u8]) -> io::Result<(u32, u32, Vec<u8>)> {
+    let image = image::load(Cursor::new(bytes), ImageFormat::Png).map_err(to_io)?.into_rgba8();
+    Ok((image.width(), image.height(), image.into_raw()))
+}
+
+fn to_io(error: image::ImageError) -> io::Error {
+    match error {
+        image::ImageError::IoError(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
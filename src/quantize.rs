@@ -0,0 +1,84 @@
+use model::Model;
+use palette::{Color, Palette};
+
+/// Whether `Model::requantize` dithers colors that fall between palette
+/// entries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dither {
+    None,
+    /// Nudges each voxel's color by a 4x4 Bayer pattern, shifted from layer
+    /// to layer, before matching it, so gradients mix neighboring entries
+    /// instead of banding.
+    Ordered,
+}
+
+/// How far `Dither::Ordered` moves a color at most, in 0-255 RGB units.
+const DITHER_SPREAD: f32 = 32.0;
+
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// The CIELAB coordinates of an sRGB color under a D65 white point.
+pub fn to_lab(color: Color) -> [f32; 3] {
+    lab([color.r as f32, color.g as f32, color.b as f32])
+}
+
+/// The index of the entry of `palette` perceptually closest to `color`,
+/// by CIE76 distance. Index 0, the empty voxel, is never picked; ties go
+/// to the lower index.
+pub fn nearest_index(color: Color, palette: &Palette) -> u8 {
+    nearest(to_lab(color), &palette_lab(palette))
+}
+
+impl Model {
+    /// Moves the model from `palette` onto `new_palette`, giving every voxel
+    /// the perceptually closest entry. Returns how many voxels changed
+    /// index.
+    pub fn requantize(&mut self, palette: &Palette, new_palette: &Palette, dither: Dither) -> usize {
+        let targets = palette_lab(new_palette);
+        let mut cache = [None; 256];
+        let mut changed = 0;
+        for voxel in &mut self.voxels {
+            let c = match dither {
+                Dither::None => *cache[voxel.c as usize].get_or_insert_with(|| nearest(to_lab(palette.get(voxel.c)), &targets)),
+                Dither::Ordered => {
+                    let (x, y, z) = (voxel.x as usize, voxel.y as usize, voxel.z as usize);
+                    let threshold = BAYER[(y + z) % 4][(x + 2 * z) % 4] as f32 / 16.0 - 0.5;
+                    let color = palette.get(voxel.c);
+                    let nudge = |channel: u8| (channel as f32 + threshold * DITHER_SPREAD).clamp(0.0, 255.0);
+                    nearest(lab([nudge(color.r), nudge(color.g), nudge(color.b)]), &targets)
+                }
+            };
+            if c != voxel.c {
+                voxel.c = c;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.invalidate_index();
+        }
+        changed
+    }
+}
+
+fn palette_lab(palette: &Palette) -> Vec<[f32; 3]> {
+    palette.colors().iter().map(|&color| to_lab(color)).collect()
+}
+
+fn nearest(lab: [f32; 3], targets: &[[f32; 3]]) -> u8 {
+    let distance = |other: &[f32; 3]| (0..3).map(|i| (lab[i] - other[i]) * (lab[i] - other[i])).sum::<f32>();
+    (1..targets.len()).min_by(|&a, &b| distance(&targets[a]).total_cmp(&distance(&targets[b]))).unwrap_or(1) as u8
+}
+
+fn lab(rgb: [f32; 3]) -> [f32; 3] {
+    let linear = |channel: f32| {
+        let c = channel / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let (r, g, b) = (linear(rgb[0]), linear(rgb[1]), linear(rgb[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette, PaletteBuilder};
+
+const CODE_FLAG: u32 = 2;
+const NEXT_SLICE_FLAG: u32 = 6;
+
+/// One named matrix of a Qubicle file, moved into MagicaVoxel's Z-up space.
+#[derive(Clone, PartialEq, Debug)]
+pub struct QbMatrix {
+    pub name: String,
+    /// Position of the matrix's minimum corner.
+    pub position: [i32; 3],
+    pub model: Model,
+}
+
+/// A Qubicle Binary file normalized into paletted models. The true colors
+/// of all matrices share one palette; past 255 distinct colors the rest are
+/// matched to the closest entry.
+#[derive(Clone, PartialEq, Debug)]
+pub struct QbFile {
+    pub matrices: Vec<QbMatrix>,
+    pub palette: Palette,
+}
+
+impl QbFile {
+    pub fn models(&self) -> Vec<&Model> {
+        self.matrices.iter().map(|matrix| &matrix.model).collect()
+    }
+
+    /// Encodes the file as an uncompressed, left-handed RGBA `.qb`, the
+    /// inverse of `read_qb`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for &word in &[0x0101u32, 0, 0, 0, 0, self.matrices.len() as u32] {
+            data.write_u32::<LittleEndian>(word).unwrap();
+        }
+        for matrix in &self.matrices {
+            let name = matrix.name.as_bytes();
+            let name = &name[..name.len().min(255)];
+            data.push(name.len() as u8);
+            data.extend_from_slice(name);
+            let size = matrix.model.size;
+            let p = matrix.position;
+            for &word in &[size.x, size.z, size.y] {
+                data.write_u32::<LittleEndian>(word).unwrap();
+            }
+            for &word in &[p[0], p[2], p[1]] {
+                data.write_i32::<LittleEndian>(word).unwrap();
+            }
+            let grid = matrix.model.to_dense_grid();
+            for y in 0..size.y as i32 {
+                for z in 0..size.z as i32 {
+                    for x in 0..size.x as i32 {
+                        let packed = match grid.get(x, y, z) {
+                            0 => 0,
+                            c => {
+                                let color = self.palette.get(c);
+                                u32::from_le_bytes([color.r, color.g, color.b, 255])
+                            }
+                        };
+                        data.write_u32::<LittleEndian>(packed).unwrap();
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+/// Builds a `.qb` holding one matrix per model, all placed at the origin.
+pub fn export_qb(models: &[Model], palette: &Palette) -> Vec<u8> {
+    let matrices = models
+        .iter()
+        .enumerate()
+        .map(|(i, model)| QbMatrix { name: format!("model_{}", i), position: [0, 0, 0], model: model.clone() })
+        .collect();
+    QbFile { matrices, palette: palette.clone() }.to_bytes()
+}
+
+pub fn write_qb<P: AsRef<Path>>(models: &[Model], palette: &Palette, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(&export_qb(models, palette))
+}
+
+pub fn load_qb<P: AsRef<Path>>(path: P) -> io::Result<QbFile> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    read_qb(&bytes)
+}
+
+/// Parses a `.qb` file. Qubicle is Y-up, so its Y and Z axes are swapped,
+/// and right-handed files are mirrored along the new Y axis.
+pub fn read_qb(bytes: &[u8]) -> io::Result<QbFile> {
+    let mut cursor = Cursor::new(bytes);
+    let _version = cursor.read_u32::<LittleEndian>()?;
+    let bgra = cursor.read_u32::<LittleEndian>()? == 1;
+    let right_handed = cursor.read_u32::<LittleEndian>()? == 1;
+    let compressed = cursor.read_u32::<LittleEndian>()? == 1;
+    let _visibility_mask_encoded = cursor.read_u32::<LittleEndian>()?;
+    let matrix_count = cursor.read_u32::<LittleEndian>()?;
+
+    let mut palette = PaletteBuilder::new();
+    let mut matrices = Vec::new();
+    for _ in 0..matrix_count {
+        let name_length = cursor.read_u8()? as usize;
+        let mut name = vec![0; name_length];
+        cursor.read_exact(&mut name)?;
+        let size = [
+            cursor.read_u32::<LittleEndian>()?,
+            cursor.read_u32::<LittleEndian>()?,
+            cursor.read_u32::<LittleEndian>()?,
+        ];
+        let position = [
+            cursor.read_i32::<LittleEndian>()?,
+            cursor.read_i32::<LittleEndian>()?,
+            cursor.read_i32::<LittleEndian>()?,
+        ];
+        if size.iter().any(|&side| side > 256) {
+            return Err(invalid_data(format!("matrix of size {:?} exceeds 256 voxels per axis", size)));
+        }
+
+        let mut model = Model::new(Size { x: size[0], y: size[2], z: size[1] });
+        let mut place = |x: u32, y: u32, z: u32, data: u32| {
+            // The alpha byte is either opacity or a face visibility mask;
+            // both are zero for empty cells.
+            if data >> 24 == 0 || x >= size[0] || y >= size[1] || z >= size[2] {
+                return;
+            }
+            let [a, b, c, _] = data.to_le_bytes();
+            let color = if bgra { Color::new(c, b, a, 255) } else { Color::new(a, b, c, 255) };
+            let depth = if right_handed { size[2] - 1 - z } else { z };
+            model.voxels.push(Voxel { x: x as u8, y: depth as u8, z: y as u8, c: palette.index_of(color) });
+        };
+        if compressed {
+            for z in 0..size[2] {
+                let mut index = 0;
+                loop {
+                    let data = cursor.read_u32::<LittleEndian>()?;
+                    if data == NEXT_SLICE_FLAG {
+                        break;
+                    }
+                    let (count, data) = if data == CODE_FLAG {
+                        (cursor.read_u32::<LittleEndian>()?, cursor.read_u32::<LittleEndian>()?)
+                    } else {
+                        (1, data)
+                    };
+                    for _ in 0..count {
+                        place(index % size[0].max(1), index / size[0].max(1), z, data);
+                        index += 1;
+                    }
+                }
+            }
+        } else {
+            for z in 0..size[2] {
+                for y in 0..size[1] {
+                    for x in 0..size[0] {
+                        place(x, y, z, cursor.read_u32::<LittleEndian>()?);
+                    }
+                }
+            }
+        }
+
+        let depth = if right_handed { -position[2] - size[2] as i32 } else { position[2] };
+        matrices.push(QbMatrix {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            position: [position[0], depth, position[1]],
+            model,
+        });
+    }
+    Ok(QbFile { matrices, palette: palette.finish() })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,96 @@
+use model::{Model, Voxel};
+
+/// The first voxel a ray meets.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hit {
+    pub voxel: Voxel,
+    /// Outward normal of the face the ray entered through, or all zeros
+    /// when the ray starts inside the voxel.
+    pub face: [i32; 3],
+    /// Distance from the origin along the normalized direction.
+    pub t: f32,
+}
+
+impl Model {
+    /// Walks the grid from `origin` along `dir` with a 3D DDA and returns
+    /// the first solid voxel within `max_dist`. Coordinates are in voxel
+    /// units, with voxel `(x, y, z)` covering `[x, x + 1)` on each axis.
+    pub fn raycast(&self, origin: [f32; 3], dir: [f32; 3], max_dist: f32) -> Option<Hit> {
+        let length = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt();
+        if length == 0.0 || !length.is_finite() {
+            return None;
+        }
+        let dir = [dir[0] / length, dir[1] / length, dir[2] / length];
+        let sides = [self.size.x as f32, self.size.y as f32, self.size.z as f32];
+
+        // Clip the ray against the model box.
+        let (mut t_enter, mut t_exit) = (f32::NEG_INFINITY, f32::INFINITY);
+        let mut enter_axis = None;
+        for axis in 0..3 {
+            if dir[axis] == 0.0 {
+                if origin[axis] < 0.0 || origin[axis] >= sides[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let a = (0.0 - origin[axis]) / dir[axis];
+            let b = (sides[axis] - origin[axis]) / dir[axis];
+            let (near, far) = if a < b { (a, b) } else { (b, a) };
+            if near > t_enter {
+                t_enter = near;
+                enter_axis = Some(axis);
+            }
+            t_exit = t_exit.min(far);
+        }
+        let t_start = t_enter.max(0.0);
+        if t_start >= t_exit || t_start > max_dist {
+            return None;
+        }
+
+        let mut cell = [0i32; 3];
+        let mut step = [0i32; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            let p = origin[axis] + dir[axis] * t_start;
+            cell[axis] = (p.floor() as i32).clamp(0, sides[axis] as i32 - 1);
+            if dir[axis] > 0.0 {
+                step[axis] = 1;
+                t_max[axis] = (cell[axis] as f32 + 1.0 - origin[axis]) / dir[axis];
+                t_delta[axis] = 1.0 / dir[axis];
+            } else if dir[axis] < 0.0 {
+                step[axis] = -1;
+                t_max[axis] = (cell[axis] as f32 - origin[axis]) / dir[axis];
+                t_delta[axis] = -1.0 / dir[axis];
+            }
+        }
+        let mut face = [0i32; 3];
+        let mut t = t_start;
+        if t_enter > 0.0 {
+            if let Some(axis) = enter_axis {
+                face[axis] = -step[axis];
+            }
+        }
+        loop {
+            if let Some(c) = self.voxel_at(cell[0] as u32, cell[1] as u32, cell[2] as u32) {
+                let voxel = Voxel { x: cell[0] as u8, y: cell[1] as u8, z: cell[2] as u8, c };
+                return Some(Hit { voxel, face, t });
+            }
+            let axis = if t_max[0] < t_max[1] {
+                if t_max[0] < t_max[2] { 0 } else { 2 }
+            } else if t_max[1] < t_max[2] {
+                1
+            } else {
+                2
+            };
+            t = t_max[axis];
+            if t > max_dist || t >= t_exit {
+                return None;
+            }
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            face = [0; 3];
+            face[axis] = -step[axis];
+        }
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "image")]
+use std::io;
+use model::Model;
+use palette::Palette;
+#[cfg(feature = "image")]
+use png;
+
+/// An RGBA image, row by row from the top, four bytes per pixel.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// A fully transparent image.
+    pub fn new(width: u32, height: u32) -> RgbaImage {
+        RgbaImage { width, height, pixels: vec![0; (width * height * 4) as usize] }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.width + x) * 4) as usize;
+        [self.pixels[offset], self.pixels[offset + 1], self.pixels[offset + 2], self.pixels[offset + 3]]
+    }
+
+    #[cfg(feature = "image")]
+    pub fn to_png(&self) -> io::Result<Vec<u8>> {
+        png::encode(self.width, self.height, &self.pixels)
+    }
+}
+
+/// The faces a thumbnail shows, as the corners of the face on a unit cube
+/// at the origin and the brightness it is drawn with: top, +X and +Y.
+const FACES: [([i32; 3], [[f32; 3]; 4], f32); 3] = [
+    ([0, 0, 1], [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]], 1.0),
+    ([1, 0, 0], [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 1.0]], 0.8),
+    ([0, 1, 0], [[0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]], 0.6),
+];
+
+const COS_30: f32 = 0.866_025_4;
+
+/// Renders `model` as a `size` by `size` isometric view, looking down from
+/// the +X, +Y, +Z corner, for previews without a GPU. The model is scaled
+/// to fit and centered on a transparent background; faces are flat shaded
+/// by direction.
+pub fn thumbnail(model: &Model, palette: &Palette, size: u32) -> RgbaImage {
+    let mut image = RgbaImage::new(size, size);
+    let (sx, sy, sz) = (model.size.x as f32, model.size.y as f32, model.size.z as f32);
+    let (width, height) = ((sx + sy) * COS_30, (sx + sy) / 2.0 + sz);
+    if size == 0 || width <= 0.0 || height <= 0.0 {
+        return image;
+    }
+    let scale = (size as f32 / width).min(size as f32 / height);
+    // Model (0, 0, 0) projects to x = 0 and sits `sx / 2` above the bottom
+    // of the drawing; center the drawing in the image.
+    let origin = [
+        (size as f32 - width * scale) / 2.0 + sx * COS_30 * scale,
+        (size as f32 + height * scale) / 2.0 - (sx + sy) / 2.0 * scale,
+    ];
+    let project = |p: [f32; 3]| [origin[0] + (p[1] - p[0]) * COS_30 * scale, origin[1] - (p[2] - (p[0] + p[1]) / 2.0) * scale];
+
+    let grid = model.to_dense_grid();
+    let mut depth = vec![i32::MIN; (size * size) as usize];
+    for voxel in grid.to_model().voxels {
+        let p = [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+        let color = palette.get(voxel.c);
+        for &(normal, corners, shade) in &FACES {
+            if grid.is_solid(p[0] + normal[0], p[1] + normal[1], p[2] + normal[2]) {
+                continue;
+            }
+            let quad = corners.map(|c| project([p[0] as f32 + c[0], p[1] as f32 + c[1], p[2] as f32 + c[2]]));
+            let shaded = [color.r, color.g, color.b].map(|channel| (channel as f32 * shade).round() as u8);
+            fill(&mut image, &mut depth, quad, p[0] + p[1] + p[2], [shaded[0], shaded[1], shaded[2], color.a]);
+        }
+    }
+    image
+}
+
+/// Fills the pixels whose centers fall inside the convex `quad`, where no
+/// nearer face has been drawn.
+fn fill(image: &mut RgbaImage, depth: &mut [i32], quad: [[f32; 2]; 4], distance: i32, rgba: [u8; 4]) {
+    let min = |axis: usize| quad.iter().fold(f32::MAX, |a, p| a.min(p[axis]));
+    let max = |axis: usize| quad.iter().fold(f32::MIN, |a, p| a.max(p[axis]));
+    let clamp = |value: f32, bound: u32| value.clamp(0.0, bound as f32) as u32;
+    let (x0, x1) = (clamp(min(0).floor(), image.width), clamp(max(0).ceil(), image.width));
+    let (y0, y1) = (clamp(min(1).floor(), image.height), clamp(max(1).ceil(), image.height));
+    let edge = |a: [f32; 2], b: [f32; 2], p: [f32; 2]| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = [x as f32 + 0.5, y as f32 + 0.5];
+            let sides = [edge(quad[0], quad[1], p), edge(quad[1], quad[2], p), edge(quad[2], quad[3], p), edge(quad[3], quad[0], p)];
+            let inside = sides.iter().all(|&side| side >= 0.0) || sides.iter().all(|&side| side <= 0.0);
+            let index = (y * image.width + x) as usize;
+            if inside && distance > depth[index] {
+                depth[index] = distance;
+                image.pixels[index * 4..index * 4 + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+}
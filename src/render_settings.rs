@@ -0,0 +1,97 @@
+use std::string::{String, ToString};
+use dict::Dict;
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CameraMode {
+    Perspective,
+    Free,
+    Panorama,
+    Orthographic,
+    Isometric,
+    Other(String),
+}
+
+impl CameraMode {
+    pub fn from_name(name: &str) -> CameraMode {
+        match name {
+            "pers" => CameraMode::Perspective,
+            "free" => CameraMode::Free,
+            "pano" => CameraMode::Panorama,
+            "orth" => CameraMode::Orthographic,
+            "iso" => CameraMode::Isometric,
+            other => CameraMode::Other(other.to_string()),
+        }
+    }
+    /// The `_mode` value, the inverse of `from_name`.
+    pub fn name(&self) -> &str {
+        match *self {
+            CameraMode::Perspective => "pers",
+            CameraMode::Free => "free",
+            CameraMode::Panorama => "pano",
+            CameraMode::Orthographic => "orth",
+            CameraMode::Isometric => "iso",
+            CameraMode::Other(ref name) => name,
+        }
+    }
+}
+
+/// A saved viewport camera from an rCAM chunk.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Camera {
+    pub id: u32,
+    pub mode: CameraMode,
+    pub focus: [f32; 3],
+    /// Pitch, yaw and roll in degrees.
+    pub angle: [f32; 3],
+    pub radius: f32,
+    pub frustum: f32,
+    pub fov: f32,
+}
+
+impl Camera {
+    pub fn from_attributes(id: u32, attributes: &Dict) -> Camera {
+        Camera {
+            id,
+            mode: CameraMode::from_name(attributes.get("_mode").unwrap_or("pers")),
+            focus: attributes.get_vec3_f32("_focus").unwrap_or([0.0; 3]),
+            angle: attributes.get_vec3_f32("_angle").unwrap_or([0.0; 3]),
+            radius: attributes.get_f32("_radius").unwrap_or(0.0),
+            frustum: attributes.get_f32("_frustum").unwrap_or(0.0),
+            fov: attributes.get_f32("_fov").unwrap_or(0.0),
+        }
+    }
+
+    /// The rCAM attributes for the camera, in the order MagicaVoxel writes
+    /// them.
+    pub fn to_attributes(&self) -> Dict {
+        let triple = |v: [f32; 3]| format!("{} {} {}", v[0], v[1], v[2]);
+        let mut attributes = Dict::new();
+        attributes.insert("_mode", self.mode.name());
+        attributes.insert("_focus", triple(self.focus));
+        attributes.insert("_angle", triple(self.angle));
+        attributes.insert("_radius", self.radius.to_string());
+        attributes.insert("_frustum", self.frustum.to_string());
+        attributes.insert("_fov", self.fov.to_string());
+        attributes
+    }
+}
+
+/// One group of renderer settings from an rOBJ chunk, such as `_bloom` or
+/// `_fog_uni`. The keys vary by type, so they are kept as raw attributes.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RenderObject {
+    pub object_type: String,
+    pub attributes: Dict,
+}
+
+impl RenderObject {
+    pub fn from_attributes(attributes: Dict) -> RenderObject {
+        RenderObject {
+            object_type: attributes.get_string("_type").unwrap_or_default(),
+            attributes,
+        }
+    }
+}
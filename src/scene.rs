@@ -0,0 +1,368 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::string::String;
+use std::vec::Vec;
+
+/// Packed rotation from a `_r` frame attribute.
+///
+/// Bits 0-1 hold the column of the non-zero entry in the first row, bits 2-3
+/// the column for the second row, and bits 4-6 the signs of rows one to three
+/// (set means negative). The third row takes the remaining column.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rotation(pub u8);
+
+impl Rotation {
+    pub const IDENTITY: Rotation = Rotation(0b0000100);
+
+    pub fn to_matrix(self) -> [[i32; 3]; 3] {
+        let first = (self.0 & 0b11) as usize;
+        let second = ((self.0 >> 2) & 0b11) as usize;
+        let third = 3usize.saturating_sub(first + second);
+        let mut matrix = [[0; 3]; 3];
+        for (row, &column) in [first, second, third].iter().enumerate() {
+            let negative = self.0 & (1 << (4 + row)) != 0;
+            matrix[row][column.min(2)] = if negative { -1 } else { 1 };
+        }
+        matrix
+    }
+
+    /// Packs a signed permutation matrix, or returns `None` if `m` is not
+    /// one.
+    pub fn from_matrix(m: [[i32; 3]; 3]) -> Option<Rotation> {
+        let mut bits = 0;
+        let mut used = [false; 3];
+        for (row, values) in m.iter().enumerate() {
+            let column = values.iter().position(|&value| value != 0)?;
+            if used[column] || values.iter().filter(|&&value| value != 0).count() != 1 || values[column].abs() != 1 {
+                return None;
+            }
+            used[column] = true;
+            if row < 2 {
+                bits |= (column as u8) << (2 * row);
+            }
+            if values[column] < 0 {
+                bits |= 1 << (4 + row);
+            }
+        }
+        Some(Rotation(bits))
+    }
+
+    pub fn apply(self, v: [i32; 3]) -> [i32; 3] {
+        let m = self.to_matrix();
+        [
+            m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+            m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+            m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+        ]
+    }
+}
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation::IDENTITY
+    }
+}
+impl fmt::Debug for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rotation({:?})", self.to_matrix())
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransformNode {
+    pub id: u32,
+    pub child: u32,
+    pub layer_id: i32,
+    pub rotation: Rotation,
+    pub translation: [i32; 3],
+    /// The `_name` attribute, as shown in MagicaVoxel's outliner.
+    pub name: Option<String>,
+    /// The `_hidden` attribute. Hidden nodes are skipped with everything
+    /// below them, like nodes on hidden layers.
+    pub hidden: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GroupNode {
+    pub id: u32,
+    pub children: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShapeNode {
+    pub id: u32,
+    pub models: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SceneNode {
+    Transform(TransformNode),
+    Group(GroupNode),
+    Shape(ShapeNode),
+}
+
+impl SceneNode {
+    pub fn id(&self) -> u32 {
+        match *self {
+            SceneNode::Transform(ref node) => node.id,
+            SceneNode::Group(ref node) => node.id,
+            SceneNode::Shape(ref node) => node.id,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Layer {
+    pub id: u32,
+    pub name: String,
+    pub hidden: bool,
+}
+
+/// A transform keyframe from an nTRN frame with an `_f` attribute.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransformKey {
+    pub frame: u32,
+    pub rotation: Rotation,
+    pub translation: [i32; 3],
+}
+
+/// A model switch from an nSHP model entry with an `_f` attribute.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModelKey {
+    pub frame: u32,
+    pub model: u32,
+}
+
+/// The keyframes of an animated scene, keyed by node id and sorted by
+/// frame. Files from before MagicaVoxel 0.99.7 have one key at frame 0 per
+/// node. A node holds its key until the next one; before its first key it
+/// uses that first key.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Animation {
+    pub transforms: BTreeMap<u32, Vec<TransformKey>>,
+    pub shapes: BTreeMap<u32, Vec<ModelKey>>,
+}
+
+impl Animation {
+    /// One past the last keyed frame, or 0 for a scene without keys. A key
+    /// at `u32::MAX` counts as the last frame.
+    pub fn frame_count(&self) -> u32 {
+        let transforms = self.transforms.values().flat_map(|keys| keys.last().map(|key| key.frame));
+        let shapes = self.shapes.values().flat_map(|keys| keys.last().map(|key| key.frame));
+        transforms.chain(shapes).max().map_or(0, |last| last.saturating_add(1))
+    }
+}
+
+/// How `Animation::sample` maps times past the last frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoopMode {
+    /// Holds the last frame.
+    Once,
+    /// Starts over at frame 0 after the last frame, as MagicaVoxel plays.
+    Loop,
+    /// Plays backwards to frame 0 after the last frame, then forwards again.
+    PingPong,
+}
+
+/// The state of every animated node at one moment, from
+/// `Animation::sample`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Pose {
+    /// Rotation and translation by transform node id.
+    pub transforms: BTreeMap<u32, (Rotation, [f32; 3])>,
+    /// The model shown by shape node id.
+    pub models: BTreeMap<u32, u32>,
+}
+
+impl Animation {
+    /// The pose `time` seconds in at `fps` frames per second. Translations
+    /// are interpolated linearly between keys; rotations and model switches
+    /// step at their key's frame, as in MagicaVoxel.
+    pub fn sample(&self, time: f32, fps: f32, loop_mode: LoopMode) -> Pose {
+        let frame = self.frame_at(time, fps, loop_mode);
+        let mut pose = Pose::default();
+        for (&id, keys) in &self.transforms {
+            let next = keys.partition_point(|key| key.frame as f32 <= frame);
+            let sampled = match (next.checked_sub(1).map(|index| &keys[index]), keys.get(next)) {
+                (Some(from), Some(to)) => {
+                    let t = (frame - from.frame as f32) / (to.frame - from.frame) as f32;
+                    let lerp = |axis: usize| from.translation[axis] as f32 + (to.translation[axis] - from.translation[axis]) as f32 * t;
+                    (from.rotation, [lerp(0), lerp(1), lerp(2)])
+                }
+                (Some(key), None) | (None, Some(key)) => (key.rotation, key.translation.map(|value| value as f32)),
+                (None, None) => continue,
+            };
+            pose.transforms.insert(id, sampled);
+        }
+        for (&id, keys) in &self.shapes {
+            if let Some(key) = key_at(keys, frame as u32, |key| key.frame) {
+                pose.models.insert(id, key.model);
+            }
+        }
+        pose
+    }
+
+    /// The fractional frame shown at `time`.
+    fn frame_at(&self, time: f32, fps: f32, loop_mode: LoopMode) -> f32 {
+        let last = self.frame_count().saturating_sub(1) as f32;
+        let frame = (time * fps).max(0.0);
+        if last == 0.0 {
+            return 0.0;
+        }
+        match loop_mode {
+            LoopMode::Once => frame.min(last),
+            LoopMode::Loop => frame % (last + 1.0),
+            LoopMode::PingPong => {
+                let phase = frame % (2.0 * last);
+                if phase > last { 2.0 * last - phase } else { phase }
+            }
+        }
+    }
+}
+
+/// The key in effect at `frame`, out of keys sorted by frame.
+pub(crate) fn key_at<T, F: Fn(&T) -> u32>(keys: &[T], frame: u32, key_frame: F) -> Option<&T> {
+    let after = keys.partition_point(|key| key_frame(key) <= frame);
+    keys.get(after.saturating_sub(1))
+}
+
+/// One model placed in the world by `SceneGraph::frame`, with the
+/// transforms from the root down composed into `rotation` and
+/// `translation`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Instance {
+    pub model: u32,
+    /// The nSHP node that placed the model.
+    pub shape: u32,
+    pub rotation: Rotation,
+    pub translation: [i32; 3],
+}
+
+/// The nTRN/nGRP/nSHP hierarchy of a world file, keyed by node id. Files
+/// without scene chunks produce an empty graph.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneGraph {
+    pub nodes: BTreeMap<u32, SceneNode>,
+    pub layers: Vec<Layer>,
+    pub animation: Animation,
+}
+
+impl SceneGraph {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn root(&self) -> Option<&SceneNode> {
+        self.nodes.get(&0)
+    }
+
+    pub fn node(&self, id: u32) -> Option<&SceneNode> {
+        self.nodes.get(&id)
+    }
+
+    pub fn insert(&mut self, node: SceneNode) {
+        self.nodes.insert(node.id(), node);
+    }
+
+    pub fn layer(&self, id: i32) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.id as i32 == id)
+    }
+
+    pub fn is_layer_hidden(&self, id: i32) -> bool {
+        self.layer(id).is_some_and(|layer| layer.hidden)
+    }
+
+    /// Whether `node` is hidden itself or sits on a hidden layer.
+    pub fn is_hidden(&self, node: &TransformNode) -> bool {
+        node.hidden || self.is_layer_hidden(node.layer_id)
+    }
+
+    /// The first transform node, by id, whose `_name` is `name`.
+    pub fn find_node_by_name(&self, name: &str) -> Option<&TransformNode> {
+        self.nodes.values().find_map(|node| match node {
+            SceneNode::Transform(transform) if transform.name.as_deref() == Some(name) => Some(transform),
+            _ => None,
+        })
+    }
+
+    /// Model ids referenced by shapes that are not under a hidden transform
+    /// or one on a hidden layer, in traversal order.
+    pub fn visible_models(&self) -> Vec<u32> {
+        let mut models = Vec::new();
+        let mut stack = vec![0];
+        let mut visited = BTreeSet::new();
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            match self.nodes.get(&id) {
+                Some(SceneNode::Transform(node)) if !self.is_hidden(node) => {
+                    stack.push(node.child);
+                }
+                Some(SceneNode::Group(node)) => {
+                    stack.extend(node.children.iter().rev());
+                }
+                Some(SceneNode::Shape(node)) => {
+                    models.extend(node.models.iter());
+                }
+                _ => {}
+            }
+        }
+        models
+    }
+
+    /// The models shown at animation frame `frame`, skipping hidden nodes
+    /// and layers, in traversal order. Nodes without keys in `animation` use
+    /// their own transform or every model they list.
+    pub fn frame(&self, frame: u32) -> Vec<Instance> {
+        let mut instances = Vec::new();
+        let mut stack = vec![(0, Rotation::IDENTITY, [0; 3])];
+        let mut visited = BTreeSet::new();
+        while let Some((id, rotation, translation)) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            match self.nodes.get(&id) {
+                Some(SceneNode::Transform(node)) if !self.is_hidden(node) => {
+                    let keys = self.animation.transforms.get(&id).map_or(&[][..], |keys| &keys[..]);
+                    let (local_rotation, local_translation) = match key_at(keys, frame, |key| key.frame) {
+                        Some(key) => (key.rotation, key.translation),
+                        None => (node.rotation, node.translation),
+                    };
+                    let offset = rotation.apply(local_translation);
+                    let world = [0, 1, 2].map(|axis| translation[axis] + offset[axis]);
+                    let composed = multiply(rotation.to_matrix(), local_rotation.to_matrix());
+                    stack.push((node.child, Rotation::from_matrix(composed).unwrap_or(Rotation::IDENTITY), world));
+                }
+                Some(SceneNode::Group(node)) => {
+                    stack.extend(node.children.iter().rev().map(|&child| (child, rotation, translation)));
+                }
+                Some(SceneNode::Shape(node)) => {
+                    let models = match self.animation.shapes.get(&id) {
+                        Some(keys) => key_at(keys, frame, |key| key.frame).map(|key| key.model).into_iter().collect(),
+                        None => node.models.clone(),
+                    };
+                    for model in models {
+                        instances.push(Instance { model, shape: id, rotation, translation });
+                    }
+                }
+                _ => {}
+            }
+        }
+        instances
+    }
+}
+
+/// The product of two 3x3 integer matrices, `a` applied after `b`.
+pub(crate) fn multiply(a: [[i32; 3]; 3], b: [[i32; 3]; 3]) -> [[i32; 3]; 3] {
+    [0, 1, 2].map(|row| [0, 1, 2].map(|col| (0..3).map(|k| a[row][k] * b[k][col]).sum()))
+}
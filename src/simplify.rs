@@ -0,0 +1,199 @@
+use mesher::VoxMesh;
+
+/// Settings for `VoxMesh::simplify`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SimplifyOptions {
+    /// Largest difference in any color channel, alpha included, between
+    /// two faces that may still be merged, from 0 (identical colors only)
+    /// to 1 (any colors).
+    pub color_tolerance: f32,
+    /// Keep loosening the color tolerance, doubling it each round, until
+    /// the mesh has at most this many triangles or nothing more can merge.
+    pub target_triangles: Option<usize>,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> SimplifyOptions {
+        SimplifyOptions { color_tolerance: 0.0, target_triangles: None }
+    }
+}
+
+/// An axis-aligned face from the quad meshers, spanning `min` to `max` on
+/// the plane's two other axes.
+#[derive(Clone, Copy)]
+struct Rect {
+    axis: usize,
+    normal: [f32; 3],
+    plane: f32,
+    min: [f32; 2],
+    max: [f32; 2],
+    color: [f32; 4],
+    ao: Option<f32>,
+    material: Option<u8>,
+}
+
+impl Rect {
+    fn area(&self) -> f32 {
+        (self.max[0] - self.min[0]) * (self.max[1] - self.min[1])
+    }
+
+    fn same_plane(&self, other: &Rect) -> bool {
+        self.axis == other.axis && self.normal == other.normal && self.plane == other.plane && self.ao == other.ao && self.material == other.material
+    }
+}
+
+impl VoxMesh {
+    /// Merges coplanar faces that share a whole edge and whose colors lie
+    /// within the tolerance into larger rectangles, coloured by the
+    /// area-weighted average. Only meshes made of the axis-aligned quads
+    /// of `culled` and `greedy` are simplified; others, such as
+    /// `marching_cubes` output, come back unchanged. Quads with per-corner
+    /// occlusion shading are kept as they are, and faces only merge with
+    /// faces of the same `material_ids` entry.
+    pub fn simplify(&self, options: &SimplifyOptions) -> VoxMesh {
+        let mut tolerance = options.color_tolerance.max(0.0);
+        let mut mesh = self.merge_rects(tolerance);
+        if let Some(target) = options.target_triangles {
+            while mesh.triangle_count() > target && tolerance < 1.0 {
+                tolerance = (tolerance * 2.0).max(1.0 / 64.0);
+                mesh = mesh.merge_rects(tolerance);
+            }
+        }
+        mesh
+    }
+
+    fn merge_rects(&self, tolerance: f32) -> VoxMesh {
+        let count = self.positions.len();
+        if !count.is_multiple_of(4) || self.indices.len() != count / 4 * 6 || self.normals.len() != count || self.colors.len() != count {
+            return self.clone();
+        }
+        let has_ao = self.ao.len() == count;
+        let has_materials = self.material_ids.len() == count / 2;
+        let mut rects = Vec::new();
+        let mut kept = Vec::new();
+        for quad in 0..count / 4 {
+            match self.rect(quad, has_ao, has_materials) {
+                Some(Some(rect)) => rects.push(rect),
+                Some(None) => kept.push(quad),
+                None => return self.clone(),
+            }
+        }
+
+        loop {
+            let before = rects.len();
+            for along in 0..2 {
+                let across = 1 - along;
+                rects.sort_by(|a, b| {
+                    let key = |r: &Rect| {
+                        (r.axis, r.normal.map(f32::to_bits), r.plane.to_bits(), r.ao.map(f32::to_bits), r.material, r.min[across].to_bits(), r.max[across].to_bits())
+                    };
+                    key(a).cmp(&key(b)).then(a.min[along].total_cmp(&b.min[along]))
+                });
+                let mut merged: Vec<Rect> = Vec::with_capacity(rects.len());
+                for rect in rects.drain(..) {
+                    if let Some(last) = merged.last_mut() {
+                        let joins = last.same_plane(&rect)
+                            && last.min[across] == rect.min[across]
+                            && last.max[across] == rect.max[across]
+                            && last.max[along] == rect.min[along];
+                        let close = (0..4).all(|channel| (last.color[channel] - rect.color[channel]).abs() <= tolerance);
+                        if joins && close {
+                            let (a, b) = (last.area(), rect.area());
+                            last.color = [0, 1, 2, 3].map(|channel| (last.color[channel] * a + rect.color[channel] * b) / (a + b));
+                            last.max[along] = rect.max[along];
+                            continue;
+                        }
+                    }
+                    merged.push(rect);
+                }
+                rects = merged;
+            }
+            if rects.len() == before {
+                break;
+            }
+        }
+
+        let mut mesh = VoxMesh::default();
+        for quad in kept {
+            let base = mesh.positions.len() as u32;
+            let first = quad as u32 * 4;
+            let range = quad * 4..quad * 4 + 4;
+            mesh.positions.extend_from_slice(&self.positions[range.clone()]);
+            mesh.normals.extend_from_slice(&self.normals[range.clone()]);
+            mesh.uvs.extend_from_slice(&self.uvs[range.clone()]);
+            mesh.colors.extend_from_slice(&self.colors[range.clone()]);
+            if has_ao {
+                mesh.ao.extend_from_slice(&self.ao[range]);
+            }
+            if has_materials {
+                mesh.material_ids.extend_from_slice(&self.material_ids[quad * 2..quad * 2 + 2]);
+            }
+            mesh.indices.extend(self.indices[quad * 6..quad * 6 + 6].iter().map(|&index| index - first + base));
+        }
+        for rect in rects {
+            mesh.push_rect(&rect);
+        }
+        mesh
+    }
+
+    /// Quad `quad` as a rectangle, `Some(None)` if it is axis-aligned but
+    /// shaded per corner, or `None` if it is not an axis-aligned quad.
+    fn rect(&self, quad: usize, has_ao: bool, has_materials: bool) -> Option<Option<Rect>> {
+        let range = quad * 4..quad * 4 + 4;
+        let normal = self.normals[quad * 4];
+        let axis = (0..3).find(|&axis| normal[axis].abs() == 1.0)?;
+        if self.normals[range.clone()].iter().any(|&n| n != normal) || (0..3).any(|a| a != axis && normal[a] != 0.0) {
+            return None;
+        }
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let corners = &self.positions[range.clone()];
+        let plane = corners[0][axis];
+        let fold = |init: f32, pick: fn(f32, f32) -> f32, a: usize| corners.iter().fold(init, |m, p| pick(m, p[a]));
+        let min = [fold(f32::MAX, f32::min, u), fold(f32::MAX, f32::min, v)];
+        let max = [fold(f32::MIN, f32::max, u), fold(f32::MIN, f32::max, v)];
+        let on_corner = |p: &[f32; 3]| p[axis] == plane && (p[u] == min[0] || p[u] == max[0]) && (p[v] == min[1] || p[v] == max[1]);
+        if !corners.iter().all(on_corner) {
+            return None;
+        }
+        let color = self.colors[quad * 4];
+        let ao = if has_ao { Some(self.ao[quad * 4]) } else { None };
+        let material = if has_materials { Some(self.material_ids[quad * 2]) } else { None };
+        let uniform = self.colors[range.clone()].iter().all(|&c| c == color)
+            && (!has_ao || self.ao[range].iter().all(|&a| Some(a) == ao))
+            && (!has_materials || self.material_ids[quad * 2 + 1] == self.material_ids[quad * 2]);
+        Some(if uniform { Some(Rect { axis, normal, plane, min, max, color, ao, material }) } else { None })
+    }
+
+    /// Appends a rectangle as a quad wound like the greedy mesher's.
+    fn push_rect(&mut self, rect: &Rect) {
+        let (u, v) = ((rect.axis + 1) % 3, (rect.axis + 2) % 3);
+        let corner = |a: usize, b: usize| {
+            let mut p = [0.0; 3];
+            p[rect.axis] = rect.plane;
+            p[u] = if a == 0 { rect.min[0] } else { rect.max[0] };
+            p[v] = if b == 0 { rect.min[1] } else { rect.max[1] };
+            p
+        };
+        let (w, h) = (rect.max[0] - rect.min[0], rect.max[1] - rect.min[1]);
+        let corners = if rect.normal[rect.axis] > 0.0 {
+            [corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)]
+        } else {
+            [corner(0, 0), corner(0, 1), corner(1, 1), corner(1, 0)]
+        };
+        let uvs = [[0.0, 0.0], [w, 0.0], [w, h], [0.0, h]];
+        let base = self.positions.len() as u32;
+        for (corner, uv) in corners.iter().zip(uvs.iter()) {
+            self.positions.push(*corner);
+            self.normals.push(rect.normal);
+            self.uvs.push(*uv);
+            self.colors.push(rect.color);
+            if let Some(ao) = rect.ao {
+                self.ao.push(ao);
+            }
+        }
+        self.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        if let Some(material) = rect.material {
+            self.material_ids.extend_from_slice(&[material; 2]);
+        }
+    }
+}
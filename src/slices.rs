@@ -0,0 +1,193 @@
+#[cfg(feature = "image")]
+use std::fs;
+#[cfg(feature = "image")]
+use std::fs::File;
+#[cfg(feature = "image")]
+use std::io;
+#[cfg(feature = "image")]
+use std::io::{Read, Write};
+#[cfg(feature = "image")]
+use std::path::{Path, PathBuf};
+use model::Model;
+#[cfg(feature = "image")]
+use model::{Size, Voxel};
+use palette::Palette;
+#[cfg(feature = "image")]
+use palette::{median_cut, Color, PaletteBuilder};
+#[cfg(feature = "image")]
+use png;
+pub use transform::Axis;
+
+impl Axis {
+    /// Number of slices along the axis and the width and height of each.
+    pub fn slice_dimensions(self, model: &Model) -> (u32, u32, u32) {
+        let size = model.size;
+        match self {
+            Axis::X => (size.x, size.y, size.z),
+            Axis::Y => (size.y, size.x, size.z),
+            Axis::Z => (size.z, size.x, size.y),
+        }
+    }
+
+    /// Maps a pixel of slice `layer` back to model coordinates. Image rows
+    /// run top to bottom, so the vertical model axis is flipped.
+    pub fn to_model(self, model: &Model, layer: u32, column: u32, row: u32) -> (u32, u32, u32) {
+        let (_, _, height) = self.slice_dimensions(model);
+        let up = height - 1 - row;
+        match self {
+            Axis::X => (layer, column, up),
+            Axis::Y => (column, layer, up),
+            Axis::Z => (column, up, layer),
+        }
+    }
+}
+
+/// How `Model::from_image_stack` turns image colors into palette indices.
+#[cfg(feature = "image")]
+#[derive(Clone, PartialEq, Debug)]
+pub enum PaletteStrategy {
+    /// Gives each distinct color its own entry until the palette is full;
+    /// later colors take the closest entry.
+    FirstSeen,
+    /// Reduces all colors of the stack to 255 with a median cut.
+    MedianCut,
+    /// Maps every color onto the closest entry of an existing palette.
+    Fixed(Palette),
+}
+
+/// Renders slice `layer` along `axis` as RGBA pixels, with empty cells
+/// left transparent.
+pub fn slice_pixels(model: &Model, palette: &Palette, axis: Axis, layer: u32) -> Vec<u8> {
+    let (_, width, height) = axis.slice_dimensions(model);
+    let grid = model.to_dense_grid();
+    let mut pixels = vec![0; (width * height * 4) as usize];
+    for row in 0..height {
+        for column in 0..width {
+            let (x, y, z) = axis.to_model(model, layer, column, row);
+            let c = grid.get(x as i32, y as i32, z as i32);
+            if c != 0 {
+                let color = palette.get(c);
+                let offset = ((row * width + column) * 4) as usize;
+                pixels[offset..offset + 4].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        }
+    }
+    pixels
+}
+
+/// Encodes slice `layer` along `axis` as a PNG.
+#[cfg(feature = "image")]
+pub fn slice_png(model: &Model, palette: &Palette, axis: Axis, layer: u32) -> io::Result<Vec<u8>> {
+    let (_, width, height) = axis.slice_dimensions(model);
+    png::encode(width, height, &slice_pixels(model, palette, axis, layer))
+}
+
+/// Writes one PNG per layer along `axis` into `dir`, named `slice_000.png`
+/// upwards, and returns their paths in order. The directory is created if
+/// it does not exist.
+#[cfg(feature = "image")]
+pub fn export_png_slices<P: AsRef<Path>>(model: &Model, palette: &Palette, dir: P, axis: Axis) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(&dir)?;
+    let (count, _, _) = axis.slice_dimensions(model);
+    let mut paths = Vec::with_capacity(count as usize);
+    for layer in 0..count {
+        let path = dir.as_ref().join(format!("slice_{:03}.png", layer));
+        File::create(&path)?.write_all(&slice_png(model, palette, axis, layer)?)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// The character `Model::ascii_slices` draws a color index as.
+fn ascii_cell(c: u8) -> char {
+    match c {
+        0 => '.',
+        1..=9 => (b'0' + c) as char,
+        10..=35 => (b'a' + c - 10) as char,
+        36..=61 => (b'A' + c - 36) as char,
+        _ => '#',
+    }
+}
+
+impl Model {
+    /// Draws every layer along `axis` as text, laid out like the images of
+    /// `export_png_slices`, for comparing voxel layouts in tests and
+    /// terminals. Each layer starts with a line such as `z = 0`. Empty cells
+    /// are `.`, indices 1 to 9 their digit, 10 to 35 `a` to `z`, 36 to 61
+    /// `A` to `Z` and higher indices `#`.
+    pub fn ascii_slices(&self, axis: Axis) -> String {
+        let (count, width, height) = axis.slice_dimensions(self);
+        let name = match axis {
+            Axis::X => 'x',
+            Axis::Y => 'y',
+            Axis::Z => 'z',
+        };
+        let grid = self.to_dense_grid();
+        let mut text = String::new();
+        for layer in 0..count {
+            if layer > 0 {
+                text.push('\n');
+            }
+            text.push_str(&format!("{} = {}\n", name, layer));
+            for row in 0..height {
+                for column in 0..width {
+                    let (x, y, z) = axis.to_model(self, layer, column, row);
+                    text.push(ascii_cell(grid.get(x as i32, y as i32, z as i32)));
+                }
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Builds a model from a stack of PNG layers, the first image being the
+    /// bottom Z slice, as written by `export_png_slices` along `Axis::Z`.
+    /// Any PNG color type works, indexed images with transparency
+    /// included. All images must share one size; fully transparent pixels
+    /// are empty.
+    #[cfg(feature = "image")]
+    pub fn from_image_stack<P: AsRef<Path>>(paths: &[P], strategy: PaletteStrategy) -> io::Result<(Model, Palette)> {
+        let mut images = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut bytes = Vec::new();
+            File::open(path)?.read_to_end(&mut bytes)?;
+            images.push(png::decode(&bytes)?);
+        }
+        let (width, height) = images.first().map_or((0, 0), |&(width, height, _)| (width, height));
+        if images.iter().any(|&(w, h, _)| (w, h) != (width, height)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "images of the stack differ in size"));
+        }
+        let size = Size { x: width, y: height, z: images.len() as u32 };
+        if size.x > 256 || size.y > 256 || size.z > 256 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} exceeds 256 voxels per axis", size)));
+        }
+
+        let color_at = |pixels: &[u8], offset: usize| Color::new(pixels[offset], pixels[offset + 1], pixels[offset + 2], pixels[offset + 3]);
+        let mut palette = match strategy {
+            PaletteStrategy::FirstSeen => PaletteBuilder::new(),
+            PaletteStrategy::Fixed(ref palette) => PaletteBuilder::from_palette(palette),
+            PaletteStrategy::MedianCut => {
+                let solid: Vec<Color> = images
+                    .iter()
+                    .flat_map(|(_, _, pixels)| (0..pixels.len() / 4).map(move |i| color_at(pixels, i * 4)))
+                    .filter(|color| color.a != 0)
+                    .collect();
+                PaletteBuilder::with_colors(&median_cut(&solid, 255))
+            }
+        };
+        let mut model = Model::new(size);
+        for (layer, (_, _, pixels)) in images.iter().enumerate() {
+            for row in 0..height {
+                for column in 0..width {
+                    let color = color_at(pixels, ((row * width + column) * 4) as usize);
+                    if color.a == 0 {
+                        continue;
+                    }
+                    let (x, y, z) = Axis::Z.to_model(&model, layer as u32, column, row);
+                    model.voxels.push(Voxel { x: x as u8, y: y as u8, z: z as u8, c: palette.index_of(color) });
+                }
+            }
+        }
+        Ok((model, palette.finish()))
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette, PaletteBuilder};
+
+pub fn load_csv<P: AsRef<Path>>(path: P) -> io::Result<(Model, Palette)> {
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    read_csv(&text)
+}
+
+/// Parses a Sproxel CSV file: a `width,height,depth` line followed by one
+/// block of `#RRGGBBAA` rows per layer, top layer first. Sproxel is Y-up,
+/// so its layers become Z slices, mirrored along Y to keep the model
+/// right-handed. Cells with zero alpha are empty.
+pub fn read_csv(text: &str) -> io::Result<(Model, Palette)> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let header = lines.next().ok_or_else(|| invalid_data("empty CSV".to_string()))?;
+    let size: Vec<u32> = header
+        .split(',')
+        .map(|side| side.trim().parse().map_err(|_| invalid_data(format!("bad size line {:?}", header))))
+        .collect::<io::Result<_>>()?;
+    if size.len() != 3 || size.iter().any(|&side| side > 256) {
+        return Err(invalid_data(format!("bad size line {:?}", header)));
+    }
+    let (width, height, depth) = (size[0], size[1], size[2]);
+
+    let mut palette = PaletteBuilder::new();
+    let mut model = Model::new(Size { x: width, y: depth, z: height });
+    for row in 0..height * depth {
+        let line = lines.next().ok_or_else(|| invalid_data(format!("missing row {}", row)))?;
+        let (y, z) = (height - 1 - row / depth, row % depth);
+        let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+        if cells.len() != width as usize {
+            return Err(invalid_data(format!("row {} has {} cells instead of {}", row, cells.len(), width)));
+        }
+        for (x, cell) in cells.iter().enumerate() {
+            let color = parse_color(cell).ok_or_else(|| invalid_data(format!("bad color {:?}", cell)))?;
+            if color.a == 0 {
+                continue;
+            }
+            model.voxels.push(Voxel { x: x as u8, y: (depth - 1 - z) as u8, z: y as u8, c: palette.index_of(color) });
+        }
+    }
+    Ok((model, palette.finish()))
+}
+
+/// Formats `model` as Sproxel CSV, the inverse of `read_csv`.
+pub fn to_csv(model: &Model, palette: &Palette) -> String {
+    let grid = model.to_dense_grid();
+    let (width, height, depth) = (model.size.x, model.size.z, model.size.y);
+    let mut csv = format!("{},{},{}\n", width, height, depth);
+    for y in (0..height).rev() {
+        for z in 0..depth {
+            let cells: Vec<String> = (0..width)
+                .map(|x| match grid.get(x as i32, (depth - 1 - z) as i32, y as i32) {
+                    0 => "#00000000".to_string(),
+                    c => {
+                        let color = palette.get(c);
+                        format!("#{:02X}{:02X}{:02X}{:02X}", color.r, color.g, color.b, color.a)
+                    }
+                })
+                .collect();
+            writeln!(csv, "{}", cells.join(",")).unwrap();
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn export_csv<P: AsRef<Path>>(model: &Model, palette: &Palette, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(to_csv(model, palette).as_bytes())
+}
+
+fn parse_color(cell: &str) -> Option<Color> {
+    let hex = cell.strip_prefix('#')?;
+    if hex.len() != 8 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok();
+    Some(Color::new(channel(0)?, channel(1)?, channel(2)?, channel(3)?))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,57 @@
+use std::vec::Vec;
+use model::Model;
+use palette::Palette;
+use transform::Aabb;
+
+/// Size and color figures for a model, from `Model::stats`, for checking
+/// assets against budgets.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ModelStats {
+    /// Occupied cells; duplicate voxels at one coordinate count once.
+    pub voxel_count: usize,
+    /// `voxel_count` over the volume of the model, or 0 for an empty size.
+    pub occupancy: f64,
+    /// The box around the occupied cells, or `None` if there are none.
+    pub bounds: Option<Aabb>,
+    /// Occupied cells with at least one empty face neighbor.
+    pub surface_count: usize,
+    /// Occupied cells per color index.
+    pub color_counts: [usize; 256],
+}
+
+impl Model {
+    pub fn stats(&self) -> ModelStats {
+        let color_counts = self.color_histogram();
+        let voxel_count = color_counts.iter().sum();
+        let volume = self.size.x as u64 * self.size.y as u64 * self.size.z as u64;
+        ModelStats {
+            voxel_count,
+            occupancy: if volume == 0 { 0.0 } else { voxel_count as f64 / volume as f64 },
+            bounds: self.bounds(),
+            surface_count: self.surface_faces().len(),
+            color_counts,
+        }
+    }
+
+    /// How many occupied cells use each color index. Duplicate voxels at one
+    /// coordinate count once, under the color `voxel_at` reports.
+    pub fn color_histogram(&self) -> [usize; 256] {
+        let mut counts = [0; 256];
+        for voxel in &self.to_dense_grid().to_model().voxels {
+            counts[voxel.c as usize] += 1;
+        }
+        counts
+    }
+}
+
+impl Palette {
+    /// The entries from 1 to 255 that no voxel of `models` uses, in
+    /// ascending order; the slots a palette can be compacted by.
+    pub fn unused_indices(&self, models: &[Model]) -> Vec<u8> {
+        let mut used = [false; 256];
+        for voxel in models.iter().flat_map(|model| &model.voxels) {
+            used[voxel.c as usize] = true;
+        }
+        (1..=255u8).filter(|&index| !used[index as usize]).collect()
+    }
+}
@@ -0,0 +1,75 @@
+use mesher::VoxMesh;
+use mikktspace::{self, Geometry};
+
+/// The triangles of a mesh as MikkTSpace sees them, collecting the
+/// tangents it writes back per vertex.
+struct Triangles<'a> {
+    mesh: &'a VoxMesh,
+    tangents: Vec<Option<[f32; 4]>>,
+}
+
+impl<'a> Triangles<'a> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.mesh.indices[face * 3 + vert] as usize
+    }
+}
+
+impl<'a> Geometry for Triangles<'a> {
+    fn num_faces(&self) -> usize {
+        self.mesh.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.positions[self.vertex(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.normals[self.vertex(face, vert)]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.mesh.uvs[self.vertex(face, vert)]
+    }
+
+    fn set_tangent(&mut self, tangent_space: Option<mikktspace::TangentSpace>, face: usize, vert: usize) {
+        let vertex = self.vertex(face, vert);
+        if let Some(tangent_space) = tangent_space {
+            self.tangents[vertex] = Some(tangent_space.tangent_encoded());
+        }
+    }
+}
+
+impl VoxMesh {
+    /// Fills `tangents` with the MikkTSpace tangents of the mesh, the same
+    /// ones Blender, Unity and glTF viewers compute, so baked normal maps
+    /// line up. Generate them before `weld` rather than after: welding
+    /// keeps the vertices whose tangents differ, where computing tangents
+    /// on an already welded mesh would smooth across them. Vertices
+    /// MikkTSpace leaves without a tangent, on faces with no UV area, get
+    /// one perpendicular to their normal.
+    pub fn generate_tangents(&mut self) {
+        let mut triangles = Triangles { mesh: self, tangents: vec![None; self.positions.len()] };
+        // The error type has no variants; generation always succeeds.
+        let _ = mikktspace::generate_tangents(&mut triangles);
+        let tangents = triangles.tangents;
+        self.tangents = tangents
+            .into_iter()
+            .zip(&self.normals)
+            .map(|(tangent, normal)| tangent.unwrap_or_else(|| perpendicular(*normal)))
+            .collect();
+    }
+}
+
+/// A unit tangent at right angles to `normal`, along whichever of X or Y
+/// lies further from it.
+fn perpendicular(normal: [f32; 3]) -> [f32; 4] {
+    let axis = if normal[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    let along = axis[0] * normal[0] + axis[1] * normal[1] + axis[2] * normal[2];
+    let t = [axis[0] - normal[0] * along, axis[1] - normal[1] * along, axis[2] - normal[2] * along];
+    let length = (t[0] * t[0] + t[1] * t[1] + t[2] * t[2]).sqrt().max(1e-6);
+    [t[0] / length, t[1] / length, t[2] / length, 1.0]
+}
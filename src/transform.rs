@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::vec::Vec;
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette, PaletteBuilder};
+
+/// A model axis, for rotating, mirroring and slicing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// An axis-aligned box of cells, `min` inclusive and `max` exclusive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Aabb {
+    pub min: [u32; 3],
+    pub max: [u32; 3],
+}
+
+impl Aabb {
+    pub fn new(min: [u32; 3], max: [u32; 3]) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn size(&self) -> Size {
+        let side = |axis: usize| self.max[axis].saturating_sub(self.min[axis]);
+        Size { x: side(0), y: side(1), z: side(2) }
+    }
+
+    pub fn contains(&self, x: u32, y: u32, z: u32) -> bool {
+        (0..3).all(|axis| [x, y, z][axis] >= self.min[axis] && [x, y, z][axis] < self.max[axis])
+    }
+}
+
+/// What `Model::translated` does with voxels moved past the model bounds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overflow {
+    /// Keep the size and drop voxels that leave it.
+    Clip,
+    /// Grow the size to cover both the old and the moved extents, up to
+    /// 256 voxels per axis.
+    Grow,
+}
+
+/// Which part of a model `Model::resized` keeps in place.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Anchor {
+    /// The minimum corner; padding and trimming happen at the max sides.
+    Min,
+    /// The center of every axis, rounding toward the minimum side.
+    Center,
+    /// The maximum corner.
+    Max,
+    /// Centered in X and Y and resting on Z = 0, the usual footprint for
+    /// props.
+    BottomCenter,
+}
+
+/// How `Model::downsampled` picks the color of a merged block.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorPick {
+    /// The most common palette index, ties going to the lower index.
+    Majority,
+    /// The palette entry closest to the mean color of the block.
+    Average,
+}
+
+impl Model {
+    /// The tightest box around the voxels, or `None` for an empty model.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let first = self.voxels.first()?;
+        let mut aabb = Aabb::new([first.x as u32, first.y as u32, first.z as u32], [0; 3]);
+        for v in &self.voxels {
+            for (axis, &p) in [v.x as u32, v.y as u32, v.z as u32].iter().enumerate() {
+                aabb.min[axis] = aabb.min[axis].min(p);
+                aabb.max[axis] = aabb.max[axis].max(p + 1);
+            }
+        }
+        Some(aabb)
+    }
+
+    /// The model cropped to `bounds`, dropping empty border space. Its
+    /// origin moves to `bounds().min`; an empty model trims to size zero.
+    pub fn trimmed(&self) -> Model {
+        match self.bounds() {
+            Some(aabb) => self.crop(aabb),
+            None => Model::new(Size { x: 0, y: 0, z: 0 }),
+        }
+    }
+
+    /// Cuts out the cells inside `aabb`, clamped to the model, as a new
+    /// model whose origin is the box minimum.
+    pub fn crop(&self, aabb: Aabb) -> Model {
+        let max = [aabb.max[0].min(self.size.x), aabb.max[1].min(self.size.y), aabb.max[2].min(self.size.z)];
+        let aabb = Aabb::new([aabb.min[0].min(max[0]), aabb.min[1].min(max[1]), aabb.min[2].min(max[2])], max);
+        let voxels = self
+            .voxels
+            .iter()
+            .filter(|v| aabb.contains(v.x as u32, v.y as u32, v.z as u32))
+            .map(|v| Voxel {
+                x: (v.x as u32 - aabb.min[0]) as u8,
+                y: (v.y as u32 - aabb.min[1]) as u8,
+                z: (v.z as u32 - aabb.min[2]) as u8,
+                c: v.c,
+            })
+            .collect();
+        Model::from_voxels(aabb.size(), voxels)
+    }
+
+    /// Moves every voxel by the given offsets. With `Overflow::Grow` the
+    /// model frame expands to hold both the old and the moved extents, so
+    /// a negative offset leaves voxels in place and adds room above them.
+    pub fn translated(&self, dx: i32, dy: i32, dz: i32, overflow: Overflow) -> Model {
+        let offsets = [dx, dy, dz];
+        let sides = [self.size.x, self.size.y, self.size.z];
+        let mut size = sides;
+        let mut shift = offsets;
+        if overflow == Overflow::Grow {
+            for axis in 0..3 {
+                size[axis] = (sides[axis] as i64 + offsets[axis].unsigned_abs() as i64).min(256) as u32;
+                shift[axis] = offsets[axis].max(0);
+            }
+        }
+        let voxels = self
+            .voxels
+            .iter()
+            .filter_map(|v| {
+                let mut p = [0u8; 3];
+                for (axis, &coordinate) in [v.x, v.y, v.z].iter().enumerate() {
+                    let moved = coordinate as i64 + shift[axis] as i64;
+                    if moved < 0 || moved >= size[axis] as i64 {
+                        return None;
+                    }
+                    p[axis] = moved as u8;
+                }
+                Some(Voxel { x: p[0], y: p[1], z: p[2], c: v.c })
+            })
+            .collect();
+        Model::from_voxels(Size { x: size[0], y: size[1], z: size[2] }, voxels)
+    }
+
+    /// Rotates the model by `turns` quarter turns around `axis`,
+    /// counter-clockwise when looking down the positive axis. Negative turns
+    /// rotate the other way. The size is swapped to match and voxels stay
+    /// within `0..size`; voxels lying outside the size are dropped.
+    pub fn rotated_90(&self, axis: Axis, turns: i32) -> Model {
+        let mut model = self.clone();
+        for _ in 0..turns.rem_euclid(4) {
+            model = model.quarter_turn(axis);
+        }
+        model
+    }
+
+    /// Mirrors the model across the middle of `axis`, keeping its size.
+    /// Voxels lying outside the size are dropped.
+    pub fn flipped(&self, axis: Axis) -> Model {
+        let s = self.size;
+        let voxels = self
+            .voxels
+            .iter()
+            .filter_map(|&v| {
+                Some(match axis {
+                    Axis::X => Voxel { x: mirror(s.x, v.x)?, ..v },
+                    Axis::Y => Voxel { y: mirror(s.y, v.y)?, ..v },
+                    Axis::Z => Voxel { z: mirror(s.z, v.z)?, ..v },
+                })
+            })
+            .collect();
+        Model::from_voxels(s, voxels)
+    }
+
+    /// Pads or trims the model to `size`, keeping `anchor` fixed. Voxels
+    /// that fall outside the new size are dropped.
+    pub fn resized(&self, size: Size, anchor: Anchor) -> Model {
+        let old = [self.size.x as i64, self.size.y as i64, self.size.z as i64];
+        let new = [size.x as i64, size.y as i64, size.z as i64];
+        let mut offsets = [0i64; 3];
+        for axis in 0..3 {
+            let difference = new[axis] - old[axis];
+            offsets[axis] = match anchor {
+                Anchor::Min => 0,
+                Anchor::Max => difference,
+                Anchor::BottomCenter if axis == 2 => 0,
+                Anchor::Center | Anchor::BottomCenter => difference.div_euclid(2),
+            };
+        }
+        let voxels = self
+            .voxels
+            .iter()
+            .filter_map(|v| {
+                let mut p = [0u8; 3];
+                for (axis, &coordinate) in [v.x, v.y, v.z].iter().enumerate() {
+                    let moved = coordinate as i64 + offsets[axis];
+                    if moved < 0 || moved >= new[axis] || moved > 255 {
+                        return None;
+                    }
+                    p[axis] = moved as u8;
+                }
+                Some(Voxel { x: p[0], y: p[1], z: p[2], c: v.c })
+            })
+            .collect();
+        Model::from_voxels(size, voxels)
+    }
+
+    /// Replaces every voxel with a `factor`³ block of the same color. The
+    /// size is capped at 256 per axis and blocks past it are cut off.
+    pub fn upscaled(&self, factor: u32) -> Model {
+        let factor = factor.max(1);
+        let scale = |side: u32| side.saturating_mul(factor).min(256);
+        let size = Size { x: scale(self.size.x), y: scale(self.size.y), z: scale(self.size.z) };
+        let mut model = Model::new(size);
+        for v in &self.voxels {
+            let origin = [v.x as u32, v.y as u32, v.z as u32].map(|p| p.saturating_mul(factor));
+            let end = |axis: usize, side: u32| origin[axis].saturating_add(factor).min(side);
+            for z in origin[2]..end(2, size.z) {
+                for y in origin[1]..end(1, size.y) {
+                    for x in origin[0]..end(0, size.x) {
+                        model.voxels.push(Voxel { x: x as u8, y: y as u8, z: z as u8, c: v.c });
+                    }
+                }
+            }
+        }
+        model
+    }
+
+    /// Merges every `factor`³ block into one voxel, for lower levels of
+    /// detail. Blocks with any voxel stay solid; see `downsampled_with` for
+    /// a higher threshold.
+    pub fn downsampled(&self, factor: u32, pick: ColorPick, palette: &Palette) -> Model {
+        self.downsampled_with(factor, pick, palette, 1)
+    }
+
+    /// Like `downsampled`, but a block only becomes solid when it holds at
+    /// least `min_voxels` voxels.
+    pub fn downsampled_with(&self, factor: u32, pick: ColorPick, palette: &Palette, min_voxels: u32) -> Model {
+        let factor = factor.max(1);
+        let shrink = |side: u32| side.div_ceil(factor);
+        let size = Size { x: shrink(self.size.x), y: shrink(self.size.y), z: shrink(self.size.z) };
+        let mut blocks: BTreeMap<(u8, u8, u8), Vec<u8>> = BTreeMap::new();
+        for (x, y, z, c) in self.to_dense_grid().to_model().iter() {
+            let key = ((x as u32 / factor) as u8, (y as u32 / factor) as u8, (z as u32 / factor) as u8);
+            blocks.entry(key).or_default().push(c);
+        }
+        let mut colors = PaletteBuilder::from_palette(palette);
+        let mut model = Model::new(size);
+        for ((x, y, z), indices) in blocks {
+            if (indices.len() as u32) < min_voxels.max(1) {
+                continue;
+            }
+            let c = match pick {
+                ColorPick::Majority => {
+                    let mut counts = [0u32; 256];
+                    for &index in &indices {
+                        counts[index as usize] += 1;
+                    }
+                    (1..=255u8).max_by_key(|&index| (counts[index as usize], 255 - index)).unwrap()
+                }
+                ColorPick::Average => {
+                    let mut sums = [0u32; 4];
+                    for &index in &indices {
+                        let color = palette.get(index);
+                        for (sum, channel) in sums.iter_mut().zip([color.r, color.g, color.b, color.a].iter()) {
+                            *sum += *channel as u32;
+                        }
+                    }
+                    let mean = |sum: u32| (sum / indices.len() as u32) as u8;
+                    colors.index_of(Color::new(mean(sums[0]), mean(sums[1]), mean(sums[2]), mean(sums[3])))
+                }
+            };
+            model.voxels.push(Voxel { x, y, z, c });
+        }
+        model
+    }
+
+    fn quarter_turn(&self, axis: Axis) -> Model {
+        let s = self.size;
+        let size = match axis {
+            Axis::X => Size { x: s.x, y: s.z, z: s.y },
+            Axis::Y => Size { x: s.z, y: s.y, z: s.x },
+            Axis::Z => Size { x: s.y, y: s.x, z: s.z },
+        };
+        let voxels = self
+            .voxels
+            .iter()
+            .filter_map(|v| {
+                let (x, y, z) = match axis {
+                    Axis::X => (v.x, mirror(s.z, v.z)?, v.y),
+                    Axis::Y => (v.z, v.y, mirror(s.x, v.x)?),
+                    Axis::Z => (mirror(s.y, v.y)?, v.x, v.z),
+                };
+                Some(Voxel { x, y, z, c: v.c })
+            })
+            .collect();
+        Model::from_voxels(size, voxels)
+    }
+}
+
+/// `coordinate` counted from the far end of a side of length `side`, or
+/// `None` if it lies outside the side or the result does not fit a voxel
+/// coordinate.
+pub(crate) fn mirror(side: u32, coordinate: u8) -> Option<u8> {
+    side.checked_sub(coordinate as u32 + 1).filter(|&mirrored| mirrored <= 255).map(|mirrored| mirrored as u8)
+}
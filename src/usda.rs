@@ -0,0 +1,183 @@
+//! USD export as `.usda` text, behind the `usd` feature (`usda` is an
+//! alias). Only the text encoding is written, not binary `.usdc`; `usdcat`
+//! converts the text stage when a pipeline needs it.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use coords::CoordinateSystem;
+use mesher::{self, VoxMesh};
+use model::Model;
+use palette::Palette;
+use scene::{SceneGraph, SceneNode};
+use vox_loader::VoxLoader;
+
+/// Writes the loaded file as a USDA stage. Each model becomes one mesh
+/// under the abstract `/Prototypes` prim, and the scene graph becomes a
+/// hierarchy of `Xform` prims under `/Root` that reference those meshes as
+/// instances. Hidden nodes and layers are kept, marked invisible. Files
+/// without a scene get one instance per model at its own coordinates.
+pub fn export_usda<P: AsRef<Path>>(vl: &VoxLoader, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(to_usda(vl).as_bytes())
+}
+
+pub fn to_usda(vl: &VoxLoader) -> String {
+    let scene = vl.scene();
+    let centered = !scene.is_empty();
+    let mut stage = Stage { usda: String::new() };
+    let up_axis = match vl.coordinate_system() {
+        CoordinateSystem::ZUpRightHanded => "Z",
+        CoordinateSystem::YUpRightHanded | CoordinateSystem::YUpLeftHanded => "Y",
+    };
+    writeln!(stage.usda, "#usda 1.0\n(\n    defaultPrim = \"Root\"\n    upAxis = \"{}\"\n)\n", up_axis).unwrap();
+
+    writeln!(stage.usda, "class \"Prototypes\"\n{{").unwrap();
+    let left_handed = vl.coordinate_system() == CoordinateSystem::YUpLeftHanded;
+    for (id, model) in vl.models().iter().enumerate() {
+        let pivot = if centered { [(model.size.x / 2) as f32, (model.size.y / 2) as f32, (model.size.z / 2) as f32] } else { [0.0; 3] };
+        stage.mesh(&format!("model_{}", id), model, &vl.palette, pivot, left_handed, 1);
+    }
+    writeln!(stage.usda, "}}\n").unwrap();
+
+    writeln!(stage.usda, "def Xform \"Root\"\n{{").unwrap();
+    let mut names = BTreeSet::new();
+    if centered {
+        stage.scene_node(scene, vl.models().len(), 0, 1, &mut names, 0);
+    } else {
+        for id in 0..vl.models().len() {
+            stage.instance(id as u32, 1, &mut names);
+        }
+    }
+    writeln!(stage.usda, "}}").unwrap();
+    stage.usda
+}
+
+/// Writes a single model, without any scene transform, as a USDA stage
+/// whose default prim is the model's mesh.
+pub fn model_to_usda(model: &Model, palette: &Palette) -> String {
+    let mut stage = Stage { usda: String::new() };
+    writeln!(stage.usda, "#usda 1.0\n(\n    defaultPrim = \"model\"\n    upAxis = \"Z\"\n)\n").unwrap();
+    stage.mesh("model", model, palette, [0.0; 3], false, 0);
+    stage.usda
+}
+
+struct Stage {
+    usda: String,
+}
+
+impl Stage {
+    fn line(&mut self, depth: usize, text: &str) {
+        writeln!(self.usda, "{}{}", "    ".repeat(depth), text).unwrap();
+    }
+
+    fn mesh(&mut self, name: &str, model: &Model, palette: &Palette, pivot: [f32; 3], left_handed: bool, depth: usize) {
+        let mesh = mesher::greedy(model, palette);
+        self.line(depth, &format!("def Mesh \"{}\"", name));
+        self.line(depth, "{");
+        let inner = depth + 1;
+        if left_handed {
+            self.line(inner, "uniform token orientation = \"leftHanded\"");
+        }
+        let points: Vec<[f32; 3]> = mesh.positions.iter().map(|p| [p[0] - pivot[0], p[1] - pivot[1], p[2] - pivot[2]]).collect();
+        self.line(inner, &format!("point3f[] points = [{}]", vectors(&points)));
+        self.line(inner, &format!("normal3f[] normals = [{}] (\n{}    interpolation = \"vertex\"\n{})", vectors(&mesh.normals), "    ".repeat(inner), "    ".repeat(inner)));
+        self.line(inner, &format!("int[] faceVertexCounts = [{}]", vec!["3"; mesh.triangle_count()].join(", ")));
+        self.line(inner, &format!("int[] faceVertexIndices = [{}]", join(mesh.indices.iter())));
+        self.line(inner, "uniform token subdivisionScheme = \"none\"");
+        self.display_primvars(&mesh, inner);
+        self.line(depth, "}");
+    }
+
+    /// Vertex colors as `displayColor`, and `displayOpacity` when any of
+    /// them is see-through.
+    fn display_primvars(&mut self, mesh: &VoxMesh, depth: usize) {
+        let indent = "    ".repeat(depth);
+        let colors: Vec<[f32; 3]> = mesh.colors.iter().map(|c| [c[0], c[1], c[2]]).collect();
+        self.line(depth, &format!("color3f[] primvars:displayColor = [{}] (\n{}    interpolation = \"vertex\"\n{})", vectors(&colors), indent, indent));
+        if mesh.colors.iter().any(|c| c[3] < 1.0) {
+            let opacity = join(mesh.colors.iter().map(|c| c[3]));
+            self.line(depth, &format!("float[] primvars:displayOpacity = [{}] (\n{}    interpolation = \"vertex\"\n{})", opacity, indent, indent));
+        }
+    }
+
+    fn scene_node(&mut self, scene: &SceneGraph, model_count: usize, id: u32, depth: usize, names: &mut BTreeSet<String>, guard: usize) {
+        if guard > scene.nodes.len() {
+            return;
+        }
+        match scene.node(id) {
+            Some(SceneNode::Transform(node)) => {
+                let name = unique_name(names, node.name.as_deref().unwrap_or(""), &format!("node_{}", node.id));
+                let r = node.rotation.to_matrix();
+                let t = node.translation;
+                self.line(depth, &format!("def Xform \"{}\"", name));
+                self.line(depth, "{");
+                let inner = depth + 1;
+                self.line(
+                    inner,
+                    &format!(
+                        "matrix4d xformOp:transform = ( ({}, {}, {}, 0), ({}, {}, {}, 0), ({}, {}, {}, 0), ({}, {}, {}, 1) )",
+                        r[0][0], r[1][0], r[2][0], r[0][1], r[1][1], r[2][1], r[0][2], r[1][2], r[2][2], t[0], t[1], t[2]
+                    ),
+                );
+                self.line(inner, "uniform token[] xformOpOrder = [\"xformOp:transform\"]");
+                if scene.is_hidden(node) {
+                    self.line(inner, "token visibility = \"invisible\"");
+                }
+                self.scene_node(scene, model_count, node.child, inner, &mut BTreeSet::new(), guard + 1);
+                self.line(depth, "}");
+            }
+            Some(SceneNode::Group(node)) => {
+                for &child in &node.children {
+                    self.scene_node(scene, model_count, child, depth, names, guard + 1);
+                }
+            }
+            Some(SceneNode::Shape(node)) => {
+                for &model in node.models.iter().filter(|&&model| (model as usize) < model_count) {
+                    self.instance(model, depth, names);
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn instance(&mut self, model: u32, depth: usize, names: &mut BTreeSet<String>) {
+        let name = unique_name(names, "", &format!("model_{}", model));
+        self.line(depth, &format!("def \"{}\" (", name));
+        self.line(depth + 1, "instanceable = true");
+        self.line(depth + 1, &format!("prepend references = </Prototypes/model_{}>", model));
+        self.line(depth, ")");
+        self.line(depth, "{");
+        self.line(depth, "}");
+    }
+}
+
+/// `name` made into a valid prim name, or `fallback` when it is empty,
+/// with a numeric suffix if a sibling already took it.
+fn unique_name(taken: &mut BTreeSet<String>, name: &str, fallback: &str) -> String {
+    let mut base: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    if base.is_empty() {
+        base = fallback.to_string();
+    }
+    if base.starts_with(|c: char| c.is_ascii_digit()) {
+        base.insert(0, '_');
+    }
+    let mut name = base.clone();
+    let mut suffix = 1;
+    while taken.contains(&name) {
+        name = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    taken.insert(name.clone());
+    name
+}
+
+fn vectors<const N: usize>(values: &[[f32; N]]) -> String {
+    values.iter().map(|v| format!("({})", join(v.iter()))).collect::<Vec<_>>().join(", ")
+}
+
+fn join<T: ToString, I: Iterator<Item = T>>(values: I) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt};
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette};
+
+/// Where one model's voxels live in the file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ModelEntry {
+    pub size: Size,
+    /// Offset of the first voxel of the XYZI chunk, or `None` if the model
+    /// has no voxels.
+    offset: Option<u64>,
+    /// Bytes of voxel data the XYZI chunk holds within the file.
+    data_length: u64,
+    pub voxel_count: u32,
+}
+
+/// A `.vox` file whose chunk headers have been scanned but whose voxel data
+/// is only read on demand, for files with many models where only a few are
+/// needed up front. Only SIZE, XYZI and RGBA chunks are read; use
+/// `VoxLoader` for materials and the scene graph.
+pub struct VoxIndex<R> {
+    reader: R,
+    version: u32,
+    models: Vec<ModelEntry>,
+    pub palette: Palette,
+}
+
+impl VoxIndex<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<VoxIndex<BufReader<File>>> {
+        VoxIndex::from_reader(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> VoxIndex<R> {
+    /// Scans the chunk headers of `reader`, skipping over everything but
+    /// model sizes, voxel counts and the palette.
+    pub fn from_reader(mut reader: R) -> io::Result<VoxIndex<R>> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != b"VOX " {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected file magic {:?}", magic)));
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(8))?;
+
+        let mut index = VoxIndex { reader, version, models: Vec::new(), palette: Palette::magica_voxel_default() };
+        let mut position = 8;
+        while position + 12 <= end {
+            let mut id = [0; 4];
+            index.reader.read_exact(&mut id)?;
+            let length = index.reader.read_u32::<LittleEndian>()? as u64;
+            let child_length = index.reader.read_u32::<LittleEndian>()? as u64;
+            let content = position + 12;
+            // MAIN wraps every other chunk, so step into its children.
+            let next = if &id == b"MAIN" { content + length } else { content + length + child_length };
+            match &id {
+                b"SIZE" => {
+                    let size = Size {
+                        x: index.reader.read_u32::<LittleEndian>()?,
+                        y: index.reader.read_u32::<LittleEndian>()?,
+                        z: index.reader.read_u32::<LittleEndian>()?,
+                    };
+                    index.models.push(ModelEntry { size, offset: None, data_length: 0, voxel_count: 0 });
+                }
+                b"XYZI" => {
+                    let voxel_count = index.reader.read_u32::<LittleEndian>()?;
+                    if index.models.last().is_none_or(|entry| entry.offset.is_some()) {
+                        index.models.push(ModelEntry { size: Size { x: 0, y: 0, z: 0 }, offset: None, data_length: 0, voxel_count: 0 });
+                    }
+                    let entry = index.models.last_mut().unwrap();
+                    entry.offset = Some(content + 4);
+                    entry.data_length = length.saturating_sub(4).min(end.saturating_sub(content + 4));
+                    entry.voxel_count = voxel_count;
+                }
+                b"RGBA" => {
+                    let mut entries = Vec::with_capacity(256);
+                    for _ in 0..256 {
+                        let mut rgba = [0; 4];
+                        index.reader.read_exact(&mut rgba)?;
+                        entries.push(Color::new(rgba[0], rgba[1], rgba[2], rgba[3]));
+                    }
+                    index.palette = Palette::from_rgba_chunk(&entries);
+                }
+                _ => {}
+            }
+            position = next;
+            index.reader.seek(SeekFrom::Start(position))?;
+        }
+        Ok(index)
+    }
+
+    /// The raw version number from the file header.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    pub fn models(&self) -> &[ModelEntry] {
+        &self.models
+    }
+
+    /// Reads the voxels of model `i`. A voxel count that does not fit in
+    /// its XYZI chunk is `InvalidData`.
+    pub fn load_model(&mut self, i: usize) -> io::Result<Model> {
+        let entry = *self
+            .models
+            .get(i)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no model {} in a file of {}", i, self.models.len())))?;
+        let mut model = Model::new(entry.size);
+        if let Some(offset) = entry.offset {
+            if entry.voxel_count as u64 * 4 > entry.data_length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("model {} claims {} voxels in {} bytes", i, entry.voxel_count, entry.data_length),
+                ));
+            }
+            self.reader.seek(SeekFrom::Start(offset))?;
+            let mut data = vec![0; entry.voxel_count as usize * 4];
+            self.reader.read_exact(&mut data)?;
+            model.voxels = data.chunks(4).map(|v| Voxel { x: v[0], y: v[1], z: v[2], c: v[3] }).collect();
+        }
+        Ok(model)
+    }
+}
@@ -1,46 +1,144 @@
 
+use std::collections::BTreeMap;
 use std::fmt;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 use std::fs::File;
+#[cfg(all(feature = "mmap", unix))]
+use std::io;
+#[cfg(feature = "std")]
 use std::io::Read;
-use std::error::Error;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 use std::path::Path;
-use std::io::Cursor;
-use byteorder::{LittleEndian, BigEndian, ReadBytesExt};
-
-pub struct Voxel {
-    pub x: u8,
-    pub y: u8,
-    pub z: u8,
-    pub c: u8
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+use std::ops::Range;
+use std::string::String;
+use std::vec::Vec;
+use chunk::RawChunk;
+use coords::CoordinateSystem;
+use dict::Dict;
+use material::Material;
+#[cfg(all(feature = "mmap", unix))]
+use mmap::Mmap;
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use render_settings::{Camera, RenderObject};
+use scene::{GroupNode, Layer, ModelKey, Rotation, SceneGraph, SceneNode, ShapeNode, TransformKey, TransformNode};
+
+/// The header version, which decides how version-dependent chunks are read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FormatVersion {
+    /// MagicaVoxel 0.98 and the early 0.99 releases. Legacy MATT chunks are
+    /// honoured.
+    V150,
+    /// MagicaVoxel 0.99.7 and later. Materials come only from MATL.
+    V200,
+    /// Any other version. Parsed with the rules of the closest known version.
+    Unknown(u32),
 }
-impl fmt::Debug for Voxel {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Voxel {{ x: {}, y: {} z: {}, c: {} }}", self.x, self.y, self.z, self.c)
+
+impl FormatVersion {
+    pub fn from_raw(version: u32) -> FormatVersion {
+        match version {
+            150 => FormatVersion::V150,
+            200 => FormatVersion::V200,
+            other => FormatVersion::Unknown(other),
+        }
+    }
+
+    pub fn raw(self) -> u32 {
+        match self {
+            FormatVersion::V150 => 150,
+            FormatVersion::V200 => 200,
+            FormatVersion::Unknown(version) => version,
+        }
+    }
+
+    pub fn reads_legacy_materials(self) -> bool {
+        self.raw() < 200
     }
 }
 
-pub struct Size {
-    pub x: u32,
-    pub y: u32,
-    pub z: u32
+/// Receives updates while a loader parses, e.g. to drive a progress bar
+/// for large world exports. Both methods do nothing by default.
+pub trait Progress {
+    /// Called after each chunk with the bytes parsed so far and the file
+    /// size.
+    fn on_progress(&mut self, _bytes_read: usize, _total: usize) {}
+
+    /// Called with the four-character id of each chunk as it is reached.
+    fn on_chunk(&mut self, _id: &str) {}
+}
+
+impl Progress for () {}
+
+/// How `VoxLoader::from_bytes_with_options` treats malformed data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ParseOptions {
+    /// Fail on the first unknown chunk, bad size or out-of-range voxel
+    /// instead of recovering what can be read and recording a warning.
+    pub strict: bool,
+    /// The axis convention models and scene transforms are converted into
+    /// after parsing. `VoxWriter` converts them back when saving.
+    pub target_coords: CoordinateSystem,
 }
-impl fmt::Debug for Size {
+
+/// Why a strict parse failed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Size {{ x: {}, y: {} z: {}}}", self.x, self.y, self.z)
+        write!(f, "malformed vox data: {}", self.message)
     }
 }
 
+#[cfg(feature = "std")]
+impl ::std::error::Error for ParseError {}
+
 pub struct VoxLoader {
-    filepath: &'static str,
+    #[cfg(feature = "std")]
+    filepath: Option<PathBuf>,
     data: Vec<u8>,
+    #[cfg(all(feature = "mmap", unix))]
+    mapped: Option<Mmap>,
     offset: usize,
-    pub size: Size,
-    pub voxels: Vec<Vec<Voxel>>,
-    pub palette: Vec<u32>,
+    /// XYZI data ranges waiting to be decoded, by model index.
+    pending_voxels: Vec<(usize, Range<usize>)>,
+    version: u32,
+    options: ParseOptions,
+    error: Option<ParseError>,
+    /// Set when a read ran past the end of the data.
+    overrun: bool,
+    truncated_at: Option<usize>,
+    /// Number of models whose XYZI chunk has been read.
+    complete_models: usize,
+    warnings: Vec<String>,
+    models: Vec<Model>,
+    scene: SceneGraph,
+    materials: BTreeMap<u32, Material>,
+    cameras: Vec<Camera>,
+    render_objects: Vec<RenderObject>,
+    index_map: Option<Vec<u8>>,
+    palette_notes: Vec<String>,
+    unknown_chunks: Vec<RawChunk>,
+    /// How many chunks of each known id have been read, and the last one,
+    /// to anchor unknown chunks.
+    chunk_counts: BTreeMap<String, usize>,
+    last_known: Option<(String, usize)>,
+    pub palette: Palette,
 }
 impl fmt::Debug for VoxLoader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "VoxLoader {{ filepath: {:?}, data: {:?}, offset: {:?}, size: {:?}, voxels: {:?}, palette: {:?} }}", self.filepath, self.data, self.offset, self.size, self.voxels, self.palette)
+        #[cfg(feature = "std")]
+        write!(f, "VoxLoader {{ filepath: {:?}, ", self.filepath)?;
+        #[cfg(not(feature = "std"))]
+        write!(f, "VoxLoader {{ ")?;
+        write!(f, "data: {:?}, offset: {:?}, version: {:?}, warnings: {:?}, models: {:?}, scene: {:?}, materials: {:?}, cameras: {:?}, render_objects: {:?}, index_map: {:?}, palette_notes: {:?}, palette: {:?} }}", self.data, self.offset, self.version, self.warnings, self.models, self.scene, self.materials, self.cameras, self.render_objects, self.index_map, self.palette_notes, self.palette)
     }
 }
 
@@ -52,109 +150,591 @@ struct Chunk {
 }
 
 impl VoxLoader {
-    pub fn new(fp: &'static str) -> VoxLoader {
-        let mut vl = VoxLoader {
-            filepath: fp,
+    /// Reads and parses the file at `fp`. Not available on wasm32, which
+    /// has no file system; use `from_bytes` there.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn new<P: AsRef<Path>>(fp: P) -> VoxLoader {
+        let mut vl = VoxLoader::empty();
+        vl.filepath = Some(fp.as_ref().to_path_buf());
+        vl.load();
+        vl.parse(&mut ());
+        vl
+    }
+
+    /// Like `new`, reporting parse progress to `progress`.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn with_progress<P: AsRef<Path>>(fp: P, progress: &mut dyn Progress) -> VoxLoader {
+        let mut vl = VoxLoader::empty();
+        vl.filepath = Some(fp.as_ref().to_path_buf());
+        vl.load();
+        vl.parse(progress);
+        vl
+    }
+
+    /// Parses a file already in memory. This is the one constructor
+    /// available without the `std` feature.
+    pub fn from_bytes(bytes: &[u8]) -> VoxLoader {
+        VoxLoader::from_bytes_with_progress(bytes, &mut ())
+    }
+
+    /// Like `from_bytes`, reporting parse progress to `progress`.
+    pub fn from_bytes_with_progress(bytes: &[u8], progress: &mut dyn Progress) -> VoxLoader {
+        let mut vl = VoxLoader::empty();
+        vl.data.extend_from_slice(bytes);
+        vl.parse(progress);
+        vl
+    }
+
+    /// Parses with explicit `options`. In strict mode the first problem is
+    /// returned as an error; in lenient mode, which `from_bytes` uses, it
+    /// is recorded in `warnings` and parsing carries on.
+    pub fn from_bytes_with_options(bytes: &[u8], options: &ParseOptions) -> Result<VoxLoader, ParseError> {
+        let mut vl = VoxLoader::empty();
+        vl.options = *options;
+        vl.data.extend_from_slice(bytes);
+        vl.parse(&mut ());
+        match vl.error.take() {
+            Some(error) => Err(error),
+            None => Ok(vl),
+        }
+    }
+
+    /// A loader holding `models` and `palette` and nothing else, as if read
+    /// from a version 150 file, for handing converted or generated models
+    /// to `VoxWriter` and the exporters.
+    pub fn from_models(models: Vec<Model>, palette: Palette) -> VoxLoader {
+        let mut vl = VoxLoader::empty();
+        vl.version = 150;
+        vl.models = models;
+        vl.palette = palette;
+        vl
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: Read>(mut reader: R) -> VoxLoader {
+        let mut vl = VoxLoader::empty();
+        if let Err(why) = reader.read_to_end(&mut vl.data) {
+            panic!("couldn't read vox data: {}", why);
+        }
+        vl.parse(&mut ());
+        vl
+    }
+
+    /// Parses a file through a read-only memory mapping instead of copying
+    /// it into memory first, which keeps peak memory low for large world
+    /// exports. The mapping lives as long as the loader.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified, by this or any other
+    /// process, while the loader is alive. The loader reads the mapped
+    /// pages directly, so a shrunk file faults with `SIGBUS` and a
+    /// rewritten one changes bytes under a shared slice, which is
+    /// undefined behavior.
+    #[cfg(all(feature = "mmap", unix))]
+    pub unsafe fn from_mmap<P: AsRef<Path>>(fp: P) -> io::Result<VoxLoader> {
+        let mut vl = VoxLoader::empty();
+        vl.filepath = Some(fp.as_ref().to_path_buf());
+        vl.mapped = Some(Mmap::open(fp)?);
+        vl.parse(&mut ());
+        Ok(vl)
+    }
+
+    fn empty() -> VoxLoader {
+        VoxLoader {
+            #[cfg(feature = "std")]
+            filepath: None,
             data: Vec::new(),
+            #[cfg(all(feature = "mmap", unix))]
+            mapped: None,
             offset: 0,
-            size: Size { x: 0, y: 0, z: 0 },
-            voxels: Vec::new(),
-            palette: Vec::new(),
-        };
-        vl.load();
-        if vl.palette.len() != 256 {
-            vl.palette = vec![0x0, 0xffffffff, 0xffffccff, 0xffff99ff, 0xffff66ff, 0xffff33ff, 0xffff00ff, 0xffccffff, 0xffccccff, 0xffcc99ff, 0xffcc66ff, 0xffcc33ff, 0xffcc00ff, 0xff99ffff, 0xff99ccff, 0xff9999ff, 0xff9966ff, 0xff9933ff, 0xff9900ff, 0xff66ffff, 0xff66ccff, 0xff6699ff, 0xff6666ff, 0xff6633ff, 0xff6600ff, 0xff33ffff, 0xff33ccff, 0xff3399ff, 0xff3366ff, 0xff3333ff, 0xff3300ff, 0xff00ffff, 0xff00ccff, 0xff0099ff, 0xff0066ff, 0xff0033ff, 0xff0000ff, 0xccffffff, 0xccffccff, 0xccff99ff, 0xccff66ff, 0xccff33ff, 0xccff00ff, 0xccccffff, 0xccccccff, 0xcccc99ff, 0xcccc66ff, 0xcccc33ff, 0xcccc00ff, 0xcc99ffff, 0xcc99ccff, 0xcc9999ff, 0xcc9966ff, 0xcc9933ff, 0xcc9900ff, 0xcc66ffff, 0xcc66ccff, 0xcc6699ff, 0xcc6666ff, 0xcc6633ff, 0xcc6600ff, 0xcc33ffff, 0xcc33ccff, 0xcc3399ff, 0xcc3366ff, 0xcc3333ff, 0xcc3300ff, 0xcc00ffff, 0xcc00ccff, 0xcc0099ff, 0xcc0066ff, 0xcc0033ff, 0xcc0000ff, 0x99ffffff, 0x99ffccff, 0x99ff99ff, 0x99ff66ff, 0x99ff33ff, 0x99ff00ff, 0x99ccffff, 0x99ccccff, 0x99cc99ff, 0x99cc66ff, 0x99cc33ff, 0x99cc00ff, 0x9999ffff, 0x9999ccff, 0x999999ff, 0x999966ff, 0x999933ff, 0x999900ff, 0x9966ffff, 0x9966ccff, 0x996699ff, 0x996666ff, 0x996633ff, 0x996600ff, 0x9933ffff, 0x9933ccff, 0x993399ff, 0x993366ff, 0x993333ff, 0x993300ff, 0x9900ffff, 0x9900ccff, 0x990099ff, 0x990066ff, 0x990033ff, 0x990000ff, 0x66ffffff, 0x66ffccff, 0x66ff99ff, 0x66ff66ff, 0x66ff33ff, 0x66ff00ff, 0x66ccffff, 0x66ccccff, 0x66cc99ff, 0x66cc66ff, 0x66cc33ff, 0x66cc00ff, 0x6699ffff, 0x6699ccff, 0x669999ff, 0x669966ff, 0x669933ff, 0x669900ff, 0x6666ffff, 0x6666ccff, 0x666699ff, 0x666666ff, 0x666633ff, 0x666600ff, 0x6633ffff, 0x6633ccff, 0x663399ff, 0x663366ff, 0x663333ff, 0x663300ff, 0x6600ffff, 0x6600ccff, 0x660099ff, 0x660066ff, 0x660033ff, 0x660000ff, 0x33ffffff, 0x33ffccff, 0x33ff99ff, 0x33ff66ff, 0x33ff33ff, 0x33ff00ff, 0x33ccffff, 0x33ccccff, 0x33cc99ff, 0x33cc66ff, 0x33cc33ff, 0x33cc00ff, 0x3399ffff, 0x3399ccff, 0x339999ff, 0x339966ff, 0x339933ff, 0x339900ff, 0x3366ffff, 0x3366ccff, 0x336699ff, 0x336666ff, 0x336633ff, 0x336600ff, 0x3333ffff, 0x3333ccff, 0x333399ff, 0x333366ff, 0x333333ff, 0x333300ff, 0x3300ffff, 0x3300ccff, 0x330099ff, 0x330066ff, 0x330033ff, 0x330000ff, 0xffffff, 0xffccff, 0xff99ff, 0xff66ff, 0xff33ff, 0xff00ff, 0xccffff, 0xccccff, 0xcc99ff, 0xcc66ff, 0xcc33ff, 0xcc00ff, 0x99ffff, 0x99ccff, 0x9999ff, 0x9966ff, 0x9933ff, 0x9900ff, 0x66ffff, 0x66ccff, 0x6699ff, 0x6666ff, 0x6633ff, 0x6600ff, 0x33ffff, 0x33ccff, 0x3399ff, 0x3366ff, 0x3333ff, 0x3300ff, 0xffff, 0xccff, 0x99ff, 0x66ff, 0x33ff, 0xee0000ff, 0xdd0000ff, 0xbb0000ff, 0xaa0000ff, 0x880000ff, 0x770000ff, 0x550000ff, 0x440000ff, 0x220000ff, 0x110000ff, 0xee00ff, 0xdd00ff, 0xbb00ff, 0xaa00ff, 0x8800ff, 0x7700ff, 0x5500ff, 0x4400ff, 0x2200ff, 0x1100ff, 0xeeff, 0xddff, 0xbbff, 0xaaff, 0x88ff, 0x77ff, 0x55ff, 0x44ff, 0x22ff, 0x11ff, 0xeeeeeeff, 0xddddddff, 0xbbbbbbff, 0xaaaaaaff, 0x888888ff, 0x777777ff, 0x555555ff, 0x444444ff, 0x222222ff, 0x111111ff];
+            pending_voxels: Vec::new(),
+            version: 0,
+            options: ParseOptions::default(),
+            error: None,
+            overrun: false,
+            truncated_at: None,
+            complete_models: 0,
+            warnings: Vec::new(),
+            models: Vec::new(),
+            scene: SceneGraph::default(),
+            materials: BTreeMap::new(),
+            cameras: Vec::new(),
+            render_objects: Vec::new(),
+            index_map: None,
+            palette_notes: Vec::new(),
+            unknown_chunks: Vec::new(),
+            chunk_counts: BTreeMap::new(),
+            last_known: None,
+            palette: Palette::magica_voxel_default(),
+        }
+    }
+
+    /// The raw version number from the file header.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn format_version(&self) -> FormatVersion {
+        FormatVersion::from_raw(self.version)
+    }
+
+    /// Problems that did not stop parsing, such as an unknown file version.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The coordinate system models and the scene graph are expressed in.
+    pub fn coordinate_system(&self) -> CoordinateSystem {
+        self.options.target_coords
+    }
+
+    /// Where the data ended in the middle of a chunk, for files cut off by
+    /// an interrupted download. Parsing stops there, keeping only the
+    /// models that were read in full.
+    pub fn truncated_at(&self) -> Option<usize> {
+        self.truncated_at
+    }
+
+    pub fn models(&self) -> &[Model] {
+        &self.models
+    }
+
+    pub fn models_mut(&mut self) -> &mut Vec<Model> {
+        &mut self.models
+    }
+
+    pub fn scene(&self) -> &SceneGraph {
+        &self.scene
+    }
+
+    /// Models that are not hidden through their layer. Files without a scene
+    /// graph have every model visible.
+    pub fn visible_models(&self) -> Vec<&Model> {
+        if self.scene.is_empty() {
+            return self.models.iter().collect();
         }
+        self.scene.visible_models().into_iter().filter_map(|id| self.models.get(id as usize)).collect()
+    }
 
-        return vl;
+    /// MATL (or legacy MATT) materials keyed by palette index.
+    pub fn materials(&self) -> &BTreeMap<u32, Material> {
+        &self.materials
+    }
+
+    pub fn cameras(&self) -> &[Camera] {
+        &self.cameras
+    }
+
+    pub fn render_objects(&self) -> &[RenderObject] {
+        &self.render_objects
+    }
+
+    /// The IMAP chunk, if present: entry `i` is the palette index shown at
+    /// position `i` of MagicaVoxel's palette panel.
+    pub fn index_map(&self) -> Option<&[u8]> {
+        self.index_map.as_deref()
+    }
+
+    /// Position at which the artist sees palette index `c`. Without an IMAP
+    /// chunk the display order matches the storage order.
+    pub fn palette_display_index(&self, c: u8) -> u8 {
+        self.index_map
+            .as_ref()
+            .and_then(|map| map.iter().position(|&index| index == c))
+            .map_or(c, |position| position as u8)
+    }
+
+    /// Palette row names from the NOTE chunk, one per row of eight colors.
+    pub fn palette_notes(&self) -> &[String] {
+        &self.palette_notes
+    }
+
+    /// Chunks the loader does not understand, with their content and child
+    /// chunks, in file order. Their children are kept as raw bytes rather
+    /// than parsed. `VoxWriter` writes them back so a load and save keeps
+    /// newer or third-party data.
+    pub fn unknown_chunks(&self) -> &[RawChunk] {
+        &self.unknown_chunks
+    }
+
+    pub fn palette_row_name(&self, row: usize) -> Option<&str> {
+        self.palette_notes.get(row).map(|name| name.as_str()).filter(|name| !name.is_empty())
+    }
+
+    fn parse(&mut self, progress: &mut dyn Progress) {
+        let magic = self.read_string();
+        self.version = self.read_int(false);
+        if self.overrun {
+            self.truncate(0);
+            return;
+        }
+        if magic != "VOX " {
+            self.report(format!("unexpected file magic {:?}", magic));
+        }
+        if self.version > 200 {
+            self.warnings.push(format!("version {} is newer than the supported 200, parsing as 200", self.version));
+        } else if self.version < 150 {
+            self.warnings.push(format!("version {} is older than the supported 150, parsing as 150", self.version));
+        }
+        self.read_chunk(progress);
+        self.decode_pending_voxels();
+        for i in 0..self.models.len() {
+            let model = &mut self.models[i];
+            if model.size == (Size { x: 0, y: 0, z: 0 }) && !model.voxels.is_empty() {
+                let extent = |axis: fn(&Voxel) -> u8| model.voxels.iter().map(|v| axis(v) as u32 + 1).max().unwrap_or(0);
+                model.size = Size { x: extent(|v| v.x), y: extent(|v| v.y), z: extent(|v| v.z) };
+                self.report(format!("model {} has voxels but no SIZE chunk", i));
+                continue;
+            }
+            let size = model.size;
+            let count = model.voxels.len();
+            model.voxels.retain(|v| (v.x as u32) < size.x && (v.y as u32) < size.y && (v.z as u32) < size.z);
+            let dropped = count - model.voxels.len();
+            if dropped > 0 {
+                self.report(format!("model {} has {} voxels outside its {}x{}x{} size", i, dropped, size.x, size.y, size.z));
+            }
+        }
+        let coords = self.options.target_coords;
+        if coords != CoordinateSystem::ZUpRightHanded {
+            for model in &mut self.models {
+                *model = model.converted(CoordinateSystem::ZUpRightHanded, coords);
+            }
+            self.scene.convert(CoordinateSystem::ZUpRightHanded, coords);
+        }
+    }
+
+    /// Stops parsing at a chunk that runs past the end of the data, dropping
+    /// models whose voxels were not reached.
+    fn truncate(&mut self, chunk_start: usize) {
+        self.models.truncate(self.complete_models);
+        self.truncated_at = Some(chunk_start);
+        let length = self.bytes().len();
+        self.report(format!("truncated: data ends at offset {} inside the chunk at offset {}", length, chunk_start));
+    }
+
+    /// Records a problem with the data: the first one becomes the error in
+    /// strict mode, every one is a warning otherwise.
+    fn report(&mut self, message: String) {
+        if !self.options.strict {
+            self.warnings.push(message);
+        } else if self.error.is_none() {
+            self.error = Some(ParseError { message });
+        }
+    }
+
+    /// Decodes the XYZI chunks collected while parsing with the `rayon`
+    /// feature on the rayon thread pool.
+    #[cfg(feature = "rayon")]
+    fn decode_pending_voxels(&mut self) {
+        let pending = std::mem::take(&mut self.pending_voxels);
+        let bytes = self.bytes();
+        let decoded: Vec<(usize, Vec<Voxel>)> = pending.par_iter().map(|(model, range)| (*model, decode_voxels(&bytes[range.clone()]))).collect();
+        for (model, voxels) in decoded {
+            self.models[model].voxels = voxels;
+        }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn decode_pending_voxels(&mut self) {}
+
+    /// The file contents, mapped or read.
+    fn bytes(&self) -> &[u8] {
+        #[cfg(all(feature = "mmap", unix))]
+        {
+            if let Some(ref mapped) = self.mapped {
+                return mapped;
+            }
+        }
+        &self.data
+    }
+
+    /// The next `length` bytes, or `None` with `overrun` set if the data
+    /// ends first.
+    fn take(&mut self, length: usize) -> Option<Range<usize>> {
+        let range = self.offset..self.offset.saturating_add(length);
+        if range.end > self.bytes().len() {
+            self.overrun = true;
+            self.offset = self.bytes().len();
+            return None;
+        }
+        self.offset = range.end;
+        Some(range)
     }
 
     fn read_string(&mut self) -> String {
-        let mut char_vector: Vec<char> = Vec::new();
-        for _ in 0..4 {
-            char_vector.push(self.data[self.offset] as char);
-            self.offset += 1;
+        match self.take(4) {
+            Some(range) => self.bytes()[range].iter().map(|&b| b as char).collect(),
+            None => String::new(),
+        }
+    }
+
+    fn read_sized_string(&mut self) -> String {
+        let length = self.read_int(false) as usize;
+        match self.take(length) {
+            Some(range) => String::from_utf8_lossy(&self.bytes()[range]).into_owned(),
+            None => String::new(),
+        }
+    }
+
+    fn read_dict(&mut self) -> Dict {
+        match Dict::read(self.bytes().get(self.offset..).unwrap_or(&[])) {
+            Some((dict, length)) => {
+                self.offset += length;
+                dict
+            }
+            None => {
+                self.overrun = true;
+                self.offset = self.bytes().len();
+                Dict::new()
+            }
         }
-        return char_vector.iter().cloned().collect::<String>();
     }
 
     fn read_byte(&mut self) -> u8 {
-        let result: u8 = self.data[self.offset];
-        self.offset += 1;
-        return result;
+        match self.take(1) {
+            Some(range) => self.bytes()[range.start],
+            None => 0,
+        }
+    }
+
+    /// Reads an element count, capped at what the rest of the data can
+    /// hold with elements of at least `min_size` bytes.
+    fn read_count(&mut self, min_size: usize) -> u32 {
+        let count = self.read_int(false);
+        let room = (self.bytes().len() - self.offset) / min_size;
+        if count as usize > room {
+            self.overrun = true;
+            return room as u32;
+        }
+        count
     }
 
     fn read_int(&mut self, big_endian: bool) -> u32 {
-        let mut u8_vector: Vec<u8> = Vec::new();
-        for _ in 0..4 {
-            u8_vector.push(self.data[self.offset]);
-            self.offset += 1;
+        let mut bytes = [0; 4];
+        if let Some(range) = self.take(4) {
+            bytes.copy_from_slice(&self.bytes()[range]);
         }
-        let mut buf = Cursor::new(u8_vector);
         if big_endian {
-            return buf.read_u32::<BigEndian>().unwrap();
+            u32::from_be_bytes(bytes)
         } else {
-            return buf.read_u32::<LittleEndian>().unwrap();
+            u32::from_le_bytes(bytes)
         }
     }
 
-    fn read_chunk(&mut self) -> Chunk {
+    fn read_float(&mut self) -> f32 {
+        f32::from_bits(self.read_int(false))
+    }
+
+    fn read_chunk(&mut self, progress: &mut dyn Progress) -> Chunk {
         let mut chunk = Chunk {
             id: self.read_string(),
             length: self.read_int(false),
             child_length: self.read_int(false),
             childs: vec![],
         };
+        let content_start = self.offset;
+        if self.overrun || content_start + chunk.length as usize > self.bytes().len() {
+            self.truncate(content_start.saturating_sub(12));
+            return chunk;
+        }
+        progress.on_chunk(&chunk.id);
 
-        if chunk.id == "MAIN" && chunk.child_length > 0 {
-            let mut child_bytes_remaining = chunk.child_length;
-            while child_bytes_remaining > 0 {
-                let child_chunk = self.read_chunk();
-                child_bytes_remaining -= child_chunk.length + 12;
-                chunk.childs.push(child_chunk);
-            }
+        if chunk.id == "PACK" {
+            let num_models = self.read_count(40);
+            self.models.reserve(num_models as usize);
         } else if chunk.id == "SIZE" {
-            self.size.x = self.read_int(false);
-            self.size.y = self.read_int(false);
-            self.size.z = self.read_int(false);
+            let mut size = Size {
+                x: self.read_int(false),
+                y: self.read_int(false),
+                z: self.read_int(false),
+            };
+            if [size.x, size.y, size.z].iter().any(|&side| side == 0 || side > 256) {
+                self.report(format!("model size {}x{}x{} at offset {} is outside 1 to 256, clamped", size.x, size.y, size.z, content_start));
+                size = Size { x: size.x.clamp(1, 256), y: size.y.clamp(1, 256), z: size.z.clamp(1, 256) };
+            }
+            self.models.push(Model::new(size));
         } else if chunk.id == "XYZI" {
-            let num_voxels = self.read_int(false);
-            let mut voxels: Vec<Voxel> = Vec::new();
-
-            for _ in 0..num_voxels {
-                let voxel: Voxel = Voxel {
-                    x: self.read_byte(),
-                    y: self.read_byte(),
-                    z: self.read_byte(),
-                    c: self.read_byte(),
-                };
-                voxels.push(voxel);
+            let mut num_voxels = self.read_int(false) as usize;
+            let room = (chunk.length as usize).saturating_sub(4) / 4;
+            if num_voxels > room {
+                self.report(format!("XYZI chunk at offset {} lists {} voxels but has room for {}", content_start, num_voxels, room));
+                num_voxels = room;
             }
-            self.voxels.push(voxels)
-
+            let range = self.offset..self.offset + num_voxels * 4;
+            let filled = self.models.last().is_none_or(|model| !model.voxels.is_empty())
+                || self.pending_voxels.last().is_some_and(|&(model, _)| model + 1 == self.models.len());
+            if filled {
+                self.models.push(Model::new(Size { x: 0, y: 0, z: 0 }));
+            }
+            if cfg!(feature = "rayon") {
+                self.pending_voxels.push((self.models.len() - 1, range));
+            } else {
+                self.models.last_mut().unwrap().voxels = decode_voxels(&self.bytes()[range]);
+            }
+            self.complete_models = self.models.len();
         } else if chunk.id == "RGBA" {
-            for _ in 0..256 {
-                let color: u32 = self.read_int(true);
-                self.palette.push(color);
+            let entries: Vec<Color> = (0..256)
+                .map(|_| Color::new(self.read_byte(), self.read_byte(), self.read_byte(), self.read_byte()))
+                .collect();
+            self.palette = Palette::from_rgba_chunk(&entries);
+        } else if chunk.id == "nTRN" {
+            let node = self.read_transform_node();
+            self.scene.insert(SceneNode::Transform(node));
+        } else if chunk.id == "nGRP" {
+            let id = self.read_int(false);
+            self.read_dict();
+            let num_children = self.read_count(4);
+            let children = (0..num_children).map(|_| self.read_int(false)).collect();
+            self.scene.insert(SceneNode::Group(GroupNode { id, children }));
+        } else if chunk.id == "nSHP" {
+            let id = self.read_int(false);
+            self.read_dict();
+            let num_models = self.read_count(8);
+            let mut models = Vec::new();
+            let mut keys = Vec::new();
+            for _ in 0..num_models {
+                let model = self.read_int(false);
+                let frame = self.read_dict().get_u32("_f").unwrap_or(0);
+                models.push(model);
+                keys.push(ModelKey { frame, model });
             }
-        } else {
-            println!("unsupported chunk type {}", chunk.id);
+            keys.sort_by_key(|key| key.frame);
+            self.scene.animation.shapes.insert(id, keys);
+            self.scene.insert(SceneNode::Shape(ShapeNode { id, models }));
+        } else if chunk.id == "MATL" {
+            let id = self.read_int(false);
+            let attributes = self.read_dict();
+            self.materials.insert(id, Material::from_attributes(id, &attributes));
+        } else if chunk.id == "MATT" && !self.format_version().reads_legacy_materials() {
+            self.warnings.push(format!("ignoring legacy MATT chunk in version {} file", self.version));
+        } else if chunk.id == "MATT" {
+            let id = self.read_int(false);
+            let material_type = self.read_int(false);
+            let weight = self.read_float();
+            let property_bits = self.read_int(false);
+            let num_properties = (property_bits & 0x7f).count_ones();
+            let properties: Vec<f32> = (0..num_properties).map(|_| self.read_float()).collect();
+            self.materials.entry(id).or_insert_with(|| {
+                Material::from_legacy(id, material_type, weight, property_bits, &properties)
+            });
+        } else if chunk.id == "LAYR" {
+            let id = self.read_int(false);
+            let attributes = self.read_dict();
+            self.scene.layers.push(Layer {
+                id,
+                name: attributes.get_string("_name").unwrap_or_default(),
+                hidden: attributes.get_bool("_hidden").unwrap_or(false),
+            });
+        } else if chunk.id == "rCAM" {
+            let id = self.read_int(false);
+            let attributes = self.read_dict();
+            self.cameras.push(Camera::from_attributes(id, &attributes));
+        } else if chunk.id == "rOBJ" {
+            let attributes = self.read_dict();
+            self.render_objects.push(RenderObject::from_attributes(attributes));
+        } else if chunk.id == "IMAP" {
+            let index_map = (0..256).map(|_| self.read_byte()).collect();
+            self.index_map = Some(index_map);
+        } else if chunk.id == "NOTE" {
+            let num_names = self.read_count(4);
+            self.palette_notes = (0..num_names).map(|_| self.read_sized_string()).collect();
+        } else if chunk.id != "MAIN" {
+            self.report(format!("unsupported chunk type {:?} at offset {}", chunk.id, content_start - 12));
+            let content_end = content_start + chunk.length as usize;
+            let children_end = content_end.saturating_add(chunk.child_length as usize);
+            if children_end > self.bytes().len() {
+                self.truncate(content_start - 12);
+                return chunk;
+            }
+            self.unknown_chunks.push(RawChunk {
+                id: chunk.id.clone(),
+                content: self.bytes()[content_start..content_end].to_vec(),
+                children: self.bytes()[content_end..children_end].to_vec(),
+                after: self.last_known.clone(),
+            });
+            self.offset = children_end;
+            progress.on_progress(self.offset, self.bytes().len());
+            return chunk;
+        }
+        if self.overrun || self.offset > content_start + chunk.length as usize {
+            self.report(format!("{} chunk at offset {} is shorter than its contents", chunk.id, content_start - 12));
+            self.overrun = false;
+        }
+        if chunk.id != "MAIN" {
+            let count = self.chunk_counts.entry(chunk.id.clone()).or_insert(0);
+            self.last_known = Some((chunk.id.clone(), *count));
+            *count += 1;
         }
+        self.offset = content_start + chunk.length as usize;
+
+        let mut child_bytes_remaining = chunk.child_length;
+        while child_bytes_remaining > 0 && self.error.is_none() && self.truncated_at.is_none() {
+            let child_chunk = self.read_chunk(progress);
+            let child_size = child_chunk.length.saturating_add(child_chunk.child_length).saturating_add(12);
+            chunk.childs.push(child_chunk);
+            match child_bytes_remaining.checked_sub(child_size) {
+                Some(remaining) => child_bytes_remaining = remaining,
+                None => {
+                    self.report(format!("children of {} overrun its declared {} bytes", chunk.id, chunk.child_length));
+                    break;
+                }
+            }
+        }
+        progress.on_progress(self.offset, self.bytes().len());
+
+        chunk
+    }
 
-        return chunk;
+    fn read_transform_node(&mut self) -> TransformNode {
+        let mut node = TransformNode {
+            id: self.read_int(false),
+            child: 0,
+            layer_id: -1,
+            rotation: Rotation::IDENTITY,
+            translation: [0, 0, 0],
+            name: None,
+            hidden: false,
+        };
+        let attributes = self.read_dict();
+        node.name = attributes.get_string("_name");
+        node.hidden = attributes.get_bool("_hidden").unwrap_or(false);
+        node.child = self.read_int(false);
+        self.read_int(false);
+        node.layer_id = self.read_int(false) as i32;
+        let num_frames = self.read_count(4);
+        let mut keys = Vec::new();
+        for _ in 0..num_frames {
+            let attributes = self.read_dict();
+            keys.push(TransformKey {
+                frame: attributes.get_u32("_f").unwrap_or(0),
+                rotation: attributes.get_rotation("_r").unwrap_or(Rotation::IDENTITY),
+                translation: attributes.get_vec3("_t").unwrap_or([0, 0, 0]),
+            });
+        }
+        keys.sort_by_key(|key| key.frame);
+        if let Some(first) = keys.first() {
+            node.rotation = first.rotation;
+            node.translation = first.translation;
+        }
+        self.scene.animation.transforms.insert(node.id, keys);
+        node
     }
 
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
     fn load(&mut self) {
-        let path = Path::new(self.filepath);
-        let display = path.display();
-        let mut file = match File::open(path) {
-            Err(why) => panic!("couldn't open {}: {}", display, Error::description(&why)),
-            Ok(file) => file,
+        let mut file = match self.filepath {
+            Some(ref path) => match File::open(path) {
+                Err(why) => panic!("couldn't open {}: {}", path.display(), why),
+                Ok(file) => file,
+            },
+            None => return,
         };
         file.read_to_end(&mut self.data).unwrap();
-        self.read_string();
-        self.read_int(false);
-        self.read_chunk();
     }
 
 }
+
+fn decode_voxels(data: &[u8]) -> Vec<Voxel> {
+    data.chunks(4).map(|v| Voxel { x: v[0], y: v[1], z: v[2], c: v[3] }).collect()
+}
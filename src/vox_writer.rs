@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::collections::BTreeMap;
+use byteorder::{LittleEndian, WriteBytesExt};
+use chunk::{ChunkWriter, RawChunk};
+use coords::CoordinateSystem;
+use dict::Dict;
+use scene::{Rotation, SceneGraph, SceneNode, TransformKey, TransformNode};
+use vox_loader::VoxLoader;
+
+/// The oldest version written. Older files are saved as this version,
+/// since their legacy MATT materials are written as MATL.
+const MIN_VERSION: u32 = 150;
+
+pub struct VoxWriter;
+
+impl VoxWriter {
+    pub fn write<P: AsRef<Path>>(vl: &VoxLoader, fp: P) -> io::Result<()> {
+        let mut file = File::create(fp)?;
+        file.write_all(&VoxWriter::to_bytes(vl))
+    }
+
+    /// Encodes the loader as a `.vox` file with the version it was read
+    /// with. Besides the models and palette, the scene graph, layers,
+    /// materials, render settings, cameras, palette index map and notes
+    /// are written back in MagicaVoxel's chunk order, and each unknown
+    /// chunk follows the chunk it came after in the file. Attributes this
+    /// crate does not model are dropped and dictionaries are rebuilt, so
+    /// other writers' files may not come back byte for byte.
+    pub fn to_bytes(vl: &VoxLoader) -> Vec<u8> {
+        let mut chunks = ChunkWriter::with_header(vl.version().max(MIN_VERSION));
+        chunks.begin(b"MAIN", &[]);
+        let mut writer = PlacingWriter {
+            writer: chunks,
+            unknown: vl.unknown_chunks(),
+            written: vec![false; vl.unknown_chunks().len()],
+            counts: BTreeMap::new(),
+        };
+        writer.place(None);
+        if vl.models().len() > 1 {
+            let mut pack: Vec<u8> = Vec::new();
+            write_int(&mut pack, vl.models().len() as u32);
+            writer.chunk(b"PACK", &pack);
+        }
+        for model in vl.models() {
+            let model = &model.converted(vl.coordinate_system(), CoordinateSystem::ZUpRightHanded);
+            let mut size: Vec<u8> = Vec::new();
+            write_int(&mut size, model.size.x);
+            write_int(&mut size, model.size.y);
+            write_int(&mut size, model.size.z);
+            writer.chunk(b"SIZE", &size);
+
+            let mut xyzi: Vec<u8> = Vec::new();
+            write_int(&mut xyzi, model.voxels.len() as u32);
+            for voxel in &model.voxels {
+                xyzi.extend_from_slice(&[voxel.x, voxel.y, voxel.z, voxel.c]);
+            }
+            writer.chunk(b"XYZI", &xyzi);
+        }
+
+        let mut scene = vl.scene().clone();
+        scene.convert(vl.coordinate_system(), CoordinateSystem::ZUpRightHanded);
+        write_scene(&mut writer, &scene);
+        for layer in &scene.layers {
+            let mut attributes = Dict::new();
+            if !layer.name.is_empty() {
+                attributes.insert("_name", layer.name.as_str());
+            }
+            if layer.hidden {
+                attributes.insert("_hidden", "1");
+            }
+            let mut data = Vec::new();
+            write_int(&mut data, layer.id);
+            attributes.write(&mut data);
+            write_int(&mut data, u32::MAX);
+            writer.chunk(b"LAYR", &data);
+        }
+
+        let mut rgba: Vec<u8> = Vec::new();
+        for color in vl.palette.to_rgba_chunk() {
+            rgba.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        writer.chunk(b"RGBA", &rgba);
+        if let Some(index_map) = vl.index_map() {
+            writer.chunk(b"IMAP", index_map);
+        }
+        for (&id, material) in vl.materials() {
+            let mut data = Vec::new();
+            write_int(&mut data, id);
+            material.to_attributes().write(&mut data);
+            writer.chunk(b"MATL", &data);
+        }
+        for object in vl.render_objects() {
+            writer.chunk(b"rOBJ", &object.attributes.to_bytes());
+        }
+        for camera in vl.cameras() {
+            let mut data = Vec::new();
+            write_int(&mut data, camera.id);
+            camera.to_attributes().write(&mut data);
+            writer.chunk(b"rCAM", &data);
+        }
+        if !vl.palette_notes().is_empty() {
+            let mut data = Vec::new();
+            write_int(&mut data, vl.palette_notes().len() as u32);
+            for note in vl.palette_notes() {
+                write_int(&mut data, note.len() as u32);
+                data.extend_from_slice(note.as_bytes());
+            }
+            writer.chunk(b"NOTE", &data);
+        }
+        writer.finish()
+    }
+}
+
+/// A chunk writer that puts each unknown chunk back after the chunk it was
+/// anchored to. Chunks whose anchor is never written go at the end.
+struct PlacingWriter<'a> {
+    writer: ChunkWriter,
+    unknown: &'a [RawChunk],
+    written: Vec<bool>,
+    counts: BTreeMap<[u8; 4], usize>,
+}
+
+impl<'a> PlacingWriter<'a> {
+    fn chunk(&mut self, id: &[u8; 4], content: &[u8]) {
+        self.writer.chunk(id, content);
+        let count = self.counts.entry(*id).or_insert(0);
+        let anchor = (*id, *count);
+        *count += 1;
+        self.place(Some(anchor));
+    }
+
+    /// Writes the unknown chunks anchored after the `n`th chunk of an id,
+    /// or at the start of MAIN for `None`.
+    fn place(&mut self, anchor: Option<([u8; 4], usize)>) {
+        for (chunk, written) in self.unknown.iter().zip(self.written.iter_mut()) {
+            let here = match (&chunk.after, anchor) {
+                (None, None) => true,
+                (Some((id, n)), Some((anchor_id, anchor_n))) => {
+                    *n == anchor_n && id.chars().map(|c| c as u8).eq(anchor_id.iter().cloned())
+                }
+                _ => false,
+            };
+            if here && !*written {
+                self.writer.raw(chunk);
+                *written = true;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for (chunk, written) in self.unknown.iter().zip(&self.written) {
+            if !written {
+                self.writer.raw(chunk);
+            }
+        }
+        self.writer.finish()
+    }
+}
+
+/// Writes the nodes as nTRN, nGRP and nSHP chunks in id order, with their
+/// animation keys when they have any.
+fn write_scene(writer: &mut PlacingWriter, scene: &SceneGraph) {
+    for node in scene.nodes.values() {
+        let mut data = Vec::new();
+        match node {
+            SceneNode::Transform(node) => {
+                write_int(&mut data, node.id);
+                let mut attributes = Dict::new();
+                if let Some(ref name) = node.name {
+                    attributes.insert("_name", name.as_str());
+                }
+                if node.hidden {
+                    attributes.insert("_hidden", "1");
+                }
+                attributes.write(&mut data);
+                write_int(&mut data, node.child);
+                write_int(&mut data, u32::MAX);
+                write_int(&mut data, node.layer_id as u32);
+                let frames = transform_frames(scene, node);
+                write_int(&mut data, frames.len() as u32);
+                for frame in frames {
+                    frame.write(&mut data);
+                }
+                writer.chunk(b"nTRN", &data);
+            }
+            SceneNode::Group(node) => {
+                write_int(&mut data, node.id);
+                Dict::new().write(&mut data);
+                write_int(&mut data, node.children.len() as u32);
+                for &child in &node.children {
+                    write_int(&mut data, child);
+                }
+                writer.chunk(b"nGRP", &data);
+            }
+            SceneNode::Shape(node) => {
+                write_int(&mut data, node.id);
+                Dict::new().write(&mut data);
+                let keys = scene.animation.shapes.get(&node.id).filter(|keys| keys.iter().any(|key| key.frame != 0));
+                match keys {
+                    Some(keys) => {
+                        write_int(&mut data, keys.len() as u32);
+                        for key in keys {
+                            write_int(&mut data, key.model);
+                            let mut attributes = Dict::new();
+                            attributes.insert("_f", key.frame.to_string());
+                            attributes.write(&mut data);
+                        }
+                    }
+                    None => {
+                        write_int(&mut data, node.models.len() as u32);
+                        for &model in &node.models {
+                            write_int(&mut data, model);
+                            Dict::new().write(&mut data);
+                        }
+                    }
+                }
+                writer.chunk(b"nSHP", &data);
+            }
+        }
+    }
+}
+
+/// The frame attributes of a transform. The first key takes the node's
+/// own rotation and translation, which is where the loader puts it, so
+/// edits to the node are saved. `_f` is only written for animated nodes.
+fn transform_frames(scene: &SceneGraph, node: &TransformNode) -> Vec<Dict> {
+    let mut keys = scene.animation.transforms.get(&node.id).cloned().unwrap_or_default();
+    match keys.first_mut() {
+        Some(first) => {
+            first.rotation = node.rotation;
+            first.translation = node.translation;
+        }
+        None => keys.push(TransformKey { frame: 0, rotation: node.rotation, translation: node.translation }),
+    }
+    let animated = keys.len() > 1;
+    keys.iter()
+        .map(|key| {
+            let mut attributes = Dict::new();
+            if key.rotation != Rotation::IDENTITY {
+                attributes.insert("_r", key.rotation.0.to_string());
+            }
+            if key.translation != [0, 0, 0] {
+                let t = key.translation;
+                attributes.insert("_t", format!("{} {} {}", t[0], t[1], t[2]));
+            }
+            if animated {
+                attributes.insert("_f", key.frame.to_string());
+            }
+            attributes
+        })
+        .collect()
+}
+
+fn write_int(data: &mut Vec<u8>, value: u32) {
+    data.write_u32::<LittleEndian>(value).unwrap();
+}
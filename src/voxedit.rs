@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt};
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette};
+
+const EMPTY: u8 = 0xff;
+
+/// One layer of a VoxEdit model. Files before version 12 have a single
+/// layer named "Main".
+#[derive(Clone, PartialEq, Debug)]
+pub struct VxmLayer {
+    pub name: String,
+    pub visible: bool,
+    pub model: Model,
+}
+
+/// A VoxEdit `.vxm` model normalized into paletted models. Material `i`
+/// of the file becomes palette index `i + 1`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct VxmFile {
+    pub version: u8,
+    pub layers: Vec<VxmLayer>,
+    pub palette: Palette,
+    /// Pivot as a fraction of the size, in the converted Z-up space.
+    pub pivot: [f32; 3],
+    /// Palette indices of materials flagged as emissive.
+    pub emissive: Vec<u8>,
+}
+
+impl VxmFile {
+    pub fn models(&self) -> Vec<&Model> {
+        self.layers.iter().map(|layer| &layer.model).collect()
+    }
+}
+
+pub fn load_vxm<P: AsRef<Path>>(path: P) -> io::Result<VxmFile> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    read_vxm(&bytes)
+}
+
+/// Parses a `.vxm` of version 6 to 12. The baked surface preview and LOD
+/// meshes are skipped; only the palette and the run-length encoded layers
+/// are read. VoxEdit is Y-up, so Y and Z are swapped and the new Y
+/// mirrored to stay right-handed.
+pub fn read_vxm(bytes: &[u8]) -> io::Result<VxmFile> {
+    if bytes.len() < 4 || &bytes[..3] != b"VXM" {
+        return Err(invalid_data("missing VXM file magic".to_string()));
+    }
+    let version = match bytes[3] {
+        digit @ b'0'..=b'9' => digit - b'0',
+        letter @ b'A'..=b'C' => letter - b'A' + 10,
+        other => return Err(invalid_data(format!("unknown VXM version {:?}", other as char))),
+    };
+    if !(6..=12).contains(&version) {
+        return Err(invalid_data(format!("unsupported VXM version {}", version)));
+    }
+    let mut cursor = Cursor::new(&bytes[4..]);
+    let size = [
+        cursor.read_u32::<LittleEndian>()?,
+        cursor.read_u32::<LittleEndian>()?,
+        cursor.read_u32::<LittleEndian>()?,
+    ];
+    if size.iter().any(|&side| side > 256) {
+        return Err(invalid_data(format!("size {:?} exceeds 256 voxels per axis", size)));
+    }
+    let pivot = [
+        cursor.read_f32::<LittleEndian>()?,
+        cursor.read_f32::<LittleEndian>()?,
+        cursor.read_f32::<LittleEndian>()?,
+    ];
+
+    if version >= 9 && cursor.read_u8()? != 0 {
+        let mut bounds = [0u32; 7];
+        for value in bounds.iter_mut() {
+            *value = cursor.read_u32::<LittleEndian>()?;
+        }
+        let (width, height) = if version >= 10 {
+            (cursor.read_u32::<LittleEndian>()?, cursor.read_u32::<LittleEndian>()?)
+        } else {
+            let [start_x, start_y, start_z, end_x, end_y, end_z, normal] = bounds;
+            match normal {
+                0 | 1 => (end_z.wrapping_sub(start_z), end_y.wrapping_sub(start_y)),
+                2 | 3 => (end_x.wrapping_sub(start_x), end_z.wrapping_sub(start_z)),
+                _ => (end_x.wrapping_sub(start_x), end_y.wrapping_sub(start_y)),
+            }
+        };
+        skip(&mut cursor, width as u64 * height as u64 * 4)?;
+    }
+    if version >= 8 {
+        skip(&mut cursor, 16)?;
+    }
+
+    let lod_levels = cursor.read_u32::<LittleEndian>()?;
+    for _ in 0..lod_levels {
+        let width = cursor.read_u32::<LittleEndian>()?;
+        let height = cursor.read_u32::<LittleEndian>()?;
+        skip(&mut cursor, width as u64 * height as u64 * 4)?;
+        for _ in 0..6 {
+            let quads = cursor.read_u32::<LittleEndian>()?;
+            skip(&mut cursor, quads as u64 * 4 * 20)?;
+        }
+    }
+
+    let mut palette = Palette::from_rgba_chunk(&[]);
+    let mut emissive = Vec::new();
+    let material_count = cursor.read_u8()?;
+    for i in 0..material_count {
+        let (b, g, r, a) = (cursor.read_u8()?, cursor.read_u8()?, cursor.read_u8()?, cursor.read_u8()?);
+        palette.set(i.saturating_add(1), Color::new(r, g, b, a));
+        if cursor.read_u8()? != 0 {
+            emissive.push(i.saturating_add(1));
+        }
+    }
+    if version >= 10 {
+        let palette_chunks = cursor.read_u8()?;
+        skip(&mut cursor, palette_chunks as u64 * 2)?;
+    }
+
+    let layer_count = if version >= 12 { cursor.read_u8()? } else { 1 };
+    let total = size[0] as usize * size[1] as usize * size[2] as usize;
+    let mut layers = Vec::new();
+    for _ in 0..layer_count {
+        let (name, visible) = if version >= 12 {
+            let mut name = Vec::new();
+            loop {
+                match cursor.read_u8()? {
+                    0 => break,
+                    byte => name.push(byte),
+                }
+            }
+            (String::from_utf8_lossy(&name).into_owned(), cursor.read_u8()? != 0)
+        } else {
+            ("Main".to_string(), true)
+        };
+        let mut model = Model::new(Size { x: size[0], y: size[2], z: size[1] });
+        let mut index = 0;
+        loop {
+            let length = cursor.read_u8()? as usize;
+            if length == 0 {
+                break;
+            }
+            let material = cursor.read_u8()?;
+            if material != EMPTY {
+                for i in index..(index + length).min(total) {
+                    let (x, y, z) = (i / (size[1] as usize * size[2] as usize), i / size[2] as usize % size[1] as usize, i % size[2] as usize);
+                    model.voxels.push(Voxel { x: x as u8, y: (size[2] as usize - 1 - z) as u8, z: y as u8, c: material + 1 });
+                }
+            }
+            index += length;
+        }
+        layers.push(VxmLayer { name, visible, model });
+    }
+    Ok(VxmFile { version, layers, palette, pivot: [pivot[0], 1.0 - pivot[2], pivot[1]], emissive })
+}
+
+fn skip(cursor: &mut Cursor<&[u8]>, length: u64) -> io::Result<()> {
+    if cursor.position() + length > cursor.get_ref().len() as u64 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "section runs past the end of the file"));
+    }
+    cursor.seek(SeekFrom::Current(length as i64))?;
+    Ok(())
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
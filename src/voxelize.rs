@@ -0,0 +1,234 @@
+use mesher::VoxMesh;
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette, PaletteBuilder};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FillMode {
+    /// Only voxels the triangles pass through.
+    Surface,
+    /// Surface voxels plus every cell they enclose. Interior cells take the
+    /// color of the closest surface voxel before them along X.
+    Solid,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VoxelizeOptions {
+    /// Number of voxels along the longest side of the mesh bounds, at most
+    /// 256.
+    pub resolution: u32,
+    pub fill: FillMode,
+    /// Color of every voxel when the mesh has no vertex colors.
+    pub color: Color,
+}
+
+impl Default for VoxelizeOptions {
+    fn default() -> VoxelizeOptions {
+        VoxelizeOptions {
+            resolution: 32,
+            fill: FillMode::Surface,
+            color: Color::new(255, 255, 255, 255),
+        }
+    }
+}
+
+/// Converts an indexed triangle list into a model. The mesh bounds are
+/// scaled uniformly so their longest side spans `options.resolution`
+/// voxels, and mesh axes map directly onto model axes. With vertex colors,
+/// each voxel takes the color of the first triangle that touches it,
+/// interpolated at the voxel center projected onto that triangle.
+pub fn voxelize(positions: &[[f32; 3]], indices: &[u32], colors: Option<&[[f32; 4]]>, options: &VoxelizeOptions) -> (Model, Palette) {
+    let resolution = options.resolution.clamp(1, 256);
+    let triangles: Vec<[usize; 3]> = indices
+        .chunks(3)
+        .filter(|triangle| triangle.len() == 3 && triangle.iter().all(|&index| (index as usize) < positions.len()))
+        .map(|triangle| [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize])
+        .collect();
+
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for triangle in &triangles {
+        for &vertex in triangle {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(positions[vertex][axis]);
+                max[axis] = max[axis].max(positions[vertex][axis]);
+            }
+        }
+    }
+    if triangles.is_empty() {
+        return (Model::new(Size { x: 0, y: 0, z: 0 }), Palette::from_rgba_chunk(&[]));
+    }
+    let extent = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+    let scale = if extent > 0.0 { resolution as f32 / extent } else { 1.0 };
+    let mut dims = [0u32; 3];
+    for axis in 0..3 {
+        dims[axis] = (((max[axis] - min[axis]) * scale - 1e-4).ceil() as u32).clamp(1, resolution);
+    }
+    let cell = |x: u32, y: u32, z: u32| (x + y * dims[0] + z * dims[0] * dims[1]) as usize;
+
+    let mut palette = PaletteBuilder::new();
+    let mut cells = vec![0u8; (dims[0] * dims[1] * dims[2]) as usize];
+    for triangle in &triangles {
+        let corners = triangle.map(|vertex| {
+            let p = positions[vertex];
+            [(p[0] - min[0]) * scale, (p[1] - min[1]) * scale, (p[2] - min[2]) * scale]
+        });
+        let mut low = [0u32; 3];
+        let mut high = [0u32; 3];
+        for axis in 0..3 {
+            let (lo, hi) = corners.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p[axis]), hi.max(p[axis])));
+            low[axis] = (lo.floor().max(0.0) as u32).min(dims[axis] - 1);
+            high[axis] = (hi.floor().max(0.0) as u32).min(dims[axis] - 1);
+        }
+        for z in low[2]..=high[2] {
+            for y in low[1]..=high[1] {
+                for x in low[0]..=high[0] {
+                    let index = cell(x, y, z);
+                    let center = [x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+                    if cells[index] != 0 || !overlaps_box(&corners, center) {
+                        continue;
+                    }
+                    let color = match colors {
+                        Some(colors) if triangle.iter().all(|&vertex| vertex < colors.len()) => {
+                            let weights = barycentric(&corners, center);
+                            let mut rgba = [0.0; 4];
+                            for (weight, &vertex) in weights.iter().zip(triangle.iter()) {
+                                for channel in 0..4 {
+                                    rgba[channel] += weight * colors[vertex][channel];
+                                }
+                            }
+                            let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                            Color::new(channel(rgba[0]), channel(rgba[1]), channel(rgba[2]), channel(rgba[3]))
+                        }
+                        _ => options.color,
+                    };
+                    cells[index] = palette.index_of(color);
+                }
+            }
+        }
+    }
+
+    if options.fill == FillMode::Solid {
+        let exterior = flood_exterior(&cells, dims);
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                let mut last = 0;
+                for x in 0..dims[0] {
+                    let index = cell(x, y, z);
+                    if cells[index] != 0 {
+                        last = cells[index];
+                    } else if !exterior[index] && last != 0 {
+                        cells[index] = last;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut model = Model::new(Size { x: dims[0], y: dims[1], z: dims[2] });
+    for z in 0..dims[2] {
+        for y in 0..dims[1] {
+            for x in 0..dims[0] {
+                let c = cells[cell(x, y, z)];
+                if c != 0 {
+                    model.voxels.push(Voxel { x: x as u8, y: y as u8, z: z as u8, c });
+                }
+            }
+        }
+    }
+    (model, palette.finish())
+}
+
+/// Voxelizes the output of a mesher, using its vertex colors when present.
+pub fn voxelize_mesh(mesh: &VoxMesh, options: &VoxelizeOptions) -> (Model, Palette) {
+    let colors = if mesh.colors.len() == mesh.positions.len() { Some(&mesh.colors[..]) } else { None };
+    voxelize(&mesh.positions, &mesh.indices, colors, options)
+}
+
+/// Marks the empty cells reachable from outside the grid.
+fn flood_exterior(cells: &[u8], dims: [u32; 3]) -> Vec<bool> {
+    let mut exterior = vec![false; cells.len()];
+    let mut stack = Vec::new();
+    for z in 0..dims[2] {
+        for y in 0..dims[1] {
+            for x in 0..dims[0] {
+                let border = x == 0 || y == 0 || z == 0 || x == dims[0] - 1 || y == dims[1] - 1 || z == dims[2] - 1;
+                if border {
+                    stack.push([x, y, z]);
+                }
+            }
+        }
+    }
+    while let Some([x, y, z]) = stack.pop() {
+        let index = (x + y * dims[0] + z * dims[0] * dims[1]) as usize;
+        if exterior[index] || cells[index] != 0 {
+            continue;
+        }
+        exterior[index] = true;
+        let p = [x, y, z];
+        for axis in 0..3 {
+            if p[axis] > 0 {
+                let mut next = p;
+                next[axis] -= 1;
+                stack.push(next);
+            }
+            if p[axis] + 1 < dims[axis] {
+                let mut next = p;
+                next[axis] += 1;
+                stack.push(next);
+            }
+        }
+    }
+    exterior
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Separating axis test between a triangle and the unit cube around
+/// `center`.
+fn overlaps_box(corners: &[[f32; 3]; 3], center: [f32; 3]) -> bool {
+    const HALF: f32 = 0.5;
+    let v = corners.map(|corner| sub(corner, center));
+    let edges = [sub(v[1], v[0]), sub(v[2], v[1]), sub(v[0], v[2])];
+    let separated = |axis: [f32; 3]| {
+        let projections = v.map(|vertex| dot(vertex, axis));
+        let radius = HALF * (axis[0].abs() + axis[1].abs() + axis[2].abs());
+        let low = projections.iter().cloned().fold(f32::MAX, f32::min);
+        let high = projections.iter().cloned().fold(f32::MIN, f32::max);
+        low > radius || high < -radius
+    };
+    let units = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    if units.iter().any(|&unit| separated(unit)) {
+        return false;
+    }
+    if separated(cross(edges[0], edges[1])) {
+        return false;
+    }
+    !units.iter().any(|&unit| edges.iter().any(|&edge| separated(cross(unit, edge))))
+}
+
+/// Barycentric weights of `point` projected onto the triangle plane,
+/// clamped into the triangle.
+fn barycentric(corners: &[[f32; 3]; 3], point: [f32; 3]) -> [f32; 3] {
+    let (e0, e1, p) = (sub(corners[1], corners[0]), sub(corners[2], corners[0]), sub(point, corners[0]));
+    let (d00, d01, d11) = (dot(e0, e0), dot(e0, e1), dot(e1, e1));
+    let (d20, d21) = (dot(p, e0), dot(p, e1));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() < f32::EPSILON {
+        return [1.0 / 3.0; 3];
+    }
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    let weights = [(1.0 - v - w).max(0.0), v.max(0.0), w.max(0.0)];
+    let sum = weights[0] + weights[1] + weights[2];
+    weights.map(|weight| weight / sum)
+}
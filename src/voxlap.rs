@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt};
+use model::{Model, Size, Voxel};
+use palette::{Color, Palette, PaletteBuilder};
+
+/// A Voxlap `.kv6` or Build engine `.kvx` sprite. Both formats count Z
+/// downwards, so slices are flipped to MagicaVoxel's Z-up.
+#[derive(Clone, PartialEq, Debug)]
+pub struct VoxlapFile {
+    pub model: Model,
+    pub palette: Palette,
+    /// Rotation pivot in voxels, in the flipped model space.
+    pub pivot: [f32; 3],
+}
+
+pub fn load_kv6<P: AsRef<Path>>(path: P) -> io::Result<VoxlapFile> {
+    read_kv6(&read_file(path)?)
+}
+
+pub fn load_kvx<P: AsRef<Path>>(path: P) -> io::Result<VoxlapFile> {
+    read_kvx(&read_file(path)?)
+}
+
+/// Parses a `.kv6`: true-color surface voxels stored column by column.
+pub fn read_kv6(bytes: &[u8]) -> io::Result<VoxlapFile> {
+    if !bytes.starts_with(b"Kvxl") {
+        return Err(invalid_data("missing Kvxl file magic".to_string()));
+    }
+    let mut cursor = Cursor::new(&bytes[4..]);
+    let size = read_size(&mut cursor)?;
+    let pivot = [
+        cursor.read_f32::<LittleEndian>()?,
+        cursor.read_f32::<LittleEndian>()?,
+        cursor.read_f32::<LittleEndian>()?,
+    ];
+    let voxel_count = cursor.read_u32::<LittleEndian>()? as usize;
+    let mut colors = Vec::with_capacity(voxel_count.min(1 << 24));
+    for _ in 0..voxel_count {
+        let [b, g, r, _] = cursor.read_u32::<LittleEndian>()?.to_le_bytes();
+        let z = cursor.read_u16::<LittleEndian>()?;
+        let _visibility = cursor.read_u8()?;
+        let _normal = cursor.read_u8()?;
+        colors.push((z, Color::new(r, g, b, 255)));
+    }
+    for _ in 0..size.x {
+        cursor.read_u32::<LittleEndian>()?;
+    }
+
+    let mut palette = PaletteBuilder::new();
+    let mut model = Model::new(size);
+    let mut voxels = colors.into_iter();
+    for x in 0..size.x {
+        for y in 0..size.y {
+            let column = cursor.read_u16::<LittleEndian>()?;
+            for _ in 0..column {
+                let (z, color) = voxels.next().ok_or_else(|| invalid_data("column lengths exceed the voxel count".to_string()))?;
+                if z as u32 >= size.z {
+                    return Err(invalid_data(format!("voxel z {} outside a depth of {}", z, size.z)));
+                }
+                model.voxels.push(Voxel { x: x as u8, y: y as u8, z: (size.z - 1 - z as u32) as u8, c: palette.index_of(color) });
+            }
+        }
+    }
+    Ok(VoxlapFile { model, palette: palette.finish(), pivot: [pivot[0], pivot[1], size.z as f32 - pivot[2]] })
+}
+
+/// Parses the full-resolution level of a `.kvx`: run-length slabs of
+/// palette indices, with a 6-bit-per-channel palette in the last 768 bytes.
+pub fn read_kvx(bytes: &[u8]) -> io::Result<VoxlapFile> {
+    if bytes.len() < 4 + 24 + 768 {
+        return Err(invalid_data("file too short for a KVX".to_string()));
+    }
+    let colors: Vec<Color> = bytes[bytes.len() - 768..]
+        .chunks(3)
+        .map(|rgb| Color::new(rgb[0] << 2 | rgb[0] >> 4, rgb[1] << 2 | rgb[1] >> 4, rgb[2] << 2 | rgb[2] >> 4, 255))
+        .collect();
+
+    let length = (&bytes[..4]).read_u32::<LittleEndian>()? as usize;
+    let level = bytes.get(4..4 + length).ok_or_else(|| invalid_data("truncated KVX level".to_string()))?;
+    let mut cursor = Cursor::new(level);
+    let size = read_size(&mut cursor)?;
+    let pivot = [
+        cursor.read_i32::<LittleEndian>()? as f32 / 256.0,
+        cursor.read_i32::<LittleEndian>()? as f32 / 256.0,
+        cursor.read_i32::<LittleEndian>()? as f32 / 256.0,
+    ];
+    // Offsets count from the start of the x offset table.
+    let base = cursor.position() as usize;
+    let mut x_offsets = Vec::with_capacity(size.x as usize + 1);
+    for _ in 0..=size.x {
+        x_offsets.push(cursor.read_u32::<LittleEndian>()? as usize);
+    }
+    let mut xy_offsets = Vec::with_capacity((size.x * (size.y + 1)) as usize);
+    for _ in 0..size.x * (size.y + 1) {
+        xy_offsets.push(cursor.read_u16::<LittleEndian>()? as usize);
+    }
+
+    let mut palette = PaletteBuilder::new();
+    let mut model = Model::new(size);
+    for (x, &x_offset) in x_offsets.iter().take(size.x as usize).enumerate() {
+        for y in 0..size.y as usize {
+            let column = x * (size.y as usize + 1) + y;
+            let start = base + x_offset + xy_offsets[column];
+            let end = base + x_offset + xy_offsets[column + 1];
+            let mut slabs = level.get(start..end).ok_or_else(|| invalid_data(format!("column {},{} out of range", x, y)))?;
+            while slabs.len() >= 3 {
+                let (top, length) = (slabs[0] as u32, slabs[1] as usize);
+                let indices = slabs.get(3..3 + length).ok_or_else(|| invalid_data(format!("truncated slab in column {},{}", x, y)))?;
+                for (i, &index) in indices.iter().enumerate() {
+                    let z = top + i as u32;
+                    if z >= size.z {
+                        return Err(invalid_data(format!("voxel z {} outside a depth of {}", z, size.z)));
+                    }
+                    let c = palette.index_of(colors[index as usize]);
+                    model.voxels.push(Voxel { x: x as u8, y: y as u8, z: (size.z - 1 - z) as u8, c });
+                }
+                slabs = &slabs[3 + length..];
+            }
+        }
+    }
+    Ok(VoxlapFile { model, palette: palette.finish(), pivot: [pivot[0], pivot[1], size.z as f32 - pivot[2]] })
+}
+
+fn read_size(cursor: &mut Cursor<&[u8]>) -> io::Result<Size> {
+    let size = Size {
+        x: cursor.read_u32::<LittleEndian>()?,
+        y: cursor.read_u32::<LittleEndian>()?,
+        z: cursor.read_u32::<LittleEndian>()?,
+    };
+    if size.x > 256 || size.y > 256 || size.z > 256 {
+        return Err(invalid_data(format!("{:?} exceeds 256 voxels per axis", size)));
+    }
+    Ok(size)
+}
+
+fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
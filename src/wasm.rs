@@ -0,0 +1,97 @@
+use wasm_bindgen::prelude::*;
+use model::{Model, Size};
+use palette::Palette;
+use vox_loader::VoxLoader;
+
+/// A parsed `.vox` file as seen from JavaScript.
+#[wasm_bindgen(js_name = VoxFile)]
+pub struct JsVoxFile {
+    version: u32,
+    models: Vec<Model>,
+    palette: Palette,
+    warnings: Vec<String>,
+}
+
+#[wasm_bindgen(js_class = VoxFile)]
+impl JsVoxFile {
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn models(&self) -> Vec<JsModel> {
+        self.models
+            .iter()
+            .map(|model| JsModel {
+                size: model.size,
+                voxels: model.voxels.iter().flat_map(|v| [v.x, v.y, v.z, v.c]).collect(),
+            })
+            .collect()
+    }
+
+    /// The 256 palette entries as RGBA bytes, index 0 first.
+    #[wasm_bindgen(getter)]
+    pub fn palette(&self) -> Vec<u8> {
+        (0..=255u8)
+            .map(|index| self.palette.get(index))
+            .flat_map(|color| [color.r, color.g, color.b, color.a])
+            .collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings.clone()
+    }
+}
+
+/// One model, with its voxels packed as `x, y, z, colorIndex` bytes.
+#[wasm_bindgen(js_name = Model)]
+pub struct JsModel {
+    size: Size,
+    voxels: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = Model)]
+impl JsModel {
+    #[wasm_bindgen(getter, js_name = sizeX)]
+    pub fn size_x(&self) -> u32 {
+        self.size.x
+    }
+
+    #[wasm_bindgen(getter, js_name = sizeY)]
+    pub fn size_y(&self) -> u32 {
+        self.size.y
+    }
+
+    #[wasm_bindgen(getter, js_name = sizeZ)]
+    pub fn size_z(&self) -> u32 {
+        self.size.z
+    }
+
+    #[wasm_bindgen(getter, js_name = voxelCount)]
+    pub fn voxel_count(&self) -> usize {
+        self.voxels.len() / 4
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn voxels(&self) -> Vec<u8> {
+        self.voxels.clone()
+    }
+}
+
+/// Parses the bytes of a `.vox` file, throwing if they do not start with the
+/// `VOX ` magic.
+#[wasm_bindgen]
+pub fn parse(bytes: &[u8]) -> Result<JsVoxFile, JsError> {
+    if !bytes.starts_with(b"VOX ") {
+        return Err(JsError::new("not a .vox file"));
+    }
+    let loader = VoxLoader::from_bytes(bytes);
+    Ok(JsVoxFile {
+        version: loader.version(),
+        models: loader.models().to_vec(),
+        palette: loader.palette.clone(),
+        warnings: loader.warnings().to_vec(),
+    })
+}
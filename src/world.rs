@@ -0,0 +1,235 @@
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+use std::slice;
+use std::vec::Vec;
+use grid::VoxGrid;
+use model::{Model, Size, Voxel};
+use scene::{Instance, Rotation, SceneGraph};
+
+/// What `SceneGraph::flatten` does with scenes wider than a model can be,
+/// 256 voxels per axis.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlattenBounds {
+    /// Keeps the 256-voxel box from the scene's minimum corner and drops
+    /// the rest.
+    Clip,
+    /// Keeps the 256-voxel box around the scene's center.
+    ClipCentered,
+    /// Shrinks the scene by the smallest whole factor that fits, each
+    /// block becoming one voxel of its most common color.
+    Downsample,
+}
+
+/// Models tiled on a grid of equal chunks, for terrain and other scenes
+/// wider than the 256 voxels a model can span. Chunk `[i, j, k]` covers
+/// world cells from `[i, j, k]` times the chunk size; cells of a chunk's
+/// model beyond the chunk size are ignored.
+#[derive(Clone, PartialEq, Debug)]
+pub struct World {
+    chunk_size: Size,
+    chunks: BTreeMap<[i32; 3], Model>,
+}
+
+impl World {
+    /// An empty world. Each side of `chunk_size` is clamped to 1 to 256.
+    pub fn new(chunk_size: Size) -> World {
+        let side = |side: u32| side.clamp(1, 256);
+        World { chunk_size: Size { x: side(chunk_size.x), y: side(chunk_size.y), z: side(chunk_size.z) }, chunks: BTreeMap::new() }
+    }
+
+    /// Reassembles the voxels a scene shows at frame 0, as
+    /// `SceneGraph::iter_world_voxels` places them, into chunks. Where
+    /// instances overlap the later one wins.
+    pub fn from_scene(scene: &SceneGraph, models: &[Model], chunk_size: Size) -> World {
+        let mut world = World::new(chunk_size);
+        let mut grids: BTreeMap<[i32; 3], VoxGrid> = BTreeMap::new();
+        for (position, c, _) in scene.iter_world_voxels(models) {
+            let (chunk, local) = world.locate(position);
+            let grid = grids.entry(chunk).or_insert_with(|| VoxGrid::new(world.chunk_size));
+            grid.set(local[0] as i32, local[1] as i32, local[2] as i32, c);
+        }
+        world.chunks = grids.into_iter().map(|(chunk, grid)| (chunk, grid.to_model())).collect();
+        world
+    }
+
+    pub fn chunk_size(&self) -> Size {
+        self.chunk_size
+    }
+
+    /// Places `model` as chunk `coords`, returning the model it replaces.
+    pub fn insert(&mut self, coords: [i32; 3], model: Model) -> Option<Model> {
+        self.chunks.insert(coords, model)
+    }
+
+    pub fn remove(&mut self, coords: [i32; 3]) -> Option<Model> {
+        self.chunks.remove(&coords)
+    }
+
+    pub fn chunk(&self, coords: [i32; 3]) -> Option<&Model> {
+        self.chunks.get(&coords)
+    }
+
+    /// The chunks in coordinate order.
+    pub fn chunks(&self) -> btree_map::Iter<'_, [i32; 3], Model> {
+        self.chunks.iter()
+    }
+
+    /// The world cell at the minimum corner of chunk `coords`.
+    pub fn chunk_origin(&self, coords: [i32; 3]) -> [i32; 3] {
+        let size = [self.chunk_size.x, self.chunk_size.y, self.chunk_size.z];
+        [0, 1, 2].map(|axis| coords[axis] * size[axis] as i32)
+    }
+
+    /// The chunk holding a world cell, and the cell's position within it.
+    pub fn locate(&self, position: [i32; 3]) -> ([i32; 3], [u32; 3]) {
+        let size = [self.chunk_size.x, self.chunk_size.y, self.chunk_size.z];
+        let chunk = [0, 1, 2].map(|axis| position[axis].div_euclid(size[axis] as i32));
+        let local = [0, 1, 2].map(|axis| position[axis].rem_euclid(size[axis] as i32) as u32);
+        (chunk, local)
+    }
+
+    /// The color index at a world cell, or `None` if it is empty or its
+    /// chunk is missing.
+    pub fn voxel_at(&self, position: [i32; 3]) -> Option<u8> {
+        let (chunk, local) = self.locate(position);
+        self.chunks.get(&chunk)?.voxel_at(local[0], local[1], local[2])
+    }
+
+    /// Sets the color index at a world cell, adding an empty chunk when
+    /// there is none; index 0 clears the cell.
+    pub fn set_voxel(&mut self, position: [i32; 3], c: u8) {
+        let (chunk, local) = self.locate(position);
+        let size = self.chunk_size;
+        if c == 0 && !self.chunks.contains_key(&chunk) {
+            return;
+        }
+        let model = self.chunks.entry(chunk).or_insert_with(|| Model::new(size));
+        if model.size.x <= local[0] || model.size.y <= local[1] || model.size.z <= local[2] {
+            model.size = Size { x: model.size.x.max(local[0] + 1), y: model.size.y.max(local[1] + 1), z: model.size.z.max(local[2] + 1) };
+            model.invalidate_index();
+        }
+        model.set_voxel(local[0], local[1], local[2], c);
+    }
+}
+
+/// Iterator over the voxels of a scene in world space, from
+/// `SceneGraph::iter_world_voxels`.
+pub struct WorldVoxels<'a> {
+    models: &'a [Model],
+    instances: Vec<Instance>,
+    /// Whether models sit on their pivot, as under a scene graph, or at
+    /// their own coordinates.
+    centered: bool,
+    current: usize,
+    voxels: slice::Iter<'a, Voxel>,
+}
+
+impl<'a> Iterator for WorldVoxels<'a> {
+    type Item = ([i32; 3], u8, u32);
+
+    fn next(&mut self) -> Option<([i32; 3], u8, u32)> {
+        loop {
+            if let Some(voxel) = self.voxels.next() {
+                let instance = &self.instances[self.current];
+                let model = &self.models[instance.model as usize];
+                return Some((world_position(instance, model, voxel, self.centered), voxel.c, instance.model));
+            }
+            self.current += 1;
+            let instance = self.instances.get(self.current)?;
+            self.voxels = self.models[instance.model as usize].voxels.iter();
+        }
+    }
+}
+
+/// Where a voxel of `model` lands when placed by `instance`. MagicaVoxel
+/// centers a model on its pivot, `size / 2` rounded down, before rotating
+/// it, so the cell is rotated about its center in doubled coordinates to
+/// stay on the integer grid.
+pub(crate) fn world_position(instance: &Instance, model: &Model, voxel: &Voxel, centered: bool) -> [i32; 3] {
+    if !centered {
+        return [voxel.x as i32, voxel.y as i32, voxel.z as i32];
+    }
+    let pivot = [model.size.x / 2, model.size.y / 2, model.size.z / 2];
+    let doubled = [
+        2 * voxel.x as i32 + 1 - 2 * pivot[0] as i32,
+        2 * voxel.y as i32 + 1 - 2 * pivot[1] as i32,
+        2 * voxel.z as i32 + 1 - 2 * pivot[2] as i32,
+    ];
+    let rotated = instance.rotation.apply(doubled);
+    [0, 1, 2].map(|axis| instance.translation[axis] + (rotated[axis] - 1) / 2)
+}
+
+impl SceneGraph {
+    /// Iterates `(world_position, color_index, model_id)` for every voxel
+    /// the scene shows at frame 0, applying the transforms from the root
+    /// down and skipping hidden nodes and layers. A graph without nodes, as
+    /// in files from before scenes, shows each model once at its own
+    /// coordinates.
+    pub fn iter_world_voxels<'a>(&self, models: &'a [Model]) -> WorldVoxels<'a> {
+        let centered = !self.is_empty();
+        let instances: Vec<Instance> = if centered {
+            self.frame(0).into_iter().filter(|instance| (instance.model as usize) < models.len()).collect()
+        } else {
+            (0..models.len() as u32)
+                .map(|model| Instance { model, shape: 0, rotation: Rotation::IDENTITY, translation: [0; 3] })
+                .collect()
+        };
+        let voxels = instances.first().map_or([].iter(), |instance| models[instance.model as usize].voxels.iter());
+        WorldVoxels { models, instances, centered, current: 0, voxels }
+    }
+}
+
+impl SceneGraph {
+    /// Bakes every voxel the scene shows at frame 0 into one model, as
+    /// `iter_world_voxels` places them, for static geometry that should
+    /// become a single mesh. The model's origin is the scene's minimum
+    /// corner; where instances overlap the later one wins.
+    pub fn flatten(&self, models: &[Model], bounds: FlattenBounds) -> Model {
+        let voxels: Vec<([i32; 3], u8)> = self.iter_world_voxels(models).map(|(p, c, _)| (p, c)).collect();
+        let (min, max) = match voxels.first() {
+            Some(&(first, _)) => voxels.iter().fold((first, first), |(min, max), &(p, _)| {
+                ([0, 1, 2].map(|axis| min[axis].min(p[axis])), [0, 1, 2].map(|axis| max[axis].max(p[axis])))
+            }),
+            None => return Model::new(Size { x: 0, y: 0, z: 0 }),
+        };
+        let extent = [0, 1, 2].map(|axis| (max[axis] - min[axis] + 1) as u32);
+        let factor = match bounds {
+            FlattenBounds::Downsample => extent.iter().map(|&side| side.div_ceil(256)).max().unwrap_or(1),
+            _ => 1,
+        };
+        let origin = match bounds {
+            FlattenBounds::ClipCentered => [0, 1, 2].map(|axis| min[axis] + (extent[axis].saturating_sub(256) / 2) as i32),
+            _ => min,
+        };
+        let side = |axis: usize| extent[axis].div_ceil(factor).min(256);
+        let mut grid = VoxGrid::new(Size { x: side(0), y: side(1), z: side(2) });
+        if factor == 1 {
+            for (p, c) in voxels {
+                grid.set(p[0] - origin[0], p[1] - origin[1], p[2] - origin[2], c);
+            }
+            return grid.to_model();
+        }
+        let mut blocks: BTreeMap<[u32; 3], BTreeMap<u8, u32>> = BTreeMap::new();
+        for (p, c) in voxels {
+            let block = [0, 1, 2].map(|axis| (p[axis] - origin[axis]) as u32 / factor);
+            *blocks.entry(block).or_default().entry(c).or_default() += 1;
+        }
+        for (block, counts) in blocks {
+            let c = counts.iter().fold((0, 0), |best, (&c, &count)| if count > best.1 { (c, count) } else { best }).0;
+            grid.set(block[0] as i32, block[1] as i32, block[2] as i32, c);
+        }
+        grid.to_model()
+    }
+}
+
+impl SceneGraph {
+    /// The instances of frame 0 grouped by model id, so engines can draw
+    /// repeated models as one instanced mesh.
+    pub fn instances(&self) -> BTreeMap<u32, Vec<Instance>> {
+        let mut groups: BTreeMap<u32, Vec<Instance>> = BTreeMap::new();
+        for instance in self.frame(0) {
+            groups.entry(instance.model).or_default().push(instance);
+        }
+        groups
+    }
+}
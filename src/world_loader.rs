@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use model::{Model, Size};
+use palette::Palette;
+use vox_index::VoxIndex;
+use vox_loader::VoxLoader;
+use world::World;
+
+/// How tile files are named after their chunk coordinates: a pattern such
+/// as `chunk_{x}_{y}.vox`, where `{x}`, `{y}` and `{z}` stand for signed
+/// integers. Placeholders left out of the pattern are 0.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NamingScheme {
+    pattern: String,
+}
+
+impl NamingScheme {
+    pub fn new(pattern: &str) -> NamingScheme {
+        NamingScheme { pattern: pattern.to_string() }
+    }
+
+    /// The file name of the tile at `coords`.
+    pub fn name(&self, coords: [i32; 3]) -> String {
+        self.pattern
+            .replace("{x}", &coords[0].to_string())
+            .replace("{y}", &coords[1].to_string())
+            .replace("{z}", &coords[2].to_string())
+    }
+
+    /// The chunk coordinates a file name stands for, or `None` if it does
+    /// not follow the pattern.
+    pub fn parse(&self, name: &str) -> Option<[i32; 3]> {
+        let mut coords = [0; 3];
+        let mut pattern = self.pattern.as_str();
+        let mut name = name;
+        while let Some(start) = pattern.find('{') {
+            name = name.strip_prefix(&pattern[..start])?;
+            let axis = match pattern.get(start..start + 3)? {
+                "{x}" => 0,
+                "{y}" => 1,
+                "{z}" => 2,
+                _ => return None,
+            };
+            pattern = &pattern[start + 3..];
+            let digits = name.char_indices().take_while(|&(i, c)| c.is_ascii_digit() || (i == 0 && c == '-')).count();
+            coords[axis] = name[..digits].parse().ok()?;
+            name = &name[digits..];
+        }
+        if name == pattern {
+            Some(coords)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for NamingScheme {
+    fn default() -> NamingScheme {
+        NamingScheme::new("chunk_{x}_{y}.vox")
+    }
+}
+
+/// The tiles one `WorldLoader::update` loaded and unloaded, so callers can
+/// build and drop their meshes.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct StreamEvents {
+    pub loaded: Vec<[i32; 3]>,
+    pub unloaded: Vec<[i32; 3]>,
+}
+
+/// Streams a directory of `.vox` tiles into a `World`, keeping only the
+/// tiles around a focus point loaded. Each tile becomes one chunk from its
+/// first model; the chunk size is the size of the first tile found.
+pub struct WorldLoader {
+    dir: PathBuf,
+    naming: NamingScheme,
+    available: BTreeSet<[i32; 3]>,
+    world: World,
+    palette: Option<Palette>,
+    /// Tiles within this many chunks of the focus are loaded.
+    pub load_radius: u32,
+    /// Loaded tiles are dropped once they are more than this many chunks
+    /// from the focus. Keep it above `load_radius` so walking back and
+    /// forth over a chunk border does not reload tiles.
+    pub unload_radius: u32,
+}
+
+impl WorldLoader {
+    /// Lists the tiles in `path` whose names follow `naming`, without
+    /// loading any of them.
+    pub fn open_dir<P: AsRef<Path>>(path: P, naming: NamingScheme) -> io::Result<WorldLoader> {
+        let dir = path.as_ref().to_path_buf();
+        let mut available = BTreeSet::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if let Some(coords) = entry.file_name().to_str().and_then(|name| naming.parse(name)) {
+                available.insert(coords);
+            }
+        }
+        let chunk_size = match available.iter().next() {
+            Some(&first) => VoxIndex::open(dir.join(naming.name(first)))?
+                .models()
+                .first()
+                .map_or(Size { x: 256, y: 256, z: 256 }, |entry| entry.size),
+            None => Size { x: 256, y: 256, z: 256 },
+        };
+        Ok(WorldLoader { dir, naming, available, world: World::new(chunk_size), palette: None, load_radius: 1, unload_radius: 2 })
+    }
+
+    /// The tiles the directory holds, loaded or not.
+    pub fn available(&self) -> &BTreeSet<[i32; 3]> {
+        &self.available
+    }
+
+    /// The loaded tiles.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// The palette of the first tile loaded.
+    pub fn palette(&self) -> Option<&Palette> {
+        self.palette.as_ref()
+    }
+
+    pub fn is_loaded(&self, coords: [i32; 3]) -> bool {
+        self.world.chunk(coords).is_some()
+    }
+
+    /// Loads the tiles within `load_radius` chunks of the world cell
+    /// `focus` and unloads those beyond `unload_radius`, measuring by the
+    /// largest per-axis distance between chunks.
+    pub fn update(&mut self, focus: [i32; 3]) -> io::Result<StreamEvents> {
+        let (center, _) = self.world.locate(focus);
+        let distance = |coords: [i32; 3]| (0..3).map(|axis| coords[axis].abs_diff(center[axis])).max().unwrap_or(0);
+        let mut events = StreamEvents::default();
+        let loaded: Vec<[i32; 3]> = self.world.chunks().map(|(&coords, _)| coords).collect();
+        for coords in loaded {
+            if distance(coords) > self.unload_radius {
+                self.world.remove(coords);
+                events.unloaded.push(coords);
+            }
+        }
+        let wanted: Vec<[i32; 3]> = self
+            .available
+            .iter()
+            .filter(|&&coords| distance(coords) <= self.load_radius && !self.is_loaded(coords))
+            .cloned()
+            .collect();
+        for coords in wanted {
+            self.load(coords)?;
+            events.loaded.push(coords);
+        }
+        Ok(events)
+    }
+
+    /// Loads one tile now, whatever the focus; it is unloaded by the next
+    /// `update` that finds it out of range. Tiles without a model load as
+    /// empty chunks.
+    pub fn load(&mut self, coords: [i32; 3]) -> io::Result<()> {
+        let vl = VoxLoader::from_bytes(&fs::read(self.dir.join(self.naming.name(coords)))?);
+        if self.palette.is_none() {
+            self.palette = Some(vl.palette.clone());
+        }
+        let model = vl.models().first().cloned().unwrap_or_else(|| Model::new(self.world.chunk_size()));
+        self.world.insert(coords, model);
+        Ok(())
+    }
+}
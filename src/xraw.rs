@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use model::{Model, Size, Voxel};
+use palette::{median_cut, Color, Palette, PaletteBuilder};
+
+/// A true-color voxel volume as stored in MagicaVoxel's `.xraw` format.
+/// Cells are stored x-fastest, then y, then z, with zero alpha meaning
+/// empty.
+#[derive(Clone, PartialEq, Debug)]
+pub struct XrawVolume {
+    pub size: Size,
+    pub colors: Vec<Color>,
+}
+
+impl XrawVolume {
+    /// An empty volume. Panics if the cell count overflows a `usize`.
+    pub fn new(size: Size) -> XrawVolume {
+        let count = size.cell_count().expect("XRAW volume size overflows usize");
+        XrawVolume { size, colors: vec![Color::default(); count] }
+    }
+
+    /// Resolves the voxels of `model` through `palette` into a volume.
+    pub fn from_model(model: &Model, palette: &Palette) -> XrawVolume {
+        let mut volume = XrawVolume::new(model.size);
+        for (x, y, z, color) in model.iter_colored(palette) {
+            if let Some(index) = volume.index(x as u32, y as u32, z as u32) {
+                volume.colors[index] = Color { a: 255, ..color };
+            }
+        }
+        volume
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> Option<usize> {
+        if x >= self.size.x || y >= self.size.y || z >= self.size.z {
+            return None;
+        }
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        Some(x + y * self.size.x as usize + z * self.size.x as usize * self.size.y as usize)
+    }
+
+    /// The color at a cell, or `None` if it is empty or outside the volume.
+    pub fn get(&self, x: u32, y: u32, z: u32) -> Option<Color> {
+        self.index(x, y, z).map(|index| self.colors[index]).filter(|color| color.a != 0)
+    }
+
+    /// Converts the volume into a paletted model. Volumes with at most 255
+    /// distinct colors keep them exactly; others are reduced with a median
+    /// cut and every voxel takes the closest remaining color.
+    pub fn quantize(&self) -> io::Result<(Model, Palette)> {
+        if self.size.x > 256 || self.size.y > 256 || self.size.z > 256 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} exceeds 256 voxels per axis", self.size)));
+        }
+        let solid: Vec<Color> = self.colors.iter().cloned().filter(|color| color.a != 0).collect();
+        let distinct: HashSet<Color> = solid.iter().cloned().collect();
+        let mut palette = if distinct.len() <= 255 {
+            PaletteBuilder::new()
+        } else {
+            PaletteBuilder::with_colors(&median_cut(&solid, 255))
+        };
+        let mut model = Model::new(self.size);
+        for z in 0..self.size.z {
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    if let Some(color) = self.get(x, y, z) {
+                        model.voxels.push(Voxel { x: x as u8, y: y as u8, z: z as u8, c: palette.index_of(color) });
+                    }
+                }
+            }
+        }
+        Ok((model, palette.finish()))
+    }
+
+    /// Encodes the volume as an unpaletted 8-bit RGBA `.xraw`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = b"XRAW".to_vec();
+        data.extend_from_slice(&[0, 4, 8, 0]);
+        for &word in &[self.size.x, self.size.y, self.size.z, 0] {
+            data.write_u32::<LittleEndian>(word).unwrap();
+        }
+        for color in &self.colors {
+            data.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        data
+    }
+}
+
+pub fn export_xraw(model: &Model, palette: &Palette) -> Vec<u8> {
+    XrawVolume::from_model(model, palette).to_bytes()
+}
+
+pub fn write_xraw<P: AsRef<Path>>(model: &Model, palette: &Palette, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(&export_xraw(model, palette))
+}
+
+pub fn load_xraw<P: AsRef<Path>>(path: P) -> io::Result<XrawVolume> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    read_xraw(&bytes)
+}
+
+/// Parses a `.xraw` with unsigned integer or float channels of 8, 16 or 32
+/// bits, either stored per voxel or through an 8 or 16-bit palette. Index 0
+/// of an 8-bit palette and the all-ones index of a 16-bit one are empty.
+pub fn read_xraw(bytes: &[u8]) -> io::Result<XrawVolume> {
+    if !bytes.starts_with(b"XRAW") {
+        return Err(invalid_data("missing XRAW file magic".to_string()));
+    }
+    let mut cursor = Cursor::new(&bytes[4..]);
+    let data_type = cursor.read_u8()?;
+    let channels = cursor.read_u8()?;
+    let channel_bits = cursor.read_u8()?;
+    let index_bits = cursor.read_u8()?;
+    let size = Size {
+        x: cursor.read_u32::<LittleEndian>()?,
+        y: cursor.read_u32::<LittleEndian>()?,
+        z: cursor.read_u32::<LittleEndian>()?,
+    };
+    let palette_size = cursor.read_u32::<LittleEndian>()?;
+    if !(1..=4).contains(&channels) {
+        return Err(invalid_data(format!("unsupported channel count {}", channels)));
+    }
+    match (data_type, channel_bits) {
+        (0, 8) | (0, 16) | (0, 32) | (2, 32) => {}
+        _ => return Err(invalid_data(format!("unsupported channel type {} with {} bits", data_type, channel_bits))),
+    }
+    let count = match size.cell_count() {
+        Some(count) if count <= bytes.len() => count,
+        _ => return Err(invalid_data(format!("{:?} does not fit in the file", size))),
+    };
+
+    let mut volume = XrawVolume::new(size);
+    match index_bits {
+        0 => {
+            for color in volume.colors.iter_mut() {
+                *color = read_color(&mut cursor, data_type, channels, channel_bits)?;
+            }
+        }
+        8 | 16 => {
+            let mut indices = Vec::with_capacity(count);
+            for _ in 0..count {
+                indices.push(match index_bits {
+                    8 => cursor.read_u8()? as u32,
+                    _ => cursor.read_u16::<LittleEndian>()? as u32,
+                });
+            }
+            let mut colors = Vec::with_capacity(palette_size.min(1 << 16) as usize);
+            for _ in 0..palette_size {
+                colors.push(read_color(&mut cursor, data_type, channels, channel_bits)?);
+            }
+            let empty = if index_bits == 8 { 0 } else { 0xffff };
+            for (color, &index) in volume.colors.iter_mut().zip(indices.iter()) {
+                if index != empty {
+                    *color = colors.get(index as usize).cloned().unwrap_or_default();
+                }
+            }
+        }
+        _ => return Err(invalid_data(format!("unsupported index size of {} bits", index_bits))),
+    }
+    Ok(volume)
+}
+
+/// Reads one color, scaling channels to 8 bits. Missing channels are zero,
+/// except alpha which is opaque.
+fn read_color(cursor: &mut Cursor<&[u8]>, data_type: u8, channels: u8, bits: u8) -> io::Result<Color> {
+    let mut rgba = [0, 0, 0, 255];
+    for value in rgba.iter_mut().take(channels as usize) {
+        *value = match (data_type, bits) {
+            (0, 8) => cursor.read_u8()?,
+            (0, 16) => (cursor.read_u16::<LittleEndian>()? >> 8) as u8,
+            (0, _) => (cursor.read_u32::<LittleEndian>()? >> 24) as u8,
+            _ => (cursor.read_f32::<LittleEndian>()?.clamp(0.0, 1.0) * 255.0).round() as u8,
+        };
+    }
+    Ok(Color::new(rgba[0], rgba[1], rgba[2], rgba[3]))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}